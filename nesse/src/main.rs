@@ -1,11 +1,129 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use nesse_lib::system::nes::nsf::player::{NsfPlayer, DEFAULT_TRACK_DURATION};
 use nesse_lib::system::nes::NES;
 
 
 fn main() {
     env_logger::init();
 
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let recording = Recording::from_args(&mut args).unwrap();
+    if args.first().map(String::as_str) == Some("nsf") {
+        play_nsf(&args[1..], &recording).unwrap();
+        return;
+    }
+
+    run_rom(&args, &recording).unwrap();
+}
+
+/// `--wav <file>` records the audio to a WAV file, `--stems` also renders every channel to its own file next to it.
+/// Stems cover the APU channels and the expansion audio of FDS games and of NSF tunes using the VRC6, VRC7,
+/// FDS, MMC5, N163 or Sunsoft 5B chips.
+struct Recording {
+    wav: Option<String>,
+    stems: bool,
+}
+
+impl Recording {
+    /// Takes the recording flags out of the arguments
+    fn from_args(args: &mut Vec<String>) -> anyhow::Result<Self> {
+        let mut recording = Recording { wav: None, stems: false };
+        if let Some(index) = args.iter().position(|arg| arg == "--wav") {
+            if index + 1 >= args.len() {
+                anyhow::bail!("--wav needs an output file");
+            }
+            recording.wav = Some(args.remove(index + 1));
+            args.remove(index);
+        }
+        if let Some(index) = args.iter().position(|arg| arg == "--stems") {
+            if recording.wav.is_none() {
+                anyhow::bail!("--stems needs --wav <file> to name the stems after");
+            }
+            args.remove(index);
+            recording.stems = true;
+        }
+        Ok(recording)
+    }
+
+    fn start(&self, nes: &mut NES) -> anyhow::Result<()> {
+        if let Some(path) = &self.wav {
+            nes.start_recording(path, self.stems)?;
+            println!("Recording audio to {path}");
+        }
+        Ok(())
+    }
+}
+
+/// `nesse [--wav <file> [--stems]] [rom] [frames]` runs a ROM, for the given amount of frames or forever
+fn run_rom(args: &[String], recording: &Recording) -> anyhow::Result<()> {
     let mut nes_emu = NES::new();
 
-    nes_emu.insert_rom(&"nesse_lib/tests/test_ines/Balloon Fight (USA).nes").expect("Could not load ROM");
-    nes_emu.run().unwrap();
-}
\ No newline at end of file
+    let rom = args.first().map_or("nesse_lib/tests/test_ines/Balloon Fight (USA).nes", String::as_str);
+    nes_emu.insert_rom(&rom).expect("Could not load ROM");
+    recording.start(&mut nes_emu)?;
+
+    let Some(frames) = args.get(1) else {
+        return nes_emu.run();
+    };
+
+    nes_emu.reset()?;
+    for _ in 0..frames.parse::<u64>()? {
+        nes_emu.next_frame()?;
+    }
+    nes_emu.stop_recording()
+}
+
+/// `nesse nsf [--wav <file> [--stems]] <file> [track] [output]` lists the tracks of a tune and renders one of them
+/// as raw 32-bit float mono PCM, and/or as WAV
+fn play_nsf(args: &[String], recording: &Recording) -> anyhow::Result<()> {
+    let Some(path) = args.first() else {
+        anyhow::bail!("Usage: nesse nsf [--wav <file> [--stems]] <file> [track] [output]");
+    };
+
+    let mut player = NsfPlayer::new(path)?;
+    println!("{} - {} ({})", player.nsf.artist, player.nsf.title, player.nsf.copyright);
+    for track in player.playlist() {
+        let title = player.track_title(track).unwrap_or("untitled");
+        match player.track_duration(track) {
+            Some(duration) => println!("{:3}: {title} [{}s]", track + 1, duration.as_secs()),
+            None => println!("{:3}: {title}", track + 1),
+        }
+    }
+
+    let output = args.get(2);
+    if output.is_none() && recording.wav.is_none() {
+        return Ok(());
+    }
+
+    // Tracks are numbered from 1 on the command line
+    let track = match args.get(1) {
+        Some(track) => track.parse::<u8>()?.saturating_sub(1),
+        None => player.track(),
+    };
+    player.select_track(track)?;
+    let duration = player.track_duration(track).unwrap_or(DEFAULT_TRACK_DURATION) + player.track_fade(track).unwrap_or_default();
+    println!("Rendering track {} at {} Hz", track + 1, player.sample_rate());
+    recording.start(&mut player.nes)?;
+
+    let mut writer = match output {
+        Some(output) => Some(BufWriter::new(File::create(output)?)),
+        None => None,
+    };
+    let mut buffer = vec![0.0; player.sample_rate() as usize / 10];
+    while player.elapsed() < duration {
+        player.render(&mut buffer)?;
+        if let Some(writer) = &mut writer {
+            for sample in &buffer {
+                writer.write_all(&sample.to_le_bytes())?;
+            }
+        }
+    }
+    if let Some(writer) = &mut writer {
+        writer.flush()?;
+    }
+    player.nes.stop_recording()?;
+
+    Ok(())
+}
@@ -1,12 +1,24 @@
+use std::fs;
 use std::path::Path;
 use std::fmt::Debug;
 use std::thread::sleep;
 use std::time::Duration;
 use log::{info, trace};
+use crate::system::nes::apu::recorder::AudioRecorder;
+use crate::system::nes::input::controller::Buttons;
 use crate::system::nes::cpu::{get_mnemonic, get_opcode_size};
+use crate::system::nes::database::GameDatabase;
+use crate::system::nes::file::NESFile;
+use crate::system::nes::input::data_recorder::DataRecorder;
+use crate::system::nes::input::family_keyboard::{FamilyKeyboard, Key};
+use crate::system::nes::input::{InputDevice, InputPort};
 use crate::system::nes::iobus::IOBus;
 use crate::system::nes::loader::NESLoader;
+use crate::system::nes::mapper::fds::{BIOS_SIZE, FDS};
 use crate::system::nes::opcodes::OPCODES;
+use crate::system::nes::region::RegionSetting;
+use crate::system::nes::state::StateFile;
+use crate::system::nes::wav::sample_to_i16;
 
 pub mod iobus;
 pub mod cpu;
@@ -16,26 +28,57 @@ pub mod apu;
 pub mod opcodes;
 pub mod mnemonics;
 pub mod loader;
-mod file;
-mod debugger;
+pub mod patch;
+pub mod database;
+pub mod unif;
+pub mod nsf;
+pub mod mapper;
+pub mod file;
+pub mod region;
+pub mod debugger;
+pub mod wav;
+pub mod input;
+pub mod state;
 
-const CPU_TICK_COUNT: u32 = 1_789_773;
-const CYCLES_PER_FRAME_NTSC: u16 = (CPU_TICK_COUNT / 60) as u16;
+const INTERRUPT_CYCLES: u8 = 7;
+const STATE_SECTIONS: [&[u8; 4]; 7] = [b"CPU ", b"RAM ", b"PPU ", b"APU ", b"BUS ", b"CART", b"INPT"];
 pub struct NES {
-    cycles_left: u16,
-    cycles_per_frame: u16,
     pub bus: IOBus,
+    pub game_database: GameDatabase,
+    pub rom: Option<NESFile>,
+    pub fds_bios: Option<Vec<u8>>,
+    pub region_setting: RegionSetting,
 }
 
 impl NES {
     pub fn new() -> Self {
         Self {
-            cycles_left: 0,
-            cycles_per_frame: CYCLES_PER_FRAME_NTSC,
-            bus: IOBus::new()
+            bus: IOBus::new(),
+            game_database: GameDatabase::new(),
+            rom: None,
+            fds_bios: None,
+            region_setting: RegionSetting::Auto,
         }
     }
 
+    /// Selects the region to emulate, and applies it to the inserted ROM
+    pub fn set_region(&mut self, setting: RegionSetting) {
+        self.region_setting = setting;
+        self.apply_region();
+    }
+
+    /// Switches the console to the region setting, resolving `Auto` with the inserted ROM
+    pub fn apply_region(&mut self) {
+        let region = self.region_setting.resolve(self.rom.as_ref().map(|rom| rom.region()));
+        info!("Running as {region:?}");
+        self.bus.set_region(region);
+    }
+
+    /// Sets the database used to correct the headers of ROMs inserted afterwards
+    pub fn set_game_database(&mut self, database: GameDatabase) {
+        self.game_database = database;
+    }
+
     pub fn insert_rom<P: AsRef<Path> + Debug>(&mut self, path: &P) -> anyhow::Result<()> {
         info!("Loading {path:?}...");
 
@@ -44,9 +87,64 @@ impl NES {
         Ok(())
     }
 
+    pub fn insert_rom_with_patch<P: AsRef<Path> + Debug, Q: AsRef<Path> + Debug>(&mut self, path: &P, patch_path: &Q) -> anyhow::Result<()> {
+        info!("Loading {path:?} with patch {patch_path:?}...");
+
+        NESLoader::load_rom_with_patch(path, patch_path, self)?;
+
+        Ok(())
+    }
+
+    /// Loads the Famicom Disk System BIOS, which is needed before inserting disk images
+    pub fn load_fds_bios<P: AsRef<Path> + Debug>(&mut self, path: &P) -> anyhow::Result<()> {
+        let bios = fs::read(path)?;
+        if bios.len() != BIOS_SIZE {
+            return Err(anyhow::anyhow!("FDS BIOS {path:?} must be {BIOS_SIZE} bytes, got {}", bios.len()));
+        }
+
+        self.fds_bios = Some(bios);
+        Ok(())
+    }
+
+    fn fds(&self) -> anyhow::Result<&FDS> {
+        self.bus.memory.cartridge()
+            .and_then(|cartridge| cartridge.fds())
+            .ok_or_else(|| anyhow::anyhow!("No FDS disk image is inserted"))
+    }
+
+    fn fds_mut(&mut self) -> anyhow::Result<&mut FDS> {
+        self.bus.memory.cartridge_mut()
+            .and_then(|cartridge| cartridge.fds_mut())
+            .ok_or_else(|| anyhow::anyhow!("No FDS disk image is inserted"))
+    }
+
+    pub fn disk_side_count(&self) -> usize {
+        self.fds().map_or(0, |fds| fds.side_count())
+    }
+
+    /// The currently inserted disk side, `None` if the disk is ejected or no FDS image is loaded
+    pub fn disk_side(&self) -> Option<usize> {
+        self.fds().ok().and_then(|fds| fds.side())
+    }
+
+    pub fn insert_disk_side(&mut self, side: usize) -> anyhow::Result<()> {
+        self.fds_mut()?.insert_side(side)
+    }
+
+    pub fn eject_disk(&mut self) -> anyhow::Result<()> {
+        self.fds_mut()?.eject();
+        Ok(())
+    }
+
+    /// Persists the disk writes to the diff file next to the disk image
+    pub fn save_disk(&self) -> anyhow::Result<()> {
+        self.fds()?.save_changes()
+    }
+
     pub fn reset(&mut self) -> anyhow::Result<()> {
-        let start_addr = self.bus.memory.read(0xFFFC)?;
+        let start_addr = self.bus.read(0xFFFC)?;
         self.bus.cpu.reset(start_addr);
+        self.bus.ppu.reset();
         Ok(())
     }
 
@@ -64,12 +162,12 @@ impl NES {
     pub fn get_instruction_at(&self, addr: u16) -> anyhow::Result<Vec<u8>> {
         let mut instruction = vec![];
         trace!("Getting instruction at 0x{:04X}", addr);
-        let opcode = self.bus.memory.read(addr)?;
+        let opcode = self.bus.peek(addr)?;
         instruction.push(opcode);
 
         let instruction_size = get_opcode_size(opcode) - 1;
         for i in 1..=instruction_size {
-            instruction.push(self.bus.memory.read(addr + i as u16)?);
+            instruction.push(self.bus.peek(addr + i as u16)?);
         }
 
         Ok(instruction)
@@ -79,7 +177,49 @@ impl NES {
         self.get_instruction_at(self.bus.cpu.pc)
     }
 
-    pub fn execute(&mut self) -> anyhow::Result<u8> {
+    /// Pushes PC and the status and jumps through the given vector, for BRK, NMI and IRQ
+    pub(crate) fn interrupt(&mut self, vector: u16, break_: bool) -> anyhow::Result<()> {
+        let pc_high = (self.bus.cpu.pc >> 8) as u8;
+        let pc_low = (self.bus.cpu.pc & 0xFF) as u8;
+
+        self.bus.write(self.bus.cpu.stack_addr(), pc_high)?;
+        self.bus.cpu.sp = self.bus.cpu.sp.wrapping_sub(1);
+        self.bus.write(self.bus.cpu.stack_addr(), pc_low)?;
+        self.bus.cpu.sp = self.bus.cpu.sp.wrapping_sub(1);
+
+        self.bus.write(self.bus.cpu.stack_addr(), self.bus.cpu.status(break_))?;
+        self.bus.cpu.sp = self.bus.cpu.sp.wrapping_sub(1);
+
+        self.bus.cpu.set_interrupt_disable(true);
+        self.bus.cpu.pc = self.bus.read(vector)?;
+        Ok(())
+    }
+
+    /// Runs a pending DMA, services an interrupt or executes one instruction, returning the CPU cycles taken
+    pub fn execute(&mut self) -> anyhow::Result<u16> {
+        let start = self.bus.cycles;
+
+        // DMA halts the CPU right after the write to $4014, interrupts raised meanwhile are taken afterwards
+        if let Some(page) = self.bus.oam_dma.take() {
+            trace!("OAM DMA from page 0x{page:02X}");
+            return self.bus.run_oam_dma(page);
+        }
+
+        if self.bus.cpu.nmi {
+            trace!("Servicing NMI");
+            self.bus.cpu.nmi = false;
+            self.interrupt(0xFFFA, false)?;
+            self.bus.tick(INTERRUPT_CYCLES);
+            return Ok((self.bus.cycles - start) as u16);
+        }
+
+        if self.bus.cpu.irq_pending() {
+            trace!("Servicing IRQ ({:?})", self.bus.cpu.irq);
+            self.interrupt(0xFFFE, false)?;
+            self.bus.tick(INTERRUPT_CYCLES);
+            return Ok((self.bus.cycles - start) as u16);
+        }
+
         let cur_instruction = self.get_instruction()?;
 
         let opcode = cur_instruction[0];
@@ -95,17 +235,149 @@ impl NES {
             _ => {}
         };
 
-        OPCODES[opcode as usize].0(self, byte1, byte2)?;
+        // PC points to the next instruction while executing, so branches and jumps can overwrite it
+        self.bus.cpu.pc = self.bus.cpu.pc.wrapping_add(cur_instruction.len() as u16);
 
-        self.bus.cpu.pc += cur_instruction.len() as u16;
+        let (instruction, cycles) = OPCODES[opcode as usize];
+        instruction(self, byte1, byte2)?;
 
-        Ok(0)
+        self.bus.tick(cycles);
+
+        Ok((self.bus.cycles - start) as u16)
+    }
+
+    /// Sets the buttons held on player 1-4's joypad, numbered from 0, usually once before every frame.
+    /// Players 3 and 4 need a Four Score or a Famicom 4 player adapter.
+    pub fn set_buttons(&mut self, player: usize, buttons: Buttons) -> anyhow::Result<()> {
+        if !self.bus.input.set_player_buttons(player, buttons) {
+            return Err(anyhow::anyhow!("No joypad is connected for player {player}"));
+        }
+        Ok(())
+    }
+
+    /// Plugs an input device into a port, or unplugs it with `None`
+    pub fn connect_input(&mut self, port: InputPort, device: Option<Box<dyn InputDevice>>) {
+        self.bus.input.connect(port, device);
+    }
+
+    /// The device in a port if it is a `T`, e.g. to aim a `Zapper`
+    pub fn input_device_mut<T: InputDevice + 'static>(&mut self, port: InputPort) -> Option<&mut T> {
+        self.bus.input.device_mut(port)
+    }
+
+    /// Presses or releases a key on the Family BASIC keyboard
+    pub fn set_key(&mut self, key: Key, pressed: bool) -> anyhow::Result<()> {
+        let Some(keyboard) = self.bus.input.device_mut::<FamilyKeyboard>(InputPort::Expansion) else {
+            return Err(anyhow::anyhow!("No keyboard is connected"));
+        };
+        keyboard.set_key(key, pressed);
+        Ok(())
+    }
+
+    /// The data recorder, to insert, play and record tapes
+    pub fn data_recorder_mut(&mut self) -> Option<&mut DataRecorder> {
+        self.bus.input.data_recorder_mut()
+    }
+
+    /// Sets the rate of the samples returned by `audio_samples`, usually 44100 or 48000 Hz
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.bus.apu.resampler.set_sample_rate(sample_rate);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.bus.apu.resampler.sample_rate()
+    }
+
+    /// The mono audio samples produced since the last call in the range -1.0..=1.0, about a frame's worth after `next_frame`
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.bus.apu.resampler.take_samples()
+    }
+
+    /// Like `audio_samples`, as signed 16-bit PCM
+    pub fn audio_samples_i16(&mut self) -> Vec<i16> {
+        self.audio_samples()
+            .into_iter()
+            .map(sample_to_i16)
+            .collect()
+    }
+
+    /// Starts writing the audio output to a WAV file at the current sample rate. With `stems`, every
+    /// APU channel and expansion audio channel of the inserted cartridge also gets its own file.
+    pub fn start_recording<P: AsRef<Path> + Debug>(&mut self, path: &P, stems: bool) -> anyhow::Result<()> {
+        self.stop_recording()?;
+
+        let expansion_channels = self.bus.memory.cartridge().map_or(&[][..], |cartridge| cartridge.audio_channels());
+        let recorder = AudioRecorder::new(path, self.bus.region.cpu_clock(), self.sample_rate(), stems, expansion_channels)?;
+        self.bus.recorder = Some(recorder);
+        Ok(())
+    }
+
+    /// Completes the WAV files of the recording in progress, if any
+    pub fn stop_recording(&mut self) -> anyhow::Result<()> {
+        match self.bus.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// CRC32 of the inserted game, which save states are tied to
+    fn rom_checksum(&self) -> anyhow::Result<u32> {
+        if let Some(rom) = &self.rom {
+            return Ok(rom.hashes.crc32);
+        }
+        self.fds().map(|fds| fds.checksum())
+            .map_err(|_| anyhow::anyhow!("No game is inserted"))
     }
 
+    /// Serializes the CPU, RAM, PPU, APU, cartridge and input devices into a versioned binary save state for the inserted game
+    pub fn save_state(&self) -> anyhow::Result<Vec<u8>> {
+        let checksum = self.rom_checksum()?;
+        let cartridge = self.bus.memory.cartridge().ok_or_else(|| anyhow::anyhow!("No game is inserted"))?;
+        Ok(StateFile::write(checksum, &[
+            (STATE_SECTIONS[0], &self.bus.cpu),
+            (STATE_SECTIONS[1], &self.bus.memory),
+            (STATE_SECTIONS[2], &self.bus.ppu),
+            (STATE_SECTIONS[3], &self.bus.apu),
+            (STATE_SECTIONS[4], &self.bus),
+            (STATE_SECTIONS[5], cartridge),
+            (STATE_SECTIONS[6], &self.bus.input),
+        ]))
+    }
+
+    /// Restores a state made by `save_state`. States of other games are refused, and the machine
+    /// is left as it was if the state can't be loaded.
+    pub fn load_state(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let state = StateFile::parse(data)?;
+        let checksum = self.rom_checksum()?;
+        if state.rom_checksum != checksum {
+            return Err(anyhow::anyhow!("Save state is for another game (CRC32 0x{:08X}, inserted 0x{checksum:08X})", state.rom_checksum));
+        }
+        state.log_unknown_sections(&STATE_SECTIONS);
+
+        let backup = self.save_state()?;
+        if let Err(err) = self.restore_state(&state) {
+            self.restore_state(&StateFile::parse(&backup)?)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn restore_state(&mut self, state: &StateFile) -> anyhow::Result<()> {
+        state.load_section(STATE_SECTIONS[0], &mut self.bus.cpu)?;
+        state.load_section(STATE_SECTIONS[1], &mut self.bus.memory)?;
+        state.load_section(STATE_SECTIONS[2], &mut self.bus.ppu)?;
+        state.load_section(STATE_SECTIONS[3], &mut self.bus.apu)?;
+        state.load_section(STATE_SECTIONS[4], &mut self.bus)?;
+        state.load_section(STATE_SECTIONS[6], &mut self.bus.input)?;
+        let cartridge = self.bus.memory.cartridge_mut().ok_or_else(|| anyhow::anyhow!("No game is inserted"))?;
+        state.load_section(STATE_SECTIONS[5], cartridge)
+    }
+
+    /// Runs until the PPU finished the current frame, leaving it in `bus.ppu.framebuffer`
     pub fn next_frame(&mut self) -> anyhow::Result<()> {
-        self.cycles_left = self.cycles_per_frame;
-        while self.cycles_left > 0 {
-            self.cycles_left -= self.execute()? as u16;
+        let frame = self.bus.ppu.frame;
+        while self.bus.ppu.frame == frame {
+            self.execute()?;
         }
 
         Ok(())
@@ -1,7 +1,298 @@
-pub struct APU {}
+use crate::system::nes::apu::dmc::DMC;
+use crate::system::nes::apu::frame_counter::FrameCounter;
+use crate::system::nes::apu::noise::Noise;
+use crate::system::nes::apu::pulse::Pulse;
+use crate::system::nes::apu::resampler::{Resampler, DEFAULT_SAMPLE_RATE};
+use crate::system::nes::apu::triangle::Triangle;
+use crate::system::nes::file::Region;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+pub mod envelope;
+pub mod length_counter;
+pub mod pulse;
+pub mod triangle;
+pub mod noise;
+pub mod dmc;
+pub mod frame_counter;
+pub mod filter;
+pub mod resampler;
+pub mod recorder;
+
+/// The APU's own sound channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    DMC,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [Channel::Pulse1, Channel::Pulse2, Channel::Triangle, Channel::Noise, Channel::DMC];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::DMC => "dmc",
+        }
+    }
+}
+
+/// How a channel is mixed into the audio output. Only the output is affected, never the emulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelControl {
+    pub volume: f32,
+    pub muted: bool,
+    /// While any channel is soloed, only the soloed channels are heard
+    pub solo: bool,
+}
+
+impl ChannelControl {
+    fn gain(&self, any_solo: bool) -> f32 {
+        if self.muted || (any_solo && !self.solo) { 0.0 } else { self.volume }
+    }
+}
+
+impl Default for ChannelControl {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+            solo: false,
+        }
+    }
+}
+
+pub struct APU {
+    /// Selects the frame counter, noise and DMC period tables
+    pub region: Region,
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: DMC,
+    pub frame_counter: FrameCounter,
+    /// Turns the mixed output into samples at the host's sample rate
+    pub resampler: Resampler,
+    /// Mute, solo and volume of the channels, indexed by `Channel`
+    pub channel_controls: [ChannelControl; 5],
+    /// Mute, solo and volume of the cartridge's expansion audio channels, indexed like `Mapper::audio_channels`.
+    /// Channels past the end use the defaults.
+    pub expansion_controls: Vec<ChannelControl>,
+    /// Output of the pulse DAC for the sum of both pulse channels
+    pulse_table: [f32; 31],
+    /// Output of the triangle, noise and DMC DAC for 3 * triangle + 2 * noise + DMC
+    tnd_table: [f32; 203],
+    /// The pulse timers are clocked on every other CPU cycle
+    odd_cycle: bool,
+}
 
 impl APU {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            region: Region::NTSC,
+            pulse1: Pulse::new(1),
+            pulse2: Pulse::new(2),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: DMC::new(),
+            frame_counter: FrameCounter::new(),
+            resampler: Resampler::new(Region::NTSC.cpu_clock(), DEFAULT_SAMPLE_RATE),
+            channel_controls: [ChannelControl::default(); 5],
+            expansion_controls: vec![],
+            pulse_table: std::array::from_fn(|n| pulse_dac(n as f32)),
+            tnd_table: std::array::from_fn(|n| tnd_dac(n as f32)),
+            odd_cycle: false,
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.resampler.set_clock_rate(region.cpu_clock());
+    }
+
+    /// CPU write to $4000-$4017
+    pub fn write_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write_register(addr, value),
+            0x4004..=0x4007 => self.pulse2.write_register(addr, value),
+            0x4008..=0x400B => self.triangle.write_register(addr, value),
+            0x400C..=0x400F => self.noise.write_register(addr, value),
+            0x4010..=0x4013 => self.dmc.write_register(addr, value),
+            0x4015 => {
+                self.pulse1.length_counter.set_enabled(value & 0x01 != 0);
+                self.pulse2.length_counter.set_enabled(value & 0x02 != 0);
+                self.triangle.length_counter.set_enabled(value & 0x04 != 0);
+                self.noise.length_counter.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+            }
+            0x4017 => self.frame_counter.write(value, self.odd_cycle),
+            _ => {}
+        }
+    }
+
+    /// CPU read of $4015, which acknowledges the frame IRQ
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_counter.acknowledge_irq();
+        status
+    }
+
+    /// The length counters and sample in progress in the low bits, the frame and DMC IRQs in the top bits
+    pub fn peek_status(&self) -> u8 {
+        (self.pulse1.length_counter.active() as u8)
+            | (self.pulse2.length_counter.active() as u8) << 1
+            | (self.triangle.length_counter.active() as u8) << 2
+            | (self.noise.length_counter.active() as u8) << 3
+            | ((self.dmc.bytes_remaining > 0) as u8) << 4
+            | (self.frame_counter.irq_flag as u8) << 6
+            | (self.dmc.irq_flag as u8) << 7
+    }
+
+    /// Advances the channels by one CPU cycle
+    pub fn tick(&mut self) {
+        let clock = self.frame_counter.tick(self.region);
+        if clock.quarter {
+            self.clock_quarter_frame();
+        }
+        if clock.half {
+            self.clock_half_frame();
+        }
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer(self.region);
+        self.dmc.clock_timer(self.region);
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+    }
+
+    /// Clocks the envelopes and the triangle's linear counter
+    pub fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    /// Clocks the length counters and sweep units
+    pub fn clock_half_frame(&mut self) {
+        self.pulse1.length_counter.clock();
+        self.pulse2.length_counter.clock();
+        self.triangle.length_counter.clock();
+        self.noise.length_counter.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    /// Mixes the APU channels through the non-linear DACs with the cartridge's expansion audio channels,
+    /// in the range 0.0..=1.0
+    pub fn output(&self, expansion_channels: &[f32]) -> f32 {
+        self.mix(expansion_channels.len(), |channel| expansion_channels[channel])
+    }
+
+    /// Same as `output`, with the expansion channels read through `channel_output` instead of from a buffer
+    pub fn mix(&self, expansion_channel_count: usize, channel_output: impl Fn(usize) -> f32) -> f32 {
+        let expansion_audio: f32 = (0..expansion_channel_count).map(&channel_output).sum();
+        if self.channel_controls.iter().chain(&self.expansion_controls).all(|control| *control == ChannelControl::default()) {
+            let pulse = self.pulse_table[(self.pulse1.output() + self.pulse2.output()) as usize];
+            let tnd = self.tnd_table[3 * self.triangle.output() as usize + 2 * self.noise.output() as usize + self.dmc.output() as usize];
+            return (pulse + tnd + expansion_audio).min(1.0);
+        }
+
+        // Scales the DAC inputs, which keeps the interaction between the channels
+        let any_solo = self.channel_controls.iter().chain(&self.expansion_controls).any(|control| control.solo);
+        let gain = |channel: Channel| self.channel_control(channel).gain(any_solo);
+        let pulse = pulse_dac(gain(Channel::Pulse1) * self.pulse1.output() as f32 + gain(Channel::Pulse2) * self.pulse2.output() as f32);
+        let tnd = tnd_dac(
+            3.0 * gain(Channel::Triangle) * self.triangle.output() as f32
+                + 2.0 * gain(Channel::Noise) * self.noise.output() as f32
+                + gain(Channel::DMC) * self.dmc.output() as f32,
+        );
+        let expansion: f32 = (0..expansion_channel_count)
+            .map(|channel| self.expansion_control(channel).gain(any_solo) * channel_output(channel))
+            .sum();
+        (pulse + tnd + expansion).min(1.0)
+    }
+
+    /// Whether the expansion audio is mixed as is, so the cartridge's own mix can stand in for its channels
+    pub fn expansion_controls_unchanged(&self) -> bool {
+        self.expansion_controls.iter().all(|control| *control == ChannelControl::default())
+    }
+
+    pub fn channel_control(&self, channel: Channel) -> &ChannelControl {
+        &self.channel_controls[channel as usize]
+    }
+
+    pub fn channel_control_mut(&mut self, channel: Channel) -> &mut ChannelControl {
+        &mut self.channel_controls[channel as usize]
+    }
+
+    pub fn expansion_control(&self, channel: usize) -> ChannelControl {
+        self.expansion_controls.get(channel).copied().unwrap_or_default()
+    }
+
+    pub fn expansion_control_mut(&mut self, channel: usize) -> &mut ChannelControl {
+        if channel >= self.expansion_controls.len() {
+            self.expansion_controls.resize(channel + 1, ChannelControl::default());
+        }
+        &mut self.expansion_controls[channel]
+    }
+
+    /// Unmutes and unsolos all channels and restores their volume
+    pub fn reset_channel_controls(&mut self) {
+        self.channel_controls = [ChannelControl::default(); 5];
+        self.expansion_controls.clear();
+    }
+
+    /// The output of a single channel through its DAC, as if the others were silent, ignoring its `ChannelControl`
+    pub fn channel_output(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::Pulse1 => self.pulse_table[self.pulse1.output() as usize],
+            Channel::Pulse2 => self.pulse_table[self.pulse2.output() as usize],
+            Channel::Triangle => self.tnd_table[3 * self.triangle.output() as usize],
+            Channel::Noise => self.tnd_table[2 * self.noise.output() as usize],
+            Channel::DMC => self.tnd_table[self.dmc.output() as usize],
+        }
+    }
+}
+
+/// The pulse DAC's output for the sum of the pulse channels
+fn pulse_dac(input: f32) -> f32 {
+    if input <= 0.0 { 0.0 } else { 95.52 / (8128.0 / input + 100.0) }
+}
+
+/// The triangle, noise and DMC DAC's output for 3 * triangle + 2 * noise + DMC
+fn tnd_dac(input: f32) -> f32 {
+    if input <= 0.0 { 0.0 } else { 163.67 / (24329.0 / input + 100.0) }
+}
+
+/// Only the channels are saved, the mixing and resampling are host settings
+impl Savestate for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        self.frame_counter.save_state(state);
+        state.write(self.odd_cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.frame_counter.load_state(state)?;
+        self.odd_cycle = state.read()?;
+        Ok(())
     }
-}
\ No newline at end of file
+}
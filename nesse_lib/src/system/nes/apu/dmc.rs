@@ -0,0 +1,179 @@
+use crate::system::nes::file::Region;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Output rates in CPU cycles per bit
+const RATES_NTSC: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+const RATES_PAL: [u16; 16] = [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50];
+
+/// The delta modulation channel at $4010-$4013, which plays 1-bit delta samples fetched from CPU memory by DMA
+pub struct DMC {
+    pub irq_enabled: bool,
+    pub irq_flag: bool,
+    looping: bool,
+    rate: u8,
+    timer: u16,
+    /// The 7-bit output level
+    pub level: u8,
+    sample_addr: u16,
+    sample_length: u16,
+    current_addr: u16,
+    pub bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+}
+
+impl DMC {
+    pub fn new() -> Self {
+        Self {
+            irq_enabled: false,
+            irq_flag: false,
+            looping: false,
+            rate: 0,
+            timer: 0,
+            level: 0,
+            sample_addr: 0xC000,
+            sample_length: 1,
+            current_addr: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+        }
+    }
+
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register & 0x03 {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+                self.looping = value & 0x40 != 0;
+                self.rate = value & 0x0F;
+            }
+            // Direct load of the output level
+            1 => self.level = value & 0x7F,
+            2 => self.sample_addr = 0xC000 | (value as u16) << 6,
+            _ => self.sample_length = (value as u16) << 4 | 1,
+        }
+    }
+
+    /// Enabling through $4015 starts the sample unless it's still playing, disabling stops it
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq_flag = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /// The address the memory reader needs to fetch by DMA, when the sample buffer ran empty
+    pub fn dma_request(&self) -> Option<u16> {
+        (self.sample_buffer.is_none() && self.bytes_remaining > 0).then_some(self.current_addr)
+    }
+
+    /// Completes the DMA started for `dma_request`
+    pub fn fill_sample_buffer(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        // The address wraps around to $8000
+        self.current_addr = self.current_addr.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle
+    pub fn clock_timer(&mut self, region: Region) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        let rates = if matches!(region, Region::PAL | Region::Dendy) { &RATES_PAL } else { &RATES_NTSC };
+        self.timer = rates[self.rate as usize] - 1;
+
+        if !self.silence {
+            if self.shift_register & 0x01 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.level
+    }
+}
+
+impl Default for DMC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Savestate for DMC {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.irq_enabled);
+        state.write(self.irq_flag);
+        state.write(self.looping);
+        state.write(self.rate);
+        state.write(self.timer);
+        state.write(self.level);
+        state.write(self.sample_addr);
+        state.write(self.sample_length);
+        state.write(self.current_addr);
+        state.write(self.bytes_remaining);
+        state.write(self.sample_buffer);
+        state.write(self.shift_register);
+        state.write(self.bits_remaining);
+        state.write(self.silence);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.irq_enabled = state.read()?;
+        self.irq_flag = state.read()?;
+        self.looping = state.read()?;
+        self.rate = state.read()?;
+        self.timer = state.read()?;
+        self.level = state.read()?;
+        self.sample_addr = state.read()?;
+        self.sample_length = state.read()?;
+        self.current_addr = state.read()?;
+        self.bytes_remaining = state.read()?;
+        self.sample_buffer = state.read()?;
+        self.shift_register = state.read()?;
+        self.bits_remaining = state.read()?;
+        self.silence = state.read()?;
+        Ok(())
+    }
+}
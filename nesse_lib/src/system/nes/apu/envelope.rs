@@ -0,0 +1,68 @@
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The volume envelope shared by the pulse and noise channels
+#[derive(Default)]
+pub struct Envelope {
+    pub start: bool,
+    pub looping: bool,
+    pub constant_volume: bool,
+    /// The constant volume, or the divider period of the decay
+    pub volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    /// Loads the `--LC VVVV` bits of the channel's first register
+    pub fn write(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant_volume = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    /// Clocked by the frame counter every quarter frame
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+            return;
+        }
+
+        if self.divider > 0 {
+            self.divider -= 1;
+            return;
+        }
+        self.divider = self.volume;
+        if self.decay > 0 {
+            self.decay -= 1;
+        } else if self.looping {
+            self.decay = 15;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume { self.volume } else { self.decay }
+    }
+}
+
+impl Savestate for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.start);
+        state.write(self.looping);
+        state.write(self.constant_volume);
+        state.write(self.volume);
+        state.write(self.divider);
+        state.write(self.decay);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.start = state.read()?;
+        self.looping = state.read()?;
+        self.constant_volume = state.read()?;
+        self.volume = state.read()?;
+        self.divider = state.read()?;
+        self.decay = state.read()?;
+        Ok(())
+    }
+}
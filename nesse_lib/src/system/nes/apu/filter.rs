@@ -0,0 +1,52 @@
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+/// A first-order RC filter running at the output sample rate
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub kind: FilterKind,
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+        Self {
+            kind,
+            alpha,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    /// The two high-pass filters and the low-pass filter between the NES's DACs and its audio output
+    pub fn nes_chain(sample_rate: u32) -> Vec<Filter> {
+        vec![
+            Filter::new(FilterKind::HighPass, 90.0, sample_rate),
+            Filter::new(FilterKind::HighPass, 440.0, sample_rate),
+            Filter::new(FilterKind::LowPass, 14_000.0, sample_rate),
+        ]
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::HighPass => self.alpha * (self.previous_output + input - self.previous_input),
+            FilterKind::LowPass => self.previous_output + self.alpha * (input - self.previous_output),
+        };
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
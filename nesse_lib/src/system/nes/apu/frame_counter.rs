@@ -0,0 +1,129 @@
+use crate::system::nes::file::Region;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// CPU cycles of the quarter frame steps, the last step of the 4-step sequence also raises the IRQ
+const STEPS_NTSC: [u32; 4] = [7457, 14913, 22371, 29829];
+const STEPS_PAL: [u32; 4] = [8313, 16627, 24939, 33253];
+/// The last step of the 5-step sequence, which wraps on the cycle after it
+const FIFTH_STEP_NTSC: u32 = 37281;
+const FIFTH_STEP_PAL: u32 = 41565;
+
+/// The envelopes, linear counter, length counters and sweeps to clock on this cycle
+#[derive(Default)]
+pub struct FrameClock {
+    pub quarter: bool,
+    pub half: bool,
+}
+
+/// The frame sequencer at $4017, which clocks the channel units about 240 times per second
+#[derive(Default)]
+pub struct FrameCounter {
+    pub five_step: bool,
+    pub irq_inhibit: bool,
+    pub irq_flag: bool,
+    /// Whether the IRQ flag was set on the current cycle, when reading $4015 can't clear it
+    irq_raised: bool,
+    cycle: u32,
+    /// CPU cycles until a $4017 write resets the sequencer
+    reset_delay: Option<u8>,
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// $4017 takes effect 3 or 4 CPU cycles later, depending on whether it was written on an APU cycle
+    pub fn write(&mut self, value: u8, apu_cycle: bool) {
+        self.five_step = value & 0x80 != 0;
+        self.irq_inhibit = value & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq_flag = false;
+        }
+        self.reset_delay = Some(if apu_cycle { 3 } else { 4 });
+    }
+
+    /// Reading $4015 clears the IRQ flag, unless the sequencer sets it on the same cycle
+    pub fn acknowledge_irq(&mut self) {
+        if !self.irq_raised {
+            self.irq_flag = false;
+        }
+    }
+
+    /// Advances the sequencer by one CPU cycle
+    pub fn tick(&mut self, region: Region) -> FrameClock {
+        self.irq_raised = false;
+        if let Some(delay) = self.reset_delay.as_mut() {
+            *delay -= 1;
+            if *delay == 0 {
+                self.reset_delay = None;
+                self.cycle = 0;
+                // Switching to the 5-step sequence clocks everything right away
+                return FrameClock { quarter: self.five_step, half: self.five_step };
+            }
+        }
+
+        self.cycle += 1;
+        let (steps, fifth_step) = match region {
+            Region::PAL | Region::Dendy => (&STEPS_PAL, FIFTH_STEP_PAL),
+            _ => (&STEPS_NTSC, FIFTH_STEP_NTSC),
+        };
+        let last_step = steps[3];
+
+        let mut clock = FrameClock::default();
+        if self.five_step {
+            match self.cycle {
+                cycle if cycle == steps[0] || cycle == steps[2] => clock.quarter = true,
+                cycle if cycle == steps[1] => clock = FrameClock { quarter: true, half: true },
+                cycle if cycle == fifth_step => clock = FrameClock { quarter: true, half: true },
+                cycle if cycle == fifth_step + 1 => self.cycle = 0,
+                _ => {}
+            }
+        } else {
+            match self.cycle {
+                cycle if cycle == steps[0] || cycle == steps[2] => clock.quarter = true,
+                cycle if cycle == steps[1] => clock = FrameClock { quarter: true, half: true },
+                // The IRQ flag is set on the three cycles around the last step
+                cycle if cycle == last_step - 1 => self.raise_irq(),
+                cycle if cycle == last_step => {
+                    clock = FrameClock { quarter: true, half: true };
+                    self.raise_irq();
+                }
+                cycle if cycle == last_step + 1 => {
+                    self.raise_irq();
+                    self.cycle = 0;
+                }
+                _ => {}
+            }
+        }
+        clock
+    }
+
+    fn raise_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.irq_flag = true;
+            self.irq_raised = true;
+        }
+    }
+}
+
+impl Savestate for FrameCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.five_step);
+        state.write(self.irq_inhibit);
+        state.write(self.irq_flag);
+        state.write(self.irq_raised);
+        state.write(self.cycle);
+        state.write(self.reset_delay);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.five_step = state.read()?;
+        self.irq_inhibit = state.read()?;
+        self.irq_flag = state.read()?;
+        self.irq_raised = state.read()?;
+        self.cycle = state.read()?;
+        self.reset_delay = state.read()?;
+        Ok(())
+    }
+}
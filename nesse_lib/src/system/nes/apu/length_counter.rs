@@ -0,0 +1,57 @@
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Silences a channel once it counts down to 0, unless halted
+#[derive(Default)]
+pub struct LengthCounter {
+    pub counter: u8,
+    pub halt: bool,
+    /// Cleared through $4015, which also clears the counter
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    /// Loads the counter from the 5-bit index in the top bits of the channel's last register
+    pub fn load(&mut self, value: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[value as usize >> 3];
+        }
+    }
+
+    /// Clocked by the frame counter every half frame
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+impl Savestate for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.counter);
+        state.write(self.halt);
+        state.write(self.enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.counter = state.read()?;
+        self.halt = state.read()?;
+        self.enabled = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use crate::system::nes::apu::envelope::Envelope;
+use crate::system::nes::apu::length_counter::LengthCounter;
+use crate::system::nes::file::Region;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Timer periods in CPU cycles
+const PERIODS_NTSC: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const PERIODS_PAL: [u16; 16] = [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+
+/// The pseudo-random noise channel at $400C-$400F
+pub struct Noise {
+    /// Uses bit 6 instead of bit 1 for the feedback, which gives a short, metallic sequence
+    short_mode: bool,
+    period: u8,
+    timer: u16,
+    shift_register: u16,
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            short_mode: false,
+            period: 0,
+            timer: 0,
+            shift_register: 1,
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+        }
+    }
+
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register & 0x03 {
+            0 => {
+                self.length_counter.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {}
+            2 => {
+                self.short_mode = value & 0x80 != 0;
+                self.period = value & 0x0F;
+            }
+            _ => {
+                self.length_counter.load(value);
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle
+    pub fn clock_timer(&mut self, region: Region) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        let periods = if matches!(region, Region::PAL | Region::Dendy) { &PERIODS_PAL } else { &PERIODS_NTSC };
+        self.timer = periods[self.period as usize] - 1;
+
+        let tap = if self.short_mode { 6 } else { 1 };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+        self.shift_register = (self.shift_register >> 1) | feedback << 14;
+    }
+
+    /// The current volume, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.length_counter.active() || self.shift_register & 0x01 != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Savestate for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.short_mode);
+        state.write(self.period);
+        state.write(self.timer);
+        state.write(self.shift_register);
+        self.envelope.save_state(state);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.short_mode = state.read()?;
+        self.period = state.read()?;
+        self.timer = state.read()?;
+        self.shift_register = state.read()?;
+        self.envelope.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        Ok(())
+    }
+}
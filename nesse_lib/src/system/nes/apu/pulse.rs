@@ -0,0 +1,167 @@
+use crate::system::nes::apu::envelope::Envelope;
+use crate::system::nes::apu::length_counter::LengthCounter;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+pub const DUTY_SEQUENCES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+#[derive(Default)]
+struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    divider: u8,
+    reload: bool,
+}
+
+/// One of the two square wave channels at $4000-$4003 and $4004-$4007
+pub struct Pulse {
+    /// Pulse 1 negates the sweep with ones' complement, pulse 2 with two's complement
+    channel: u8,
+    duty: u8,
+    step: u8,
+    pub timer_period: u16,
+    timer: u16,
+    sweep: Sweep,
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Pulse {
+    /// Creates pulse channel 1 or 2
+    pub fn new(channel: u8) -> Self {
+        Self {
+            channel,
+            duty: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            sweep: Sweep::default(),
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+        }
+    }
+
+    /// Writes one of the channel's four registers
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register & 0x03 {
+            0 => {
+                self.duty = value >> 6;
+                self.length_counter.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep.enabled = value & 0x80 != 0;
+                self.sweep.period = (value >> 4) & 0x07;
+                self.sweep.negate = value & 0x08 != 0;
+                self.sweep.shift = value & 0x07;
+                self.sweep.reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (value as u16 & 0x07) << 8;
+                self.length_counter.load(value);
+                self.envelope.start = true;
+                self.step = 0;
+            }
+        }
+    }
+
+    /// Clocked every other CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 7) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// The period the sweep unit would change to
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep.shift;
+        if !self.sweep.negate {
+            self.timer_period + change
+        } else if self.channel == 1 {
+            self.timer_period.saturating_sub(change + 1)
+        } else {
+            self.timer_period.saturating_sub(change)
+        }
+    }
+
+    /// Periods below 8 or sweeping past $7FF silence the channel, even with the sweep disabled
+    fn muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    /// Clocked by the frame counter every half frame, together with the length counter
+    pub fn clock_sweep(&mut self) {
+        if self.sweep.divider == 0 && self.sweep.enabled && self.sweep.shift != 0 && !self.muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep.divider == 0 || self.sweep.reload {
+            self.sweep.divider = self.sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    /// The current volume, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.length_counter.active() || self.muted() || DUTY_SEQUENCES[self.duty as usize][self.step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Savestate for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.enabled);
+        state.write(self.period);
+        state.write(self.negate);
+        state.write(self.shift);
+        state.write(self.divider);
+        state.write(self.reload);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.enabled = state.read()?;
+        self.period = state.read()?;
+        self.negate = state.read()?;
+        self.shift = state.read()?;
+        self.divider = state.read()?;
+        self.reload = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.duty);
+        state.write(self.step);
+        state.write(self.timer_period);
+        state.write(self.timer);
+        self.sweep.save_state(state);
+        self.envelope.save_state(state);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.duty = state.read()?;
+        self.step = state.read()?;
+        self.timer_period = state.read()?;
+        self.timer = state.read()?;
+        self.sweep.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        Ok(())
+    }
+}
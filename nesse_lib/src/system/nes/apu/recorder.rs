@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use crate::system::nes::apu::resampler::Resampler;
+use crate::system::nes::apu::{Channel, APU};
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::wav::WavWriter;
+
+/// Samples are written out in chunks of this size
+const FLUSH_SAMPLES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Mix,
+    Channel(Channel),
+    /// An expansion audio channel of the cartridge
+    Expansion(usize),
+}
+
+struct Track {
+    source: Source,
+    path: PathBuf,
+    resampler: Resampler,
+    writer: WavWriter<BufWriter<File>>,
+}
+
+/// Records the audio output to a WAV file while emulating, optionally rendering every APU and
+/// expansion audio channel to its own stem file next to it
+pub struct AudioRecorder {
+    tracks: Vec<Track>,
+    /// The first write error, recording stops after it
+    error: Option<anyhow::Error>,
+}
+
+impl AudioRecorder {
+    /// Stems are named after the output file and the channel, e.g. `music_pulse1.wav` for `music.wav`
+    pub fn new<P: AsRef<Path>>(path: &P, clock_rate: u32, sample_rate: u32, stems: bool, expansion_channels: &[&str]) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut sources = vec![(Source::Mix, path.to_path_buf())];
+        if stems {
+            for channel in Channel::ALL {
+                sources.push((Source::Channel(channel), stem_path(path, channel.name())));
+            }
+            for (index, name) in expansion_channels.iter().enumerate() {
+                sources.push((Source::Expansion(index), stem_path(path, name)));
+            }
+        }
+
+        let mut tracks = vec![];
+        for (source, path) in sources {
+            info!("Recording {source:?} audio to {path:?}");
+            tracks.push(Track {
+                source,
+                writer: WavWriter::create(&path, sample_rate)?,
+                path,
+                resampler: Resampler::new(clock_rate, sample_rate),
+            });
+        }
+
+        Ok(Self { tracks, error: None })
+    }
+
+    /// The files being written, the mix first
+    pub fn paths(&self) -> Vec<&Path> {
+        self.tracks.iter().map(|track| track.path.as_path()).collect()
+    }
+
+    /// Follows the APU and cartridge by one CPU cycle, `mix` being the mixed output
+    pub fn clock(&mut self, apu: &APU, mix: f32, cartridge: Option<&dyn Mapper>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        for track in self.tracks.iter_mut() {
+            let amplitude = match track.source {
+                Source::Mix => mix,
+                Source::Channel(channel) => apu.channel_output(channel),
+                Source::Expansion(index) => cartridge.map_or(0.0, |cartridge| cartridge.audio_channel_output(index)),
+            };
+            track.resampler.clock(amplitude);
+
+            if track.resampler.available() >= FLUSH_SAMPLES {
+                let samples = track.resampler.take_samples();
+                if let Err(error) = track.writer.write_samples(&samples).and_then(|_| track.writer.flush()) {
+                    warn!("Stopped recording audio: {error}");
+                    self.error = Some(error);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Writes the remaining samples and completes the files
+    pub fn finish(self) -> anyhow::Result<()> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        for mut track in self.tracks {
+            track.writer.write_samples(&track.resampler.take_samples())?;
+            track.writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+fn stem_path(path: &Path, channel: &str) -> PathBuf {
+    let stem = path.file_stem().map_or_else(|| "audio".into(), |stem| stem.to_string_lossy());
+    path.with_file_name(format!("{stem}_{channel}.wav"))
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use crate::system::nes::apu::filter::Filter;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Output samples each amplitude step is spread over
+const TAPS: usize = 16;
+/// Sub-sample positions the step kernel is precomputed for
+const PHASES: usize = 64;
+/// Keeps the kernel's cutoff a bit below the output Nyquist frequency
+const CUTOFF: f64 = 0.9;
+/// Samples nobody took are dropped once this many seconds are buffered
+const MAX_BUFFERED_SECONDS: usize = 2;
+
+/// Band-limited resampler from the CPU clock to the host sample rate, in the style of blip_buf:
+/// every change of the input amplitude adds a windowed sinc impulse to a buffer of deltas at its
+/// exact sub-sample position, and the output is the running sum of that buffer.
+pub struct Resampler {
+    clock_rate: u32,
+    sample_rate: u32,
+    /// Output samples per input clock
+    factor: f64,
+    /// Position of the current clock between the next output sample and the one after it
+    position: f64,
+    kernel: Vec<[f32; TAPS]>,
+    deltas: VecDeque<f32>,
+    amplitude: f32,
+    level: f32,
+    /// Applies the NES's output filters, disable for the raw DAC output
+    pub filtering: bool,
+    filters: Vec<Filter>,
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        let mut resampler = Self {
+            clock_rate,
+            sample_rate,
+            factor: 0.0,
+            position: 0.0,
+            kernel: step_kernel(),
+            deltas: VecDeque::from(vec![0.0; TAPS + 1]),
+            amplitude: 0.0,
+            level: 0.0,
+            filtering: true,
+            filters: vec![],
+            samples: vec![],
+        };
+        resampler.update_rates();
+        resampler
+    }
+
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_clock_rate(&mut self, clock_rate: u32) {
+        self.clock_rate = clock_rate;
+        self.update_rates();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.samples.clear();
+        self.update_rates();
+    }
+
+    fn update_rates(&mut self) {
+        self.factor = self.sample_rate as f64 / self.clock_rate as f64;
+        self.filters = Filter::nes_chain(self.sample_rate);
+    }
+
+    /// Advances by one input clock at the given amplitude
+    pub fn clock(&mut self, amplitude: f32) {
+        let delta = amplitude - self.amplitude;
+        if delta != 0.0 {
+            self.amplitude = amplitude;
+            let phase = ((self.position * PHASES as f64) as usize).min(PHASES - 1);
+            for (slot, weight) in self.deltas.iter_mut().zip(self.kernel[phase]) {
+                *slot += delta * weight;
+            }
+        }
+
+        self.position += self.factor;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            self.finish_sample();
+        }
+    }
+
+    fn finish_sample(&mut self) {
+        self.level += self.deltas.pop_front().unwrap_or(0.0);
+        self.deltas.push_back(0.0);
+
+        let mut sample = self.level;
+        if self.filtering {
+            for filter in self.filters.iter_mut() {
+                sample = filter.process(sample);
+            }
+        }
+
+        let limit = self.sample_rate as usize * MAX_BUFFERED_SECONDS;
+        if self.samples.len() >= limit {
+            self.samples.drain(..limit / 2);
+        }
+        self.samples.push(sample);
+    }
+
+    /// Samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Moves as many of the produced samples as fit into the buffer, returning how many
+    pub fn read_samples(&mut self, buffer: &mut [f32]) -> usize {
+        let count = self.samples.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&self.samples[..count]);
+        self.samples.drain(..count);
+        count
+    }
+
+    pub fn available(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+/// Windowed sinc impulses for each sub-sample phase, each summing to 1 so steps settle exactly
+fn step_kernel() -> Vec<[f32; TAPS]> {
+    (0..PHASES)
+        .map(|phase| {
+            let center = (TAPS / 2 - 1) as f64 + phase as f64 / PHASES as f64;
+            let mut taps = [0.0; TAPS];
+            for (tap, weight) in taps.iter_mut().enumerate() {
+                let x = tap as f64 - center;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * CUTOFF * x).sin() / (PI * CUTOFF * x) };
+                // Blackman window over the kernel's width
+                let window = 0.42 + 0.5 * (2.0 * PI * x / TAPS as f64).cos() + 0.08 * (4.0 * PI * x / TAPS as f64).cos();
+                *weight = sinc * window;
+            }
+            let sum: f64 = taps.iter().sum();
+            taps.map(|weight| (weight / sum) as f32)
+        })
+        .collect()
+}
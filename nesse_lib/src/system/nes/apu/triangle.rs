@@ -0,0 +1,104 @@
+use crate::system::nes::apu::length_counter::LengthCounter;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// The triangle wave channel at $4008-$400B
+#[derive(Default)]
+pub struct Triangle {
+    step: u8,
+    pub timer_period: u16,
+    timer: u16,
+    /// Also halts the length counter
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    pub length_counter: LengthCounter,
+    /// Stops the sequencer at periods below 2, whose ultrasonic output is heard as popping in emulators
+    pub silence_ultrasonic: bool,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register & 0x03 {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length_counter.halt = self.control;
+                self.linear_reload_value = value & 0x7F;
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (value as u16 & 0x07) << 8;
+                self.length_counter.load(value);
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period;
+
+        if self.silence_ultrasonic && self.timer_period < 2 {
+            return;
+        }
+        if self.linear_counter > 0 && self.length_counter.active() {
+            self.step = (self.step + 1) & 0x1F;
+        }
+    }
+
+    /// Clocked by the frame counter every quarter frame
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// The current level, 0-15. A silenced triangle holds its level instead of dropping to 0.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+}
+
+impl Savestate for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.step);
+        state.write(self.timer_period);
+        state.write(self.timer);
+        state.write(self.control);
+        state.write(self.linear_reload_value);
+        state.write(self.linear_counter);
+        state.write(self.linear_reload);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.step = state.read()?;
+        self.timer_period = state.read()?;
+        self.timer = state.read()?;
+        self.control = state.read()?;
+        self.linear_reload_value = state.read()?;
+        self.linear_counter = state.read()?;
+        self.linear_reload = state.read()?;
+        self.length_counter.load_state(state)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use bitflags::bitflags;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
 use crate::system::nes::mnemonics::{ABSOLUTE, ABSOLUTE_INDEXED_X, ABSOLUTE_INDEXED_Y, ABSOLUTE_INDIRECT, ACCUMULATOR, IMMEDIATE, IMPLIED, MNEMONICS, RELATIVE, ZERO_PAGE, ZERO_PAGE_INDEXED_INDIRECT, ZERO_PAGE_INDEXED_X, ZERO_PAGE_INDEXED_Y, ZERO_PAGE_INDIRECT_INDEXED_Y};
 
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct CPUFlagStruct : u8 {
         const Negative = 0b0000_0001;
         const Overflow = 0b0000_0010;
@@ -14,14 +16,26 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Devices currently asserting the level-triggered IRQ line
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IRQSource : u8 {
+        const Mapper = 0b0000_0001;
+        const DMC = 0b0000_0010;
+        const FrameCounter = 0b0000_0100;
+    }
+}
+
 pub struct CPU {
     pub a: u8,
     pub x: u8,
     pub y: u8,
-    pub p: u8,
     pub pc: u16,
     pub sp: u8,
     pub flags: CPUFlagStruct,
+    pub irq: IRQSource,
+    /// Set on the falling edge of the NMI line, cleared once the NMI is serviced
+    pub nmi: bool,
 }
 
 impl CPU {
@@ -30,10 +44,11 @@ impl CPU {
             a: 0,
             x: 0,
             y: 0,
-            p: 0,
             pc: 0xFFFC,
             sp: 0xFF,
             flags: CPUFlagStruct::empty(),
+            irq: IRQSource::empty(),
+            nmi: false,
         }
     }
 
@@ -44,6 +59,33 @@ impl CPU {
         self.pc = start_address;
     }
 
+    /// The stack lives in page 1, $0100-$01FF
+    pub fn stack_addr(&self) -> u16 {
+        0x0100 | self.sp as u16
+    }
+
+    /// The status register as pushed to the stack, NV1BDIZC. B is only set when pushed by BRK and PHP.
+    pub fn status(&self, break_: bool) -> u8 {
+        let flags = self.flags - CPUFlagStruct::Break - CPUFlagStruct::Reserved;
+        // CPUFlagStruct keeps the flags in the reverse order of the status byte
+        let status = flags.bits().reverse_bits() | 0x20;
+        if break_ { status | 0x10 } else { status }
+    }
+
+    /// Restores the status register pulled by PLP and RTI, B and bit 5 don't exist in the CPU
+    pub fn set_status(&mut self, status: u8) {
+        self.flags = CPUFlagStruct::from_bits_truncate(status.reverse_bits()) - CPUFlagStruct::Break - CPUFlagStruct::Reserved;
+    }
+
+    pub fn set_irq(&mut self, source: IRQSource, active: bool) {
+        self.irq.set(source, active);
+    }
+
+    /// An IRQ is taken when any source asserts the line and interrupts aren't disabled
+    pub fn irq_pending(&self) -> bool {
+        !self.irq.is_empty() && !self.interrupt_disable()
+    }
+
     pub fn negative(&self) -> bool {
         self.flags.contains(CPUFlagStruct::Negative)
     }
@@ -81,6 +123,8 @@ impl CPU {
     pub fn set_overflow(&mut self, value: bool) {
         if value {
             self.flags.insert(CPUFlagStruct::Overflow);
+        } else {
+            self.flags.remove(CPUFlagStruct::Overflow);
         }
     }
 
@@ -95,6 +139,8 @@ impl CPU {
     pub fn set_decimal(&mut self, value: bool) {
         if value {
             self.flags.insert(CPUFlagStruct::Decimal);
+        } else {
+            self.flags.remove(CPUFlagStruct::Decimal);
         }
     }
 
@@ -109,16 +155,45 @@ impl CPU {
     pub fn set_zero(&mut self, value: bool) {
         if value {
             self.flags.insert(CPUFlagStruct::Zero);
+        } else {
+            self.flags.remove(CPUFlagStruct::Zero);
         }
     }
 
     pub fn set_carry(&mut self, value: bool) {
         if value {
             self.flags.insert(CPUFlagStruct::Carry);
+        } else {
+            self.flags.remove(CPUFlagStruct::Carry);
         }
     }
 }
 
+impl Savestate for CPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.a);
+        state.write(self.x);
+        state.write(self.y);
+        state.write(self.pc);
+        state.write(self.sp);
+        state.write(self.flags.bits());
+        state.write(self.irq.bits());
+        state.write(self.nmi);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.a = state.read()?;
+        self.x = state.read()?;
+        self.y = state.read()?;
+        self.pc = state.read()?;
+        self.sp = state.read()?;
+        self.flags = CPUFlagStruct::from_bits_retain(state.read()?);
+        self.irq = IRQSource::from_bits_retain(state.read()?);
+        self.nmi = state.read()?;
+        Ok(())
+    }
+}
+
 pub const fn get_mnemonic(opcode: u8) -> &'static str {
     MNEMONICS[opcode as usize].0
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, format_err};
+use log::{debug, warn};
+use crate::system::nes::file::{Mirroring, Region};
+
+/// Cartridge information for a known dump, used to correct bad or incomplete iNES headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameInfo {
+    pub title: String,
+    /// CRC32 over PRG and CHR ROM, without the header
+    pub crc32: u32,
+    /// SHA-1 over PRG and CHR ROM, used to tell apart dumps with colliding CRC32s
+    pub sha1: Option<[u8; 20]>,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub region: Region,
+}
+
+/// A game database keyed by PRG+CHR CRC32.
+///
+/// Databases are read from the NES 2.0 XML format (`nes20db.xml`), where every `<game>` carries
+/// a `<rom>` hash of the PRG+CHR data alongside its `<pcb>`, RAM and `<console>` description.
+#[derive(Debug, Default)]
+pub struct GameDatabase {
+    games: HashMap<u32, Vec<GameInfo>>,
+}
+
+impl GameDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: &P) -> anyhow::Result<Self> {
+        Self::from_xml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_xml(xml: &str) -> anyhow::Result<Self> {
+        let document = roxmltree::Document::parse(xml)?;
+        let mut database = Self::new();
+
+        for game in document.descendants().filter(|node| node.has_tag_name("game")) {
+            match parse_game(game) {
+                Ok(info) => database.insert(info),
+                Err(e) => warn!("Skipping game database entry at {:?}: {e}", document.text_pos_at(game.range().start)),
+            }
+        }
+
+        debug!("Loaded {} game database entries", database.len());
+        Ok(database)
+    }
+
+    pub fn insert(&mut self, info: GameInfo) {
+        self.games.entry(info.crc32).or_default().push(info);
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Finds the entry matching the CRC32, preferring one whose SHA-1 also matches
+    pub fn lookup(&self, crc32: u32, sha1: &[u8; 20]) -> Option<&GameInfo> {
+        let candidates = self.games.get(&crc32)?;
+        candidates
+            .iter()
+            .find(|info| info.sha1.as_ref() == Some(sha1))
+            .or_else(|| candidates.iter().find(|info| info.sha1.is_none()))
+    }
+}
+
+fn parse_game(game: roxmltree::Node) -> anyhow::Result<GameInfo> {
+    let child = |name: &str| game.children().find(|node| node.has_tag_name(name));
+    let size_of = |name: &str| -> anyhow::Result<usize> {
+        child(name).map_or(Ok(0), |node| parse_number(node.attribute("size").unwrap_or("0")))
+    };
+
+    let title = game
+        .attribute("name")
+        .map(str::to_owned)
+        .or_else(|| game.children().find(|node| node.is_comment()).and_then(|node| node.text()).map(|text| text.trim().to_owned()))
+        .unwrap_or_default();
+
+    let rom = child("rom").ok_or_else(|| format_err!("Missing <rom> element"))?;
+    let crc32 = u32::from_str_radix(rom.attribute("crc32").ok_or_else(|| format_err!("Missing ROM CRC32"))?, 16)?;
+    let sha1 = rom.attribute("sha1").map(parse_sha1).transpose()?;
+
+    let pcb = child("pcb").ok_or_else(|| format_err!("Missing <pcb> element"))?;
+    let mapper = parse_number(pcb.attribute("mapper").unwrap_or("0"))? as u16;
+    let submapper = parse_number(pcb.attribute("submapper").unwrap_or("0"))? as u8;
+    let battery = pcb.attribute("battery") == Some("1");
+    let mirroring = match pcb.attribute("mirroring") {
+        Some("H") => Mirroring::Horizontal,
+        Some("V") => Mirroring::Vertical,
+        Some("4") => Mirroring::FourScreen,
+        Some("1") => Mirroring::SingleScreenLower,
+        other => bail!("Unknown mirroring {other:?}"),
+    };
+
+    let region = match child("console").and_then(|node| node.attribute("region")) {
+        None | Some("0") => Region::NTSC,
+        Some("1") => Region::PAL,
+        Some("2") => Region::MultiRegion,
+        Some("3") => Region::Dendy,
+        Some(other) => bail!("Unknown region {other}"),
+    };
+
+    Ok(GameInfo {
+        title,
+        crc32,
+        sha1,
+        mapper,
+        submapper,
+        mirroring,
+        battery,
+        prg_ram_size: size_of("prgram")?,
+        prg_nvram_size: size_of("prgnvram")?,
+        chr_ram_size: size_of("chrram")?,
+        chr_nvram_size: size_of("chrnvram")?,
+        region,
+    })
+}
+
+fn parse_number(value: &str) -> anyhow::Result<usize> {
+    Ok(value.trim().parse()?)
+}
+
+fn parse_sha1(value: &str) -> anyhow::Result<[u8; 20]> {
+    let value = value.trim();
+    if value.len() != 40 || !value.is_ascii() {
+        bail!("Invalid SHA-1 {value}");
+    }
+
+    let mut hash = [0u8; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(hash)
+}
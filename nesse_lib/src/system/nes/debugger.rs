@@ -1,5 +1,53 @@
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::ppu::{PPUCtrl, PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::system::nes::NES;
 
+/// Size of a colour in the palette view
+pub const PALETTE_SWATCH_SIZE: usize = 8;
+
+const VIEWPORT_COLOR: [u8; 4] = [0xFF, 0x00, 0xFF, 0xFF];
+
+/// An RGBA8 image, row by row
+pub struct DebugImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl DebugImage {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            rgba: vec![0; width * height * 4],
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].copy_from_slice(&color);
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].try_into().unwrap()
+    }
+}
+
+/// A decoded OAM entry
+pub struct SpriteInfo {
+    pub index: u8,
+    pub x: u8,
+    pub y: u8,
+    pub tile: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// The sprite drawn with its palette and flips, 8 pixels wide and 8 or 16 high
+    pub image: DebugImage,
+}
+
 pub struct Debugger<'a> {
     nes: &'a NES,
 }
@@ -8,4 +56,149 @@ impl<'a> Debugger<'a> {
     pub fn new(nes: &'a NES) -> Debugger<'a> {
         Debugger { nes }
     }
-}
\ No newline at end of file
+
+    fn ppu(&self) -> &PPU {
+        &self.nes.bus.ppu
+    }
+
+    fn cartridge(&self) -> Option<&dyn Mapper> {
+        self.nes.bus.memory.cartridge()
+    }
+
+    /// The colour of a pixel value in one of the 8 palettes, 0 being the backdrop
+    fn color(&self, palette: u8, pixel: u8) -> [u8; 4] {
+        let addr = if pixel == 0 { 0x3F00 } else { 0x3F00 | (palette as u16 & 0x07) << 2 | pixel as u16 };
+        let ppu = self.ppu();
+        ppu.rgb_palette.rgba(ppu.read_palette(addr) as u16)
+    }
+
+    /// Draws the 8x8 tile at the pattern address to the given position of the image
+    fn draw_tile(&self, image: &mut DebugImage, (x, y): (usize, usize), addr: u16, palette: u8, flip_horizontal: bool, flip_vertical: bool) {
+        for row in 0..8 {
+            let low = self.ppu().bus_peek(addr + row, self.cartridge());
+            let high = self.ppu().bus_peek(addr + row + 8, self.cartridge());
+            let image_row = if flip_vertical { 7 - row as usize } else { row as usize };
+            for column in 0..8 {
+                let bit = 7 - column;
+                let pixel = ((high >> bit) & 0x01) << 1 | ((low >> bit) & 0x01);
+                let image_column = if flip_horizontal { 7 - column } else { column };
+                image.set_pixel(x + image_column, y + image_row, self.color(palette, pixel));
+            }
+        }
+    }
+
+    /// One of the two 128x128 pattern tables, drawn with one of the 8 palettes
+    pub fn pattern_table(&self, table: u8, palette: u8) -> DebugImage {
+        let mut image = DebugImage::new(128, 128);
+        let base = (table as u16 & 0x01) << 12;
+        for tile in 0..256 {
+            let x = (tile % 16) * 8;
+            let y = (tile / 16) * 8;
+            self.draw_tile(&mut image, (x, y), base | (tile as u16) << 4, palette, false, false);
+        }
+        image
+    }
+
+    /// The four nametables in a 512x480 image, optionally outlining the area the scroll registers select
+    pub fn nametables(&self, viewport: bool) -> DebugImage {
+        let mut image = DebugImage::new(SCREEN_WIDTH * 2, SCREEN_HEIGHT * 2);
+        let ppu = self.ppu();
+        let pattern_table = if ppu.ctrl.contains(PPUCtrl::BackgroundPatternTable) { 0x1000 } else { 0 };
+
+        for nametable in 0..4 {
+            let base = 0x2000 + nametable as u16 * 0x400;
+            let origin_x = (nametable % 2) * SCREEN_WIDTH;
+            let origin_y = (nametable / 2) * SCREEN_HEIGHT;
+            for tile_y in 0..30u16 {
+                for tile_x in 0..32u16 {
+                    let tile = ppu.bus_peek(base + tile_y * 32 + tile_x, self.cartridge());
+                    let attribute = ppu.bus_peek(base + 0x3C0 + (tile_y / 4) * 8 + tile_x / 4, self.cartridge());
+                    let shift = ((tile_y & 0x02) << 1) | (tile_x & 0x02);
+                    let palette = (attribute >> shift) & 0x03;
+
+                    let x = origin_x + tile_x as usize * 8;
+                    let y = origin_y + tile_y as usize * 8;
+                    self.draw_tile(&mut image, (x, y), pattern_table | (tile as u16) << 4, palette, false, false);
+                }
+            }
+        }
+
+        if viewport {
+            // The scroll of the next frame, as latched in t and fine X
+            let scroll_x = ((ppu.t >> 10) & 0x01) as usize * SCREEN_WIDTH + (ppu.t & 0x1F) as usize * 8 + ppu.x as usize;
+            let scroll_y = ((ppu.t >> 11) & 0x01) as usize * SCREEN_HEIGHT + ((ppu.t >> 5) & 0x1F) as usize * 8 + ((ppu.t >> 12) & 0x07) as usize;
+            for offset in 0..SCREEN_WIDTH {
+                let x = (scroll_x + offset) % image.width;
+                image.set_pixel(x, scroll_y % image.height, VIEWPORT_COLOR);
+                image.set_pixel(x, (scroll_y + SCREEN_HEIGHT - 1) % image.height, VIEWPORT_COLOR);
+            }
+            for offset in 0..SCREEN_HEIGHT {
+                let y = (scroll_y + offset) % image.height;
+                image.set_pixel(scroll_x % image.width, y, VIEWPORT_COLOR);
+                image.set_pixel((scroll_x + SCREEN_WIDTH - 1) % image.width, y, VIEWPORT_COLOR);
+            }
+        }
+
+        image
+    }
+
+    /// Palette RAM as swatches, the background palettes in the first row and the sprite palettes in the second
+    pub fn palettes(&self) -> DebugImage {
+        let mut image = DebugImage::new(16 * PALETTE_SWATCH_SIZE, 2 * PALETTE_SWATCH_SIZE);
+        let ppu = self.ppu();
+        for entry in 0..32 {
+            let color = ppu.rgb_palette.rgba(ppu.read_palette(0x3F00 + entry as u16) as u16);
+            let x = (entry % 16) * PALETTE_SWATCH_SIZE;
+            let y = (entry / 16) * PALETTE_SWATCH_SIZE;
+            for row in 0..PALETTE_SWATCH_SIZE {
+                for column in 0..PALETTE_SWATCH_SIZE {
+                    image.set_pixel(x + column, y + row, color);
+                }
+            }
+        }
+        image
+    }
+
+    /// All 64 sprites in OAM
+    pub fn sprites(&self) -> Vec<SpriteInfo> {
+        let ppu = self.ppu();
+        let height = ppu.sprite_height();
+
+        ppu.oam
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, entry)| {
+                let (y, tile, attribute, x) = (entry[0], entry[1], entry[2], entry[3]);
+                let palette = (attribute & 0x03) + 4;
+                let flip_horizontal = attribute & 0x40 != 0;
+                let flip_vertical = attribute & 0x80 != 0;
+
+                let mut image = DebugImage::new(8, height as usize);
+                if height == 16 {
+                    let table = (tile as u16 & 0x01) << 12;
+                    let top = table | (tile as u16 & 0xFE) << 4;
+                    let bottom = top + 16;
+                    // Flipping vertically also swaps the two tiles
+                    let (top, bottom) = if flip_vertical { (bottom, top) } else { (top, bottom) };
+                    self.draw_tile(&mut image, (0, 0), top, palette, flip_horizontal, flip_vertical);
+                    self.draw_tile(&mut image, (0, 8), bottom, palette, flip_horizontal, flip_vertical);
+                } else {
+                    let table = if ppu.ctrl.contains(PPUCtrl::SpritePatternTable) { 0x1000 } else { 0 };
+                    self.draw_tile(&mut image, (0, 0), table | (tile as u16) << 4, palette, flip_horizontal, flip_vertical);
+                }
+
+                SpriteInfo {
+                    index: index as u8,
+                    x,
+                    y,
+                    tile,
+                    palette,
+                    behind_background: attribute & 0x20 != 0,
+                    flip_horizontal,
+                    flip_vertical,
+                    image,
+                }
+            })
+            .collect()
+    }
+}
@@ -1,8 +1,11 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::io::{Cursor, Read};
 use std::path::Path;
-use log::debug;
+use log::{debug, info};
+use sha1::{Digest, Sha1};
 use static_assertions::const_assert_eq;
+use crate::system::nes::database::{GameDatabase, GameInfo};
+use crate::system::nes::state::{StateReader, StateValue};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -15,27 +18,88 @@ pub struct NESFileHeader {
     pub flags8: u8,
     pub flags9: u8,
     pub flags10: u8,
-    pub unused: [u8; 4], // 4 bytes
+    // The remaining bytes are only meaningful in NES 2.0 headers
+    pub flags11: u8,
+    pub flags12: u8,
+    pub flags13: u8,
+    pub flags14: u8,
+    pub flags15: u8,
 }
 
 // Ensures the struct is 16 bytes in total.
 const_assert_eq!(size_of::<NESFileHeader>(), 16);
 
+const TRAINER_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+/// Boards that switch the mirroring save it
+impl StateValue for Mirroring {
+    fn write(self, data: &mut Vec<u8>) {
+        data.push(self as u8);
+    }
+
+    fn read(state: &mut StateReader) -> anyhow::Result<Self> {
+        Ok(match state.read::<u8>()? {
+            0 => Mirroring::Horizontal,
+            1 => Mirroring::Vertical,
+            2 => Mirroring::FourScreen,
+            3 => Mirroring::SingleScreenLower,
+            4 => Mirroring::SingleScreenUpper,
+            value => anyhow::bail!("Invalid mirroring {value} in save state"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    NTSC,
+    PAL,
+    MultiRegion,
+    Dendy,
+}
+
 #[derive(Debug)]
 pub struct NESFileData {
+    pub trainer: Option<Vec<u8>>,
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
 }
 
+/// Hashes over the PRG and CHR ROM data, excluding header and trainer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomHashes {
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+    pub prg_crc32: u32,
+    pub chr_crc32: u32,
+}
+
 #[derive(Debug)]
 pub struct NESFile {
     pub header: NESFileHeader,
     pub data: NESFileData,
+    pub hashes: RomHashes,
+    /// The game database entry the header was corrected from, if any
+    pub game: Option<GameInfo>,
+    /// Mirroring the header's flag bits can't describe, like single-screen boards from UNIF or the database
+    pub mirroring_override: Option<Mirroring>,
 }
 
 impl NESFile {
     pub fn new<P: AsRef<Path>>(rom_path: &P) -> std::io::Result<Self> {
-        let mut file = File::open(rom_path)?;
+        Self::from_bytes(&fs::read(rom_path)?)
+    }
+
+    pub fn from_bytes(rom: &[u8]) -> std::io::Result<Self> {
+        let mut file = Cursor::new(rom);
 
         // Read the 16-byte header
         let mut raw_header = [0u8; 16];
@@ -45,9 +109,17 @@ impl NESFile {
         // SAFETY: We know raw_header is exactly 16 bytes, matching the struct’s size.
         let header: NESFileHeader = unsafe { std::mem::transmute(raw_header) };
 
+        let trainer = if header.flags6 & 0x04 != 0 {
+            let mut trainer = vec![0u8; TRAINER_SIZE];
+            file.read_exact(&mut trainer)?;
+            Some(trainer)
+        } else {
+            None
+        };
+
         // Prepare to read PRG/CHR data based on header
-        let prg_len = (header.prg_size as usize) * 16384; // PRG is in 16 KB units
-        let chr_len = (header.chr_size as usize) * 8192;  // CHR is in 8 KB units
+        let prg_len = prg_rom_units(&header) * 16384; // PRG is in 16 KB units
+        let chr_len = chr_rom_units(&header) * 8192;  // CHR is in 8 KB units
 
         // Read PRG ROM
         let mut prg_rom = vec![0u8; prg_len];
@@ -61,12 +133,19 @@ impl NESFile {
 
         debug!("CHR ROM size: {}", prg_rom.len());
 
-        // TODO: Trainers
+        Ok(Self::from_parts(header, NESFileData { trainer, prg_rom, chr_rom }))
+    }
+
+    pub fn from_parts(header: NESFileHeader, data: NESFileData) -> Self {
+        let hashes = RomHashes::new(&data.prg_rom, &data.chr_rom);
 
-        Ok(Self {
+        Self {
             header,
-            data: NESFileData { prg_rom, chr_rom },
-        })
+            data,
+            hashes,
+            game: None,
+            mirroring_override: None,
+        }
     }
 
 
@@ -74,7 +153,205 @@ impl NESFile {
         self.header.magic_number == 0x1A53454E
     }
 
+    pub fn is_nes2(&self) -> bool {
+        self.header.flags7 & 0x0C == 0x08
+    }
+
     pub fn mapper_lower(&self) -> u8 {
         self.header.flags6 & 0x0F
     }
-}
\ No newline at end of file
+
+    pub fn mapper(&self) -> u16 {
+        let lower = (self.header.flags6 >> 4) as u16;
+        if self.is_nes2() {
+            lower | (self.header.flags7 & 0xF0) as u16 | ((self.header.flags8 & 0x0F) as u16) << 8
+        } else if self.header.flags7 & 0x0C == 0 {
+            lower | (self.header.flags7 & 0xF0) as u16
+        } else {
+            // Archaic iNES headers often contain garbage ("DiskDude!") from byte 7 onwards
+            lower
+        }
+    }
+
+    pub fn submapper(&self) -> u8 {
+        if self.is_nes2() {
+            self.header.flags8 >> 4
+        } else {
+            0
+        }
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        if let Some(mirroring) = self.mirroring_override {
+            mirroring
+        } else if self.header.flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if self.header.flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.header.flags6 & 0x02 != 0
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        self.header.flags6 & 0x04 != 0
+    }
+
+    pub fn prg_ram_size(&self) -> usize {
+        if self.is_nes2() {
+            shift_size(self.header.flags10 & 0x0F)
+        } else if self.has_battery() {
+            0
+        } else {
+            self.header.flags8.max(1) as usize * 8192
+        }
+    }
+
+    pub fn prg_nvram_size(&self) -> usize {
+        if self.is_nes2() {
+            shift_size(self.header.flags10 >> 4)
+        } else if self.has_battery() {
+            self.header.flags8.max(1) as usize * 8192
+        } else {
+            0
+        }
+    }
+
+    pub fn chr_ram_size(&self) -> usize {
+        if self.is_nes2() {
+            shift_size(self.header.flags11 & 0x0F)
+        } else if self.data.chr_rom.is_empty() {
+            8192
+        } else {
+            0
+        }
+    }
+
+    pub fn chr_nvram_size(&self) -> usize {
+        if self.is_nes2() {
+            shift_size(self.header.flags11 >> 4)
+        } else {
+            0
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        if self.is_nes2() {
+            match self.header.flags12 & 0x03 {
+                0 => Region::NTSC,
+                1 => Region::PAL,
+                2 => Region::MultiRegion,
+                _ => Region::Dendy,
+            }
+        } else if self.header.flags9 & 0x01 != 0 {
+            Region::PAL
+        } else {
+            Region::NTSC
+        }
+    }
+
+    /// The NES 2.0 default expansion device, which names the input devices the game expects
+    pub fn default_expansion_device(&self) -> u8 {
+        if self.is_nes2() { self.header.flags15 & 0x3F } else { 0 }
+    }
+
+    pub fn game_title(&self) -> Option<&str> {
+        self.game.as_ref().map(|game| game.title.as_str())
+    }
+
+    /// Looks the ROM up in the database and, on a match, rewrites the header as NES 2.0 from the entry
+    pub fn apply_database(&mut self, database: &GameDatabase) -> Option<&GameInfo> {
+        let game = database.lookup(self.hashes.crc32, &self.hashes.sha1)?.clone();
+        info!("Found \"{}\" in the game database (CRC32 0x{:08X})", game.title, game.crc32);
+
+        let prg_units = self.data.prg_rom.len() / 16384;
+        let chr_units = self.data.chr_rom.len() / 8192;
+        // Single-screen boards have no header bits
+        self.mirroring_override = match game.mirroring {
+            Mirroring::SingleScreenLower | Mirroring::SingleScreenUpper => Some(game.mirroring),
+            _ => None,
+        };
+        let header = &mut self.header;
+
+        header.flags6 = ((game.mapper & 0x0F) as u8) << 4 | (header.flags6 & 0x04);
+        match game.mirroring {
+            Mirroring::Vertical => header.flags6 |= 0x01,
+            Mirroring::FourScreen => header.flags6 |= 0x08,
+            _ => {}
+        }
+        if game.battery {
+            header.flags6 |= 0x02;
+        }
+
+        header.flags7 = (game.mapper & 0xF0) as u8 | 0x08 | (header.flags7 & 0x03);
+        header.flags8 = game.submapper << 4 | ((game.mapper >> 8) & 0x0F) as u8;
+        header.flags9 = ((chr_units >> 8) as u8 & 0x0F) << 4 | ((prg_units >> 8) as u8 & 0x0F);
+        header.flags10 = size_shift(game.prg_nvram_size) << 4 | size_shift(game.prg_ram_size);
+        header.flags11 = size_shift(game.chr_nvram_size) << 4 | size_shift(game.chr_ram_size);
+        header.flags12 = match game.region {
+            Region::NTSC => 0,
+            Region::PAL => 1,
+            Region::MultiRegion => 2,
+            Region::Dendy => 3,
+        };
+
+        self.game = Some(game);
+        self.game.as_ref()
+    }
+}
+
+impl RomHashes {
+    fn new(prg_rom: &[u8], chr_rom: &[u8]) -> Self {
+        let mut crc32 = crc32fast::Hasher::new();
+        crc32.update(prg_rom);
+        crc32.update(chr_rom);
+
+        let mut sha1 = Sha1::new();
+        sha1.update(prg_rom);
+        sha1.update(chr_rom);
+
+        Self {
+            crc32: crc32.finalize(),
+            sha1: sha1.finalize().into(),
+            prg_crc32: crc32fast::hash(prg_rom),
+            chr_crc32: crc32fast::hash(chr_rom),
+        }
+    }
+}
+
+fn prg_rom_units(header: &NESFileHeader) -> usize {
+    if header.flags7 & 0x0C == 0x08 {
+        header.prg_size as usize | ((header.flags9 & 0x0F) as usize) << 8
+    } else {
+        header.prg_size as usize
+    }
+}
+
+fn chr_rom_units(header: &NESFileHeader) -> usize {
+    if header.flags7 & 0x0C == 0x08 {
+        header.chr_size as usize | ((header.flags9 >> 4) as usize) << 8
+    } else {
+        header.chr_size as usize
+    }
+}
+
+/// NES 2.0 RAM sizes are stored as a shift count of 64 bytes, where 0 means no RAM
+fn shift_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+fn size_shift(size: usize) -> u8 {
+    if size == 0 {
+        0
+    } else {
+        (size.max(128) / 64).next_power_of_two().trailing_zeros() as u8
+    }
+}
@@ -0,0 +1,206 @@
+use std::any::Any;
+use log::info;
+use crate::system::nes::input::controller::{Buttons, Controller};
+use crate::system::nes::input::data_recorder::DataRecorder;
+use crate::system::nes::input::family_keyboard::FamilyKeyboard;
+use crate::system::nes::input::four_score::{FamicomFourPlayer, FourScore};
+use crate::system::nes::input::power_pad::PowerPad;
+use crate::system::nes::input::snes_mouse::SnesMouse;
+use crate::system::nes::input::vaus::Vaus;
+use crate::system::nes::input::zapper::Zapper;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+pub mod controller;
+pub mod zapper;
+pub mod four_score;
+pub mod vaus;
+pub mod power_pad;
+pub mod snes_mouse;
+pub mod data_recorder;
+pub mod family_keyboard;
+
+/// Where an input device is plugged in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPort {
+    /// Read through $4016
+    Port1,
+    /// Read through $4017
+    Port2,
+    /// The Famicom's expansion port, read through both registers
+    Expansion,
+}
+
+/// A device on a controller port or the Famicom expansion port. Its save state holds what it latched
+/// from the console, not the buttons the player holds.
+pub trait InputDevice: Savestate {
+    /// CPU write to $4016. Bit 0 is the strobe of both ports, bits 1-2 only reach the expansion port.
+    fn write(&mut self, value: u8);
+
+    /// CPU read of $4016 (port 0) or $4017 (port 1), returning the data lines D0-D4 the device drives
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8;
+
+    /// Reads without side effects
+    fn peek(&self, port: usize, ppu: &PPU) -> u8;
+
+    /// Sets the buttons of one of the device's joypads, returns false if it has none at that index
+    fn set_buttons(&mut self, _index: usize, _buttons: Buttons) -> bool {
+        false
+    }
+
+    /// Called every CPU cycle, for devices that keep time
+    fn clock(&mut self) {}
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The devices connected to the console, standard controllers in both ports by default
+pub struct Input {
+    pub ports: [Option<Box<dyn InputDevice>>; 2],
+    pub expansion_port: Option<Box<dyn InputDevice>>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            ports: [Some(Box::new(Controller::new())), Some(Box::new(Controller::new()))],
+            expansion_port: None,
+        }
+    }
+
+    fn slot(&self, port: InputPort) -> &Option<Box<dyn InputDevice>> {
+        match port {
+            InputPort::Port1 => &self.ports[0],
+            InputPort::Port2 => &self.ports[1],
+            InputPort::Expansion => &self.expansion_port,
+        }
+    }
+
+    fn slot_mut(&mut self, port: InputPort) -> &mut Option<Box<dyn InputDevice>> {
+        match port {
+            InputPort::Port1 => &mut self.ports[0],
+            InputPort::Port2 => &mut self.ports[1],
+            InputPort::Expansion => &mut self.expansion_port,
+        }
+    }
+
+    /// Plugs a device into a port, or unplugs it with `None`
+    pub fn connect(&mut self, port: InputPort, device: Option<Box<dyn InputDevice>>) {
+        *self.slot_mut(port) = device;
+    }
+
+    /// The device in a port, if it is a `T`
+    pub fn device<T: InputDevice + 'static>(&self, port: InputPort) -> Option<&T> {
+        self.slot(port).as_ref()?.as_any().downcast_ref()
+    }
+
+    pub fn device_mut<T: InputDevice + 'static>(&mut self, port: InputPort) -> Option<&mut T> {
+        self.slot_mut(port).as_mut()?.as_any_mut().downcast_mut()
+    }
+
+    /// Sets the buttons of player 1-4's joypad, numbered from 0. Players 3 and 4 are on the second
+    /// joypad of a Four Score, or on a Famicom adapter in the expansion port.
+    pub fn set_player_buttons(&mut self, player: usize, buttons: Buttons) -> bool {
+        let (port, index) = (player % 2, player / 2);
+        if index > 1 {
+            return false;
+        }
+        if self.ports[port].as_mut().is_some_and(|device| device.set_buttons(index, buttons)) {
+            return true;
+        }
+        index == 1 && self.expansion_port.as_mut().is_some_and(|device| device.set_buttons(port, buttons))
+    }
+
+    /// Connects the devices for an NES 2.0 default expansion device, leaving the ports alone for unsupported ones
+    pub fn connect_default(&mut self, expansion_device: u8) {
+        let controller = || -> Option<Box<dyn InputDevice>> { Some(Box::new(Controller::new())) };
+        let (port1, port2, expansion): (_, _, Option<Box<dyn InputDevice>>) = match expansion_device {
+            0x01 => (controller(), controller(), None),
+            0x02 => (Some(Box::new(FourScore::new(0)) as _), Some(Box::new(FourScore::new(1)) as _), None),
+            0x03 => (controller(), controller(), Some(Box::new(FamicomFourPlayer::new()))),
+            0x08 => (controller(), Some(Box::new(Zapper::new()) as _), None),
+            0x09 => (Some(Box::new(Zapper::new()) as _), Some(Box::new(Zapper::new()) as _), None),
+            // Both sides of the mat are the same device
+            0x0B | 0x0C => (controller(), Some(Box::new(PowerPad::new()) as _), None),
+            0x0F => (controller(), Some(Box::new(Vaus::new(false)) as _), None),
+            0x10 => (controller(), controller(), Some(Box::new(Vaus::new(true)))),
+            0x20 => (controller(), controller(), Some(Box::new(DataRecorder::new()))),
+            0x23 => (controller(), controller(), Some(Box::new(FamilyKeyboard::new()))),
+            0x29 => (controller(), Some(Box::new(SnesMouse::new()) as _), None),
+            _ => return,
+        };
+
+        info!("Connecting the input devices for default expansion device 0x{expansion_device:02X}");
+        self.ports = [port1, port2];
+        self.expansion_port = expansion;
+    }
+
+    /// The data recorder, on its own or plugged into the Family BASIC keyboard
+    pub fn data_recorder_mut(&mut self) -> Option<&mut DataRecorder> {
+        if self.device::<FamilyKeyboard>(InputPort::Expansion).is_some() {
+            return self.device_mut::<FamilyKeyboard>(InputPort::Expansion).map(|keyboard| &mut keyboard.data_recorder);
+        }
+        self.device_mut(InputPort::Expansion)
+    }
+
+    pub fn clock(&mut self) {
+        for device in self.ports.iter_mut().chain([&mut self.expansion_port]).flatten() {
+            device.clock();
+        }
+    }
+
+    /// CPU write to $4016, which goes to all devices
+    pub fn write(&mut self, value: u8) {
+        for device in self.ports.iter_mut().chain([&mut self.expansion_port]).flatten() {
+            device.write(value);
+        }
+    }
+
+    /// CPU read of $4016 or $4017, returning the bits driven by the port's device and the expansion port
+    pub fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        let port_value = self.ports[port].as_mut().map_or(0, |device| device.read(port, ppu));
+        let expansion_value = self.expansion_port.as_mut().map_or(0, |device| device.read(port, ppu));
+        (port_value | expansion_value) & 0x1F
+    }
+
+    pub fn peek(&self, port: usize, ppu: &PPU) -> u8 {
+        let port_value = self.ports[port].as_ref().map_or(0, |device| device.peek(port, ppu));
+        let expansion_value = self.expansion_port.as_ref().map_or(0, |device| device.peek(port, ppu));
+        (port_value | expansion_value) & 0x1F
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Each port's device state is saved as a block, empty for an empty port. States are restored into
+/// the devices connected when loading, and ports that were empty when saving are left alone.
+impl Savestate for Input {
+    fn save_state(&self, state: &mut StateWriter) {
+        for device in self.ports.iter().chain([&self.expansion_port]) {
+            let mut device_state = StateWriter::new();
+            if let Some(device) = device {
+                device.save_state(&mut device_state);
+            }
+            state.write_bytes(&device_state.into_bytes());
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        let version = state.version;
+        for device in self.ports.iter_mut().chain([&mut self.expansion_port]) {
+            let data = state.read_bytes()?;
+            if let Some(device) = device {
+                if !data.is_empty() {
+                    device.load_state(&mut StateReader::new(data, version))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::any::Any;
+use bitflags::bitflags;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+bitflags! {
+    /// Buttons of a standard controller, in the order they are shifted out
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct Buttons : u8 {
+        const A = 0b0000_0001;
+        const B = 0b0000_0010;
+        const Select = 0b0000_0100;
+        const Start = 0b0000_1000;
+        const Up = 0b0001_0000;
+        const Down = 0b0010_0000;
+        const Left = 0b0100_0000;
+        const Right = 0b1000_0000;
+    }
+}
+
+/// A standard joypad, whose 4021 shift register latches the buttons while the strobe bit of $4016 is set
+#[derive(Debug, Default)]
+pub struct Controller {
+    /// The buttons currently held down
+    pub buttons: Buttons,
+    shift_register: u8,
+    strobe: bool,
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bit 0 of $4016 writes
+    pub fn write_strobe(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        // The register reloads continuously while the strobe is high, so it holds the buttons from when it fell
+        if self.strobe || strobe {
+            self.shift_register = self.buttons.bits();
+        }
+        self.strobe = strobe;
+    }
+
+    /// Shifts out the next button in bit 0
+    pub fn shift(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 0x01;
+        }
+
+        let bit = self.shift_register & 0x01;
+        // Official controllers shift in 1s, so reads after the 8 buttons return 1
+        self.shift_register = self.shift_register >> 1 | 0x80;
+        bit
+    }
+
+    pub fn peek_bit(&self) -> u8 {
+        if self.strobe { self.buttons.bits() & 0x01 } else { self.shift_register & 0x01 }
+    }
+}
+
+impl InputDevice for Controller {
+    fn write(&mut self, value: u8) {
+        self.write_strobe(value);
+    }
+
+    fn read(&mut self, _port: usize, _ppu: &PPU) -> u8 {
+        self.shift()
+    }
+
+    fn peek(&self, _port: usize, _ppu: &PPU) -> u8 {
+        self.peek_bit()
+    }
+
+    fn set_buttons(&mut self, index: usize, buttons: Buttons) -> bool {
+        if index == 0 {
+            self.buttons = buttons;
+        }
+        index == 0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for Controller {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.shift_register);
+        state.write(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.shift_register = state.read()?;
+        self.strobe = state.read()?;
+        Ok(())
+    }
+}
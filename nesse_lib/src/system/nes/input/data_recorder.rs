@@ -0,0 +1,178 @@
+use std::any::Any;
+use std::path::Path;
+use crate::system::nes::file::Region;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+use crate::system::nes::wav::{WavFile, WavWriter};
+
+/// Sample rate of newly recorded tapes
+pub const TAPE_SAMPLE_RATE: u32 = 44100;
+/// How far the tape signal has to swing past zero to flip the read line, so noise doesn't toggle it
+const HYSTERESIS: f32 = 0.05;
+/// Amplitude of the square wave recorded from the write line
+const RECORD_LEVEL: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeState {
+    Stopped,
+    Playing,
+    Recording,
+}
+
+/// The Famicom data recorder, a cassette deck on the expansion port. The tape signal is read
+/// through D1 of $4016 and bit 2 of $4016 writes is recorded onto it.
+#[derive(Debug)]
+pub struct DataRecorder {
+    pub state: TapeState,
+    tape: Vec<f32>,
+    sample_rate: u32,
+    clock_rate: u32,
+    /// Position on the tape in samples
+    position: f64,
+    input: bool,
+    output: bool,
+}
+
+impl DataRecorder {
+    pub fn new() -> Self {
+        Self {
+            state: TapeState::Stopped,
+            tape: vec![],
+            sample_rate: TAPE_SAMPLE_RATE,
+            // The data recorder was only sold for the Famicom
+            clock_rate: Region::NTSC.cpu_clock(),
+            position: 0.0,
+            input: false,
+            output: false,
+        }
+    }
+
+    /// Inserts a tape holding the given samples, rewound to the start
+    pub fn insert_tape(&mut self, samples: Vec<f32>, sample_rate: u32) {
+        self.tape = samples;
+        self.sample_rate = sample_rate;
+        self.state = TapeState::Stopped;
+        self.rewind();
+    }
+
+    /// Inserts a tape recorded to a WAV file
+    pub fn load_wav<P: AsRef<Path>>(&mut self, path: &P) -> anyhow::Result<()> {
+        let wav = WavFile::new(path)?;
+        self.insert_tape(wav.samples, wav.sample_rate);
+        Ok(())
+    }
+
+    /// Writes the whole tape to a WAV file
+    pub fn save_wav<P: AsRef<Path>>(&self, path: &P) -> anyhow::Result<()> {
+        let mut writer = WavWriter::create(path, self.sample_rate)?;
+        writer.write_samples(&self.tape)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn tape(&self) -> &[f32] {
+        &self.tape
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Position on the tape in seconds
+    pub fn position(&self) -> f64 {
+        self.position / self.sample_rate as f64
+    }
+
+    pub fn play(&mut self) {
+        self.state = TapeState::Playing;
+    }
+
+    /// Starts recording at the current position, erasing the rest of the tape
+    pub fn record(&mut self) {
+        self.tape.truncate(self.position as usize);
+        self.state = TapeState::Recording;
+    }
+
+    pub fn stop(&mut self) {
+        self.state = TapeState::Stopped;
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0.0;
+        self.input = false;
+    }
+}
+
+impl Default for DataRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputDevice for DataRecorder {
+    fn write(&mut self, value: u8) {
+        self.output = value & 0x04 != 0;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        self.peek(port, ppu)
+    }
+
+    fn peek(&self, port: usize, _ppu: &PPU) -> u8 {
+        if port == 0 { (self.input as u8) << 1 } else { 0 }
+    }
+
+    fn clock(&mut self) {
+        if self.state == TapeState::Stopped {
+            return;
+        }
+        self.position += self.sample_rate as f64 / self.clock_rate as f64;
+
+        match self.state {
+            TapeState::Playing => match self.tape.get(self.position as usize) {
+                Some(&sample) if sample > HYSTERESIS => self.input = true,
+                Some(&sample) if sample < -HYSTERESIS => self.input = false,
+                Some(_) => {}
+                None => self.state = TapeState::Stopped,
+            },
+            TapeState::Recording => {
+                let level = if self.output { RECORD_LEVEL } else { -RECORD_LEVEL };
+                while (self.tape.len() as f64) < self.position {
+                    self.tape.push(level);
+                }
+            }
+            TapeState::Stopped => {}
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The tape itself isn't saved, only where it is and what the deck is doing with it
+impl Savestate for DataRecorder {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.state as u8);
+        state.write(self.position);
+        state.write(self.input);
+        state.write(self.output);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.state = match state.read::<u8>()? {
+            1 => TapeState::Playing,
+            2 => TapeState::Recording,
+            _ => TapeState::Stopped,
+        };
+        self.position = state.read()?;
+        self.input = state.read()?;
+        self.output = state.read()?;
+        Ok(())
+    }
+}
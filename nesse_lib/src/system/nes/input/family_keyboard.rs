@@ -0,0 +1,136 @@
+use std::any::Any;
+use crate::system::nes::input::data_recorder::DataRecorder;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const ROWS: usize = 9;
+
+/// A key of the Family BASIC keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    F1, F2, F3, F4, F5, F6, F7, F8,
+    Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Minus, Caret, Yen, Stop, Escape, At, LeftBracket, RightBracket, Return,
+    Control, Semicolon, Colon, LeftShift, RightShift, Comma, Period, Slash, Underscore,
+    Graph, Space, Kana, Clear, Insert, Delete, Up, Down, Left, Right,
+}
+
+/// The keys read in each row and column, in the order of D1-D4
+const MATRIX: [[[Key; 4]; 2]; ROWS] = [
+    [[Key::F8, Key::Return, Key::LeftBracket, Key::RightBracket], [Key::Kana, Key::RightShift, Key::Yen, Key::Stop]],
+    [[Key::F7, Key::At, Key::Colon, Key::Semicolon], [Key::Underscore, Key::Slash, Key::Minus, Key::Caret]],
+    [[Key::F6, Key::O, Key::L, Key::K], [Key::Period, Key::Comma, Key::P, Key::Num0]],
+    [[Key::F5, Key::I, Key::U, Key::J], [Key::M, Key::N, Key::Num9, Key::Num8]],
+    [[Key::F4, Key::Y, Key::G, Key::H], [Key::B, Key::V, Key::Num7, Key::Num6]],
+    [[Key::F3, Key::T, Key::R, Key::D], [Key::F, Key::C, Key::Num5, Key::Num4]],
+    [[Key::F2, Key::W, Key::S, Key::A], [Key::X, Key::Z, Key::E, Key::Num3]],
+    [[Key::F1, Key::Escape, Key::Q, Key::Control], [Key::LeftShift, Key::Graph, Key::Num1, Key::Num2]],
+    [[Key::Clear, Key::Up, Key::Right, Key::Left], [Key::Down, Key::Space, Key::Delete, Key::Insert]],
+];
+
+/// The Family BASIC keyboard on the expansion port. Writes to $4016 select one half of a row of its
+/// 9x8 matrix, whose 4 keys are read active low on D1-D4 of $4017. The data recorder plugs into it.
+#[derive(Debug, Default)]
+pub struct FamilyKeyboard {
+    pub data_recorder: DataRecorder,
+    /// Pressed keys per row and column, bit n is read on D(n + 1)
+    pressed: [[u8; 2]; ROWS],
+    row: usize,
+    column: usize,
+    enabled: bool,
+}
+
+impl FamilyKeyboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_key(&mut self, key: Key, pressed: bool) {
+        for (row, columns) in MATRIX.iter().enumerate() {
+            for (column, keys) in columns.iter().enumerate() {
+                if let Some(bit) = keys.iter().position(|matrix_key| *matrix_key == key) {
+                    let mask = 1 << bit;
+                    let bits = &mut self.pressed[row][column];
+                    if pressed { *bits |= mask } else { *bits &= !mask }
+                }
+            }
+        }
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        MATRIX.iter().zip(&self.pressed).any(|(columns, pressed)| {
+            columns.iter().zip(pressed).any(|(keys, bits)| {
+                keys.iter().enumerate().any(|(bit, matrix_key)| *matrix_key == key && bits >> bit & 0x01 != 0)
+            })
+        })
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed = [[0; 2]; ROWS];
+    }
+}
+
+impl InputDevice for FamilyKeyboard {
+    fn write(&mut self, value: u8) {
+        self.data_recorder.write(value);
+
+        let column = (value >> 1 & 0x01) as usize;
+        // Going back to the first column advances to the next row
+        if self.column == 1 && column == 0 {
+            self.row += 1;
+        }
+        if value & 0x01 != 0 {
+            self.row = 0;
+        }
+        self.column = column;
+        self.enabled = value & 0x04 != 0;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        self.peek(port, ppu)
+    }
+
+    fn peek(&self, port: usize, ppu: &PPU) -> u8 {
+        if port == 0 {
+            return self.data_recorder.peek(port, ppu);
+        }
+        // A disabled matrix reads as all keys pressed. Past the last row nothing is pressed,
+        // which is how Family BASIC detects the keyboard.
+        if !self.enabled {
+            return 0;
+        }
+        let pressed = self.pressed.get(self.row).map_or(0, |columns| columns[self.column]);
+        !pressed << 1 & 0x1E
+    }
+
+    fn clock(&mut self) {
+        self.data_recorder.clock();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for FamilyKeyboard {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.data_recorder.save_state(state);
+        state.write(self.row);
+        state.write(self.column);
+        state.write(self.enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.data_recorder.load_state(state)?;
+        self.row = state.read()?;
+        self.column = state.read::<usize>()? & 0x01;
+        self.enabled = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::any::Any;
+use crate::system::nes::input::controller::{Buttons, Controller};
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The ID shifted out after both joypads, which tells games the adapter is connected
+const SIGNATURES: [u8; 2] = [0b0000_1000, 0b0000_0100];
+
+/// One port's half of the NES Four Score: 24 reads return the buttons of players 1 and 3 (or 2 and 4)
+/// followed by the adapter's signature
+#[derive(Debug)]
+pub struct FourScore {
+    /// The joypads of player 1 and 3 on port 0, 2 and 4 on port 1
+    pub controllers: [Controller; 2],
+    signature: u8,
+    shift_register: u32,
+    strobe: bool,
+}
+
+impl FourScore {
+    pub fn new(port: usize) -> Self {
+        Self {
+            controllers: [Controller::new(), Controller::new()],
+            signature: SIGNATURES[port & 0x01],
+            shift_register: 0,
+            strobe: false,
+        }
+    }
+
+    fn latch(&self) -> u32 {
+        self.controllers[0].buttons.bits() as u32 | (self.controllers[1].buttons.bits() as u32) << 8 | (self.signature as u32) << 16
+    }
+}
+
+impl InputDevice for FourScore {
+    fn write(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        if self.strobe || strobe {
+            self.shift_register = self.latch();
+        }
+        self.strobe = strobe;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        let bit = self.peek(port, ppu);
+        if !self.strobe {
+            // 1s follow the 24 bits
+            self.shift_register = self.shift_register >> 1 | 1 << 23;
+        }
+        bit
+    }
+
+    fn peek(&self, _port: usize, _ppu: &PPU) -> u8 {
+        if self.strobe { self.controllers[0].buttons.bits() & 0x01 } else { self.shift_register as u8 & 0x01 }
+    }
+
+    fn set_buttons(&mut self, index: usize, buttons: Buttons) -> bool {
+        let Some(controller) = self.controllers.get_mut(index) else {
+            return false;
+        };
+        controller.buttons = buttons;
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The Famicom 4 player adapter in its simple mode, with player 3 on D1 of $4016 and player 4 on D1 of $4017
+#[derive(Debug, Default)]
+pub struct FamicomFourPlayer {
+    pub controllers: [Controller; 2],
+}
+
+impl FamicomFourPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl InputDevice for FamicomFourPlayer {
+    fn write(&mut self, value: u8) {
+        for controller in self.controllers.iter_mut() {
+            controller.write_strobe(value);
+        }
+    }
+
+    fn read(&mut self, port: usize, _ppu: &PPU) -> u8 {
+        self.controllers[port].shift() << 1
+    }
+
+    fn peek(&self, port: usize, _ppu: &PPU) -> u8 {
+        self.controllers[port].peek_bit() << 1
+    }
+
+    fn set_buttons(&mut self, index: usize, buttons: Buttons) -> bool {
+        let Some(controller) = self.controllers.get_mut(index) else {
+            return false;
+        };
+        controller.buttons = buttons;
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for FourScore {
+    fn save_state(&self, state: &mut StateWriter) {
+        for controller in &self.controllers {
+            controller.save_state(state);
+        }
+        state.write(self.shift_register);
+        state.write(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for controller in &mut self.controllers {
+            controller.load_state(state)?;
+        }
+        self.shift_register = state.read()?;
+        self.strobe = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for FamicomFourPlayer {
+    fn save_state(&self, state: &mut StateWriter) {
+        for controller in &self.controllers {
+            controller.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for controller in &mut self.controllers {
+            controller.load_state(state)?;
+        }
+        Ok(())
+    }
+}
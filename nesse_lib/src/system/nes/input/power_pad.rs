@@ -0,0 +1,93 @@
+use std::any::Any;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The order the buttons, numbered 1-12 as on side B, are shifted out on D3 and D4
+const D3_ORDER: [u8; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const D4_ORDER: [u8; 4] = [4, 3, 12, 8];
+
+/// The Power Pad mat, which shifts out its 12 buttons on D3 and D4 of its port
+#[derive(Debug, Default)]
+pub struct PowerPad {
+    /// Bit n - 1 is set while button n is pressed
+    pub buttons: u16,
+    d3_register: u8,
+    d4_register: u8,
+    strobe: bool,
+}
+
+impl PowerPad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Presses or releases button 1-12
+    pub fn set_button(&mut self, button: u8, pressed: bool) {
+        if (1..=12).contains(&button) {
+            let mask = 1 << (button - 1);
+            if pressed { self.buttons |= mask } else { self.buttons &= !mask }
+        }
+    }
+
+    fn pressed(&self, button: u8) -> u8 {
+        (self.buttons >> (button - 1)) as u8 & 0x01
+    }
+
+    fn latch(&mut self) {
+        self.d3_register = D3_ORDER.iter().enumerate().fold(0, |bits, (index, button)| bits | self.pressed(*button) << index);
+        // D4 reads 1s after its 4 buttons
+        self.d4_register = D4_ORDER.iter().enumerate().fold(0xF0, |bits, (index, button)| bits | self.pressed(*button) << index);
+    }
+}
+
+impl InputDevice for PowerPad {
+    fn write(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        if self.strobe || strobe {
+            self.latch();
+        }
+        self.strobe = strobe;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        let value = self.peek(port, ppu);
+        if !self.strobe {
+            self.d3_register = self.d3_register >> 1 | 0x80;
+            self.d4_register = self.d4_register >> 1 | 0x80;
+        }
+        value
+    }
+
+    fn peek(&self, _port: usize, _ppu: &PPU) -> u8 {
+        let (d3, d4) = if self.strobe {
+            (self.pressed(D3_ORDER[0]), self.pressed(D4_ORDER[0]))
+        } else {
+            (self.d3_register & 0x01, self.d4_register & 0x01)
+        };
+        d3 << 3 | d4 << 4
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for PowerPad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.d3_register);
+        state.write(self.d4_register);
+        state.write(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.d3_register = state.read()?;
+        self.d4_register = state.read()?;
+        self.strobe = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use std::any::Any;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The largest movement a single report holds, the rest is reported on the next strobe
+const MAX_MOTION: i32 = 127;
+
+/// The SNES mouse on an NES port, which shifts out a 32 bit report on D0, MSB first: a zero byte,
+/// the buttons, sensitivity and signature, then the Y and X motion as sign and magnitude
+#[derive(Debug, Default)]
+pub struct SnesMouse {
+    pub left: bool,
+    pub right: bool,
+    /// 0-2, cycled by reads while the strobe is high
+    pub sensitivity: u8,
+    /// Motion since the last report, positive X to the right and positive Y down
+    motion: (i32, i32),
+    report: u32,
+    strobe: bool,
+}
+
+impl SnesMouse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds movement to the next report
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        self.motion.0 += dx;
+        self.motion.1 += dy;
+    }
+
+    fn latch(&mut self) {
+        let x = self.motion.0.clamp(-MAX_MOTION, MAX_MOTION);
+        let y = self.motion.1.clamp(-MAX_MOTION, MAX_MOTION);
+        self.motion.0 -= x;
+        self.motion.1 -= y;
+
+        let buttons = (self.right as u32) << 7 | (self.left as u32) << 6 | (self.sensitivity as u32) << 4 | 0x01;
+        // The direction bits are set for up and left
+        let y = ((y < 0) as u32) << 7 | y.unsigned_abs();
+        let x = ((x < 0) as u32) << 7 | x.unsigned_abs();
+        self.report = buttons << 16 | y << 8 | x;
+    }
+}
+
+impl InputDevice for SnesMouse {
+    fn write(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        // The report is latched once when the strobe falls, so movement isn't lost
+        if self.strobe && !strobe {
+            self.latch();
+        }
+        self.strobe = strobe;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        if self.strobe {
+            self.sensitivity = (self.sensitivity + 1) % 3;
+            return 0;
+        }
+
+        let bit = self.peek(port, ppu);
+        // 1s follow the 32 bits
+        self.report = self.report << 1 | 0x01;
+        bit
+    }
+
+    fn peek(&self, _port: usize, _ppu: &PPU) -> u8 {
+        if self.strobe { 0 } else { (self.report >> 31) as u8 }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for SnesMouse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.sensitivity);
+        state.write(self.report);
+        state.write(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.sensitivity = state.read::<u8>()? % 3;
+        self.report = state.read()?;
+        self.strobe = state.read()?;
+        Ok(())
+    }
+}
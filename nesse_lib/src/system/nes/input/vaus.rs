@@ -0,0 +1,82 @@
+use std::any::Any;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The Arkanoid paddle, which latches its knob position on the strobe and shifts it out inverted, MSB first.
+/// The NES version reports the button in D3 and the position in D4 of its port, the Famicom version sits in
+/// the expansion port with the button in D1 of $4016 and the position in D1 of $4017.
+#[derive(Debug)]
+pub struct Vaus {
+    /// The knob, Arkanoid expects values from about 0x62 to 0xF2
+    pub position: u8,
+    pub button: bool,
+    famicom: bool,
+    shift_register: u8,
+    strobe: bool,
+}
+
+impl Vaus {
+    pub fn new(famicom: bool) -> Self {
+        Self {
+            position: 0x80,
+            button: false,
+            famicom,
+            shift_register: 0,
+            strobe: false,
+        }
+    }
+
+    fn data_bit(&self) -> u8 {
+        !self.shift_register >> 7 & 0x01
+    }
+}
+
+impl InputDevice for Vaus {
+    fn write(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        if self.strobe || strobe {
+            self.shift_register = self.position;
+        }
+        self.strobe = strobe;
+    }
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        let value = self.peek(port, ppu);
+        let shifts = !self.famicom || port == 1;
+        if shifts && !self.strobe {
+            self.shift_register <<= 1;
+        }
+        value
+    }
+
+    fn peek(&self, port: usize, _ppu: &PPU) -> u8 {
+        let button = self.button as u8;
+        match (self.famicom, port) {
+            (true, 0) => button << 1,
+            (true, _) => self.data_bit() << 1,
+            (false, _) => button << 3 | self.data_bit() << 4,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Savestate for Vaus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.shift_register);
+        state.write(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.shift_register = state.read()?;
+        self.strobe = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use std::any::Any;
+use crate::system::nes::input::InputDevice;
+use crate::system::nes::ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const DOTS_PER_SCANLINE: i32 = 341;
+/// How long the photodiode keeps reporting light after the beam drew a bright pixel
+const LIGHT_DURATION: i32 = 20 * DOTS_PER_SCANLINE;
+/// Pixels around the aim point the sensor sees
+const SENSOR_RADIUS: i32 = 2;
+/// Luma from 0.0 to 1.0 a pixel needs to be seen
+const BRIGHTNESS_THRESHOLD: f32 = 0.6;
+
+/// The light gun, which reports the trigger in D4 and whether the pixels it's aimed at were just lit in D3
+#[derive(Debug, Default)]
+pub struct Zapper {
+    /// The pixel aimed at, `None` when pointing away from the screen
+    pub aim: Option<(u16, u16)>,
+    pub trigger: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a bright pixel around the aim point was drawn shortly before the current dot
+    pub fn light_detected(&self, ppu: &PPU) -> bool {
+        let Some((aim_x, aim_y)) = self.aim else {
+            return false;
+        };
+        let beam = ppu.scanline as i32 * DOTS_PER_SCANLINE + ppu.dot as i32;
+
+        for y in (aim_y as i32 - SENSOR_RADIUS)..=(aim_y as i32 + SENSOR_RADIUS) {
+            for x in (aim_x as i32 - SENSOR_RADIUS)..=(aim_x as i32 + SENSOR_RADIUS) {
+                if !(0..SCREEN_WIDTH as i32).contains(&x) || !(0..SCREEN_HEIGHT as i32).contains(&y) {
+                    continue;
+                }
+                // Pixel x is output on dot x + 1
+                let since_drawn = beam - (y * DOTS_PER_SCANLINE + x + 1);
+                if !(0..LIGHT_DURATION).contains(&since_drawn) {
+                    continue;
+                }
+
+                let [r, g, b] = ppu.rgb_palette.rgb(ppu.framebuffer[y as usize * SCREEN_WIDTH + x as usize]);
+                let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+                if luma >= BRIGHTNESS_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl InputDevice for Zapper {
+    fn write(&mut self, _value: u8) {}
+
+    fn read(&mut self, port: usize, ppu: &PPU) -> u8 {
+        self.peek(port, ppu)
+    }
+
+    fn peek(&self, _port: usize, ppu: &PPU) -> u8 {
+        // D3 is 0 while light is detected
+        let light = if self.light_detected(ppu) { 0 } else { 0x08 };
+        let trigger = if self.trigger { 0x10 } else { 0 };
+        light | trigger
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The Zapper latches nothing, it reports the aim and trigger as they are and senses the PPU's output
+impl Savestate for Zapper {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -1,13 +1,39 @@
+use std::mem::size_of;
+use anyhow::format_err;
+use num_traits::{FromBytes, ToBytes};
+use crate::system::nes::apu::recorder::AudioRecorder;
 use crate::system::nes::apu::APU;
-use crate::system::nes::cpu::CPU;
+use crate::system::nes::cpu::{IRQSource, CPU};
+use crate::system::nes::file::Region;
+use crate::system::nes::input::Input;
 use crate::system::nes::memory::Memory;
 use crate::system::nes::ppu::PPU;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const DMC_DMA_CYCLES: u32 = 4;
+const DMC_DMA_CYCLES_DURING_OAM_DMA: u32 = 2;
+/// Bits of $4016/$4017 reads the input devices don't drive
+const INPUT_OPEN_BUS_MASK: u8 = 0xE0;
 
 pub struct IOBus {
     pub cpu: CPU,
     pub memory: Memory,
     pub ppu: PPU,
     pub apu: APU,
+    /// The devices read through $4016 and $4017
+    pub input: Input,
+    /// The port the current instruction read from, which a DMC fetch on that cycle clocks again
+    input_read: Option<usize>,
+    /// Writes the audio output to WAV files while set
+    pub recorder: Option<AudioRecorder>,
+    /// CPU cycles since power-on, whose parity decides DMA alignment
+    pub cycles: u64,
+    /// Page written to $4014, copied to OAM before the next instruction
+    pub oam_dma: Option<u8>,
+    oam_dma_active: bool,
+    pub region: Region,
+    /// Master clock cycles the PPU is behind the CPU
+    ppu_clock: u32,
 }
 
 impl IOBus {
@@ -17,6 +43,220 @@ impl IOBus {
             memory: Memory::new(),
             ppu: PPU::new(),
             apu: APU::new(),
+            input: Input::new(),
+            input_read: None,
+            recorder: None,
+            cycles: 0,
+            oam_dma: None,
+            oam_dma_active: false,
+            region: Region::NTSC,
+            ppu_clock: 0,
+        }
+    }
+
+    /// Switches the clock ratio of the CPU and PPU and the timing of the PPU and APU to the given region
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.set_region(region);
+        self.apu.set_region(region);
+    }
+
+    /// CPU read, dispatching the PPU registers at $2000-$3FFF and everything else to `Memory`
+    pub fn read<T: FromBytes + Copy + bytemuck::Pod>(&mut self, addr: u16) -> anyhow::Result<T> {
+        let size = size_of::<T>();
+        if addr as usize + size > 0x10000 {
+            return Err(format_err!("Out of Bounds Read at 0x{addr:x?}"));
+        }
+
+        let mut value = T::zeroed();
+        for (i, byte) in bytemuck::bytes_of_mut(&mut value).iter_mut().enumerate() {
+            *byte = self.read_byte(addr + i as u16)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads like `read`, but without side effects
+    pub fn peek<T: FromBytes + Copy + bytemuck::Pod>(&self, addr: u16) -> anyhow::Result<T> {
+        let size = size_of::<T>();
+        if addr as usize + size > 0x10000 {
+            return Err(format_err!("Out of Bounds Read at 0x{addr:x?}"));
+        }
+
+        let mut value = T::zeroed();
+        for (i, byte) in bytemuck::bytes_of_mut(&mut value).iter_mut().enumerate() {
+            *byte = match addr + i as u16 {
+                register @ 0x2000..=0x3FFF => self.ppu.peek_register(register),
+                0x4015 => self.apu.peek_status(),
+                port @ 0x4016..=0x4017 => input_open_bus(port) | self.input.peek(port as usize - 0x4016, &self.ppu),
+                addr => self.memory.peek(addr)?,
+            };
+        }
+
+        Ok(value)
+    }
+
+    pub fn write<T: ToBytes>(&mut self, mut dst: u16, value: T) -> anyhow::Result<()> {
+        let size = size_of::<T>();
+        if dst as usize + size > 0x10000 {
+            return Err(format_err!("Out of Bounds Write to 0x{dst:x?} of size {size}"));
+        }
+
+        for byte in value.to_be_bytes().as_ref() {
+            self.write_byte(dst, *byte)?;
+            dst += 1;
+        }
+
+        Ok(())
+    }
+
+    fn read_byte(&mut self, addr: u16) -> anyhow::Result<u8> {
+        match addr {
+            0x2000..=0x3FFF => {
+                let value = self.ppu.read_register(addr, self.memory.cartridge_mut());
+                if self.ppu.take_nmi_cancel() {
+                    self.cpu.nmi = false;
+                }
+                Ok(value)
+            }
+            0x4015 => Ok(self.apu.read_status()),
+            0x4016..=0x4017 => {
+                let port = addr as usize - 0x4016;
+                self.input_read = Some(port);
+                Ok(input_open_bus(addr) | self.input.read(port, &self.ppu))
+            }
+            _ => self.memory.read(addr),
+        }
+    }
+
+    fn write_byte(&mut self, dst: u16, byte: u8) -> anyhow::Result<()> {
+        match dst {
+            0x2000..=0x3FFF => {
+                self.ppu.write_register(dst, byte, self.memory.cartridge_mut());
+                Ok(())
+            }
+            0x4014 => {
+                self.oam_dma = Some(byte);
+                Ok(())
+            }
+            0x4000..=0x4013 | 0x4015 | 0x4017 => {
+                self.apu.write_register(dst, byte);
+                Ok(())
+            }
+            0x4016 => {
+                self.input.write(byte);
+                Ok(())
+            }
+            _ => self.memory.write(dst, byte),
         }
     }
-}
\ No newline at end of file
+
+    /// Copies a CPU page to OAM through $2004 while the CPU is halted, returning the cycles it took.
+    /// The CPU halts for one cycle, waits another if the DMA would start on a put (odd) cycle,
+    /// then alternates between reading and writing 256 bytes.
+    pub fn run_oam_dma(&mut self, page: u8) -> anyhow::Result<u16> {
+        let start = self.cycles;
+        self.oam_dma_active = true;
+        let halt = if self.cycles % 2 == 1 { 2 } else { 1 };
+        self.tick(halt);
+
+        for offset in 0..=0xFF {
+            let value = self.read::<u8>((page as u16) << 8 | offset)?;
+            self.tick(1);
+            self.write(0x2004, value)?;
+            self.tick(1);
+        }
+
+        self.oam_dma_active = false;
+        Ok((self.cycles - start) as u16)
+    }
+
+    /// Advances the devices on the bus by the given amount of CPU cycles, plus the cycles DMC DMA halts the CPU for
+    pub fn tick(&mut self, cycles: u8) {
+        let mut remaining = cycles as u32;
+        while remaining > 0 {
+            remaining -= 1;
+            self.cycles += 1;
+
+            if let Some(cartridge) = self.memory.cartridge_mut() {
+                cartridge.clock_cpu();
+            }
+            self.apu.tick();
+            self.input.clock();
+
+            // The DMC's sample fetches halt the CPU, taking fewer cycles when OAM DMA already did
+            if let Some(addr) = self.apu.dmc.dma_request() {
+                // Samples are always in cartridge space
+                let value = self.memory.read::<u8>(addr).unwrap_or(0);
+                self.apu.dmc.fill_sample_buffer(value);
+
+                // Instructions read the ports on their last cycle, the halted read is repeated after the fetch
+                if remaining == 0 {
+                    if let Some(port) = self.input_read {
+                        self.input.read(port, &self.ppu);
+                    }
+                }
+                remaining += if self.oam_dma_active { DMC_DMA_CYCLES_DURING_OAM_DMA } else { DMC_DMA_CYCLES };
+            }
+
+            // 3 dots per CPU cycle, or 3.2 on PAL
+            self.ppu_clock += self.region.cpu_divider();
+            while self.ppu_clock >= self.region.ppu_divider() {
+                self.ppu_clock -= self.region.ppu_divider();
+                self.ppu.tick(self.memory.cartridge_mut());
+            }
+
+            let output = self.audio_output();
+            self.apu.resampler.clock(output);
+            if let Some(recorder) = &mut self.recorder {
+                recorder.clock(&self.apu, output, self.memory.cartridge());
+            }
+        }
+
+        self.input_read = None;
+
+        if self.ppu.take_nmi() {
+            self.cpu.nmi = true;
+        }
+        let mapper_irq = self.memory.cartridge().is_some_and(|cartridge| cartridge.irq_pending());
+        self.cpu.set_irq(IRQSource::Mapper, mapper_irq);
+        self.cpu.set_irq(IRQSource::DMC, self.apu.dmc.irq_flag);
+        self.cpu.set_irq(IRQSource::FrameCounter, self.apu.frame_counter.irq_flag);
+    }
+
+    /// The mixed audio output of the APU and the cartridge's expansion audio
+    pub fn audio_output(&self) -> f32 {
+        let Some(cartridge) = self.memory.cartridge() else {
+            return self.apu.output(&[]);
+        };
+        if self.apu.expansion_controls_unchanged() {
+            return self.apu.output(&[cartridge.audio_output()]);
+        }
+        self.apu.mix(cartridge.audio_channels().len(), |channel| cartridge.audio_channel_output(channel))
+    }
+}
+
+/// The CPU data bus still holds the high byte of the address in the bits the devices leave floating
+fn input_open_bus(addr: u16) -> u8 {
+    (addr >> 8) as u8 & INPUT_OPEN_BUS_MASK
+}
+
+/// The bus timing, the devices on it are saved in sections of their own
+impl Savestate for IOBus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.cycles);
+        state.write(self.oam_dma);
+        state.write(self.oam_dma_active);
+        state.write(self.ppu_clock);
+        state.write(self.input_read);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cycles = state.read()?;
+        self.oam_dma = state.read()?;
+        self.oam_dma_active = state.read()?;
+        self.ppu_clock = state.read()?;
+        self.input_read = state.read()?;
+        Ok(())
+    }
+}
@@ -1,19 +1,82 @@
-use std::path::Path;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
 use crate::system::nes::file::NESFile;
+use crate::system::nes::mapper::fds::disk::FDSImage;
+use crate::system::nes::mapper::fds::FDS;
+use crate::system::nes::mapper::{create_mapper, Cartridge};
+use crate::system::nes::patch::Patch;
+use crate::system::nes::unif::UNIFFile;
 use crate::system::nes::NES;
 
 pub struct NESLoader;
 
 impl NESLoader {
+    /// Loads a ROM, soft-patching it if a patch with the same file stem lies next to it
     pub fn load_rom<P: AsRef<Path>>(path: &P, nes: &mut NES) -> anyhow::Result<()>{
-        let rom = NESFile::new(path)?;
+        let data = fs::read(path)?;
+        if FDSImage::is_fds(&data) {
+            return Self::load_disk_bytes(&data, Some(Self::disk_diff_path(path)), nes);
+        }
+
+        match Patch::find_sibling(path) {
+            Some(patch_path) => Self::load_rom_with_patch(path, &patch_path, nes),
+            None => Self::load_rom_bytes(&data, nes),
+        }
+    }
+
+    /// Loads a ROM after applying an IPS, UPS or BPS patch to the raw image in memory
+    pub fn load_rom_with_patch<P: AsRef<Path>, Q: AsRef<Path>>(path: &P, patch_path: &Q, nes: &mut NES) -> anyhow::Result<()> {
+        let patch = Patch::new(patch_path)?;
+        info!("Applying {:?} patch {:?}", patch.format, patch_path.as_ref());
+
+        let rom = patch.apply(&fs::read(path)?)?;
+        Self::load_rom_bytes(&rom, nes)
+    }
+
+    /// Disk writes are stored next to the image, e.g. `Game.fds` keeps its changes in `Game.fds.ips`
+    pub fn disk_diff_path<P: AsRef<Path>>(path: &P) -> PathBuf {
+        let mut diff_path = OsString::from(path.as_ref());
+        diff_path.push(".ips");
+        PathBuf::from(diff_path)
+    }
+
+    /// Inserts a Famicom Disk System image, using the BIOS previously loaded into the `NES`
+    pub fn load_disk_bytes(data: &[u8], diff_path: Option<PathBuf>, nes: &mut NES) -> anyhow::Result<()> {
+        let bios = nes.fds_bios.clone().ok_or_else(|| anyhow::anyhow!("Load the FDS BIOS before inserting a disk image"))?;
+        let image = FDSImage::from_bytes(data)?;
+        info!("FDS image with {} disk sides", image.sides.len());
+
+        nes.bus.memory.insert_cartridge(Box::new(FDS::new(bios, image, diff_path)?));
+        nes.rom = None;
+        nes.apply_region();
+
+        Ok(())
+    }
+
+    /// Loads an iNES, NES 2.0 or UNIF image
+    pub fn load_rom_bytes(data: &[u8], nes: &mut NES) -> anyhow::Result<()> {
+        let mut rom = if UNIFFile::is_unif(data) {
+            let unif = UNIFFile::from_bytes(data)?;
+            info!("UNIF board {}", unif.board);
+            unif.into_nes_file()?
+        } else {
+            NESFile::from_bytes(data)?
+        };
         if !rom.is_valid() {
             return Err(anyhow::anyhow!("Invalid NES ROM"));
         }
 
-        nes.bus.memory.write_slice(0xC000, &rom.data.prg_rom)?;
-        nes.bus.ppu.write_slice(0, &rom.data.chr_rom)?;
+        rom.apply_database(&nes.game_database);
+        info!("Mapper {}.{}, {:?} mirroring, {:?} (CRC32 0x{:08X})", rom.mapper(), rom.submapper(), rom.mirroring(), rom.region(), rom.hashes.crc32);
+
+        let mapper = create_mapper(rom.mapper(), Cartridge::new(&rom))?;
+        nes.bus.memory.insert_cartridge(mapper);
+        nes.bus.input.connect_default(rom.default_expansion_device());
+        nes.rom = Some(rom);
+        nes.apply_region();
 
         Ok(())
     }
-}
\ No newline at end of file
+}
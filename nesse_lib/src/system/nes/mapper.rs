@@ -0,0 +1,184 @@
+use anyhow::bail;
+use crate::system::nes::file::{Mirroring, NESFile};
+use crate::system::nes::mapper::fds::FDS;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+pub mod audio;
+pub mod nrom;
+pub mod mmc1;
+pub mod uxrom;
+pub mod cnrom;
+pub mod axrom;
+pub mod gxrom;
+pub mod sa0037;
+pub mod novel_diamond;
+pub mod fds;
+pub mod nsf;
+
+/// A cartridge board as seen from the CPU ($4020-$FFFF) and PPU ($0000-$1FFF) buses.
+/// Its save state holds the board's registers and RAM.
+pub trait Mapper: Savestate {
+    /// Reads without side effects, used by the debugger and for instruction decoding
+    fn cpu_peek(&self, addr: u16) -> u8;
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.cpu_peek(addr)
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8);
+
+    /// Reads CHR without side effects, used by the debugger
+    fn ppu_peek(&self, addr: u16) -> u8;
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    /// Called once per CPU cycle
+    fn clock_cpu(&mut self) {}
+
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    /// Expansion audio output, on the scale of the APU's output
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    /// Names of the expansion audio channels, which can be rendered separately
+    fn audio_channels(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// What a single expansion audio channel contributes to `audio_output`
+    fn audio_channel_output(&self, _channel: usize) -> f32 {
+        0.0
+    }
+
+    fn fds(&self) -> Option<&FDS> {
+        None
+    }
+
+    fn fds_mut(&mut self) -> Option<&mut FDS> {
+        None
+    }
+}
+
+/// ROM and RAM shared by all boards, with helpers for banked access
+pub struct Cartridge {
+    pub prg_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub chr_is_ram: bool,
+    pub battery: bool,
+    pub mirroring: Mirroring,
+}
+
+impl Cartridge {
+    pub fn new(rom: &NESFile) -> Self {
+        let chr_is_ram = rom.data.chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; (rom.chr_ram_size() + rom.chr_nvram_size()).max(0x2000)]
+        } else {
+            rom.data.chr_rom.clone()
+        };
+
+        let mut prg_ram = vec![0; rom.prg_ram_size() + rom.prg_nvram_size()];
+        if let Some(trainer) = &rom.data.trainer {
+            // Trainers are loaded to $7000
+            if prg_ram.len() < 0x2000 {
+                prg_ram.resize(0x2000, 0);
+            }
+            prg_ram[0x1000..0x1000 + trainer.len()].copy_from_slice(trainer);
+        }
+
+        Self {
+            prg_rom: rom.data.prg_rom.clone(),
+            prg_ram,
+            chr,
+            chr_is_ram,
+            battery: rom.has_battery(),
+            mirroring: rom.mirroring(),
+        }
+    }
+
+    /// Reads from a PRG ROM bank of `bank_size` bytes, wrapping bank numbers past the end of the ROM
+    pub fn read_prg(&self, bank: usize, bank_size: usize, offset: u16) -> u8 {
+        if self.prg_rom.is_empty() {
+            return 0;
+        }
+        let addr = bank * bank_size + (offset as usize & (bank_size - 1));
+        self.prg_rom[addr % self.prg_rom.len()]
+    }
+
+    pub fn read_chr(&self, bank: usize, bank_size: usize, offset: u16) -> u8 {
+        if self.chr.is_empty() {
+            return 0;
+        }
+        let addr = bank * bank_size + (offset as usize & (bank_size - 1));
+        self.chr[addr % self.chr.len()]
+    }
+
+    pub fn write_chr(&mut self, bank: usize, bank_size: usize, offset: u16, value: u8) {
+        if !self.chr_is_ram || self.chr.is_empty() {
+            return;
+        }
+        let addr = (bank * bank_size + (offset as usize & (bank_size - 1))) % self.chr.len();
+        self.chr[addr] = value;
+    }
+
+    /// PRG RAM at $6000-$7FFF, mirrored if smaller than 8 KiB
+    pub fn read_prg_ram(&self, addr: u16) -> u8 {
+        if self.prg_ram.is_empty() {
+            return 0;
+        }
+        self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]
+    }
+
+    pub fn write_prg_ram(&mut self, addr: u16, value: u8) {
+        if self.prg_ram.is_empty() {
+            return;
+        }
+        let len = self.prg_ram.len();
+        self.prg_ram[(addr as usize - 0x6000) % len] = value;
+    }
+}
+
+/// PRG RAM and CHR RAM, the ROMs come from the loaded game
+impl Savestate for Cartridge {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.prg_ram);
+        state.write_bytes(if self.chr_is_ram { &self.chr } else { &[] });
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            state.read_bytes_into(&mut self.chr)?;
+        } else {
+            state.read_bytes()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn create_mapper(id: u16, cartridge: Cartridge) -> anyhow::Result<Box<dyn Mapper>> {
+    let mapper: Box<dyn Mapper> = match id {
+        0 => Box::new(nrom::NROM::new(cartridge)),
+        1 => Box::new(mmc1::MMC1::new(cartridge)),
+        2 => Box::new(uxrom::UxROM::new(cartridge)),
+        3 => Box::new(cnrom::CNROM::new(cartridge)),
+        7 => Box::new(axrom::AxROM::new(cartridge)),
+        66 => Box::new(gxrom::GxROM::new(cartridge)),
+        148 => Box::new(sa0037::SA0037::new(cartridge)),
+        201 => Box::new(novel_diamond::NovelDiamond::new(cartridge)),
+        _ => bail!("Unsupported mapper {id}"),
+    };
+
+    Ok(mapper)
+}
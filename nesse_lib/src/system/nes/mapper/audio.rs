@@ -0,0 +1,38 @@
+use crate::system::nes::state::Savestate;
+
+pub mod vrc6;
+pub mod vrc7;
+pub mod mmc5;
+pub mod n163;
+pub mod sunsoft5b;
+
+/// Output of an APU pulse channel at volume 15, which the expansion chips are leveled against
+pub const PULSE_LEVEL: f32 = 0.1494;
+
+/// A sound chip on the cartridge, mixed with the APU
+pub trait ExpansionAudio: Savestate {
+    /// Names of the chip's channels, used for stems
+    fn channels(&self) -> &'static [&'static str];
+
+    /// Reads a register without side effects, `None` for addresses the chip doesn't decode
+    fn peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        self.peek(addr)
+    }
+
+    /// Writes a register, ignoring addresses the chip doesn't decode
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Advances the chip by one CPU cycle
+    fn clock(&mut self);
+
+    /// What one channel contributes to `output`, on the scale of the APU's output
+    fn channel_output(&self, channel: usize) -> f32;
+
+    fn output(&self) -> f32 {
+        (0..self.channels().len()).map(|channel| self.channel_output(channel)).sum()
+    }
+}
@@ -0,0 +1,173 @@
+use crate::system::nes::apu::envelope::Envelope;
+use crate::system::nes::apu::length_counter::LengthCounter;
+use crate::system::nes::apu::pulse::DUTY_SEQUENCES;
+use crate::system::nes::mapper::audio::{ExpansionAudio, PULSE_LEVEL};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const PULSE_UNIT: f32 = PULSE_LEVEL / 15.0;
+/// The 8-bit PCM channel at full scale is about as loud as the DMC at full scale
+const PCM_UNIT: f32 = 0.5588 / 255.0;
+/// The envelopes and length counters are clocked at a fixed 240 Hz
+const FRAME_PERIOD: u32 = 7457;
+
+/// An APU pulse channel without the sweep unit, which also never silences low or high periods
+#[derive(Default)]
+struct Pulse {
+    duty: u8,
+    step: u8,
+    timer_period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length_counter: LengthCounter,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length_counter.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (value as u16 & 0x07) << 8;
+                self.length_counter.load(value);
+                self.envelope.start = true;
+                self.step = 0;
+            }
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 7) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.length_counter.active() || DUTY_SEQUENCES[self.duty as usize][self.step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+/// The MMC5's two pulse channels at $5000-$5007, enabled through $5015, and its PCM channel written
+/// through $5011. The PCM read mode, which fetches samples from $8000-$BFFF, isn't supported.
+#[derive(Default)]
+pub struct MMC5Audio {
+    pulses: [Pulse; 2],
+    pcm_read_mode: bool,
+    pcm: u8,
+    frame_timer: u32,
+    odd_cycle: bool,
+}
+
+impl MMC5Audio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ExpansionAudio for MMC5Audio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["mmc5_pulse1", "mmc5_pulse2", "mmc5_pcm"]
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x5015 => Some(self.pulses[0].length_counter.active() as u8 | (self.pulses[1].length_counter.active() as u8) << 1),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5003 => self.pulses[0].write(addr - 0x5000, value),
+            0x5004..=0x5007 => self.pulses[1].write(addr - 0x5004, value),
+            0x5010 => self.pcm_read_mode = value & 0x01 != 0,
+            // Writes of 0 are ignored, they stop the sample on the MMC5's IRQ line
+            0x5011 if !self.pcm_read_mode && value != 0 => self.pcm = value,
+            0x5015 => {
+                self.pulses[0].length_counter.set_enabled(value & 0x01 != 0);
+                self.pulses[1].length_counter.set_enabled(value & 0x02 != 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            for pulse in &mut self.pulses {
+                pulse.clock_timer();
+            }
+        }
+
+        self.frame_timer += 1;
+        if self.frame_timer == FRAME_PERIOD {
+            self.frame_timer = 0;
+            for pulse in &mut self.pulses {
+                pulse.envelope.clock();
+                pulse.length_counter.clock();
+            }
+        }
+    }
+
+    fn channel_output(&self, channel: usize) -> f32 {
+        match channel {
+            0 | 1 => self.pulses[channel].output() as f32 * PULSE_UNIT,
+            _ => self.pcm as f32 * PCM_UNIT,
+        }
+    }
+}
+
+impl Savestate for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.duty);
+        state.write(self.step);
+        state.write(self.timer_period);
+        state.write(self.timer);
+        self.envelope.save_state(state);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.duty = state.read()?;
+        self.step = state.read()?;
+        self.timer_period = state.read()?;
+        self.timer = state.read()?;
+        self.envelope.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        Ok(())
+    }
+}
+
+impl Savestate for MMC5Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        for pulse in &self.pulses {
+            pulse.save_state(state);
+        }
+        state.write(self.pcm_read_mode);
+        state.write(self.pcm);
+        state.write(self.frame_timer);
+        state.write(self.odd_cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for pulse in &mut self.pulses {
+            pulse.load_state(state)?;
+        }
+        self.pcm_read_mode = state.read()?;
+        self.pcm = state.read()?;
+        self.frame_timer = state.read()?;
+        self.odd_cycle = state.read()?;
+        Ok(())
+    }
+}
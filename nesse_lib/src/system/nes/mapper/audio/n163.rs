@@ -0,0 +1,146 @@
+use crate::system::nes::mapper::audio::{ExpansionAudio, PULSE_LEVEL};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const CHANNELS: usize = 8;
+/// CPU cycles spent updating each active channel
+const CHANNEL_CYCLES: u32 = 15;
+/// A single channel playing a full scale wave at volume 15 is about twice as loud as an APU pulse
+const LEVEL: f32 = 2.0 * PULSE_LEVEL / 225.0;
+
+/// Namco 163 wavetable sound. Up to 8 channels play 4-bit samples from the chip's 128 bytes of RAM,
+/// which also hold their registers and are accessed through the address port at $F800 and the
+/// data port at $4800. The channels are updated one at a time, so they share the output.
+pub struct N163Audio {
+    ram: [u8; 0x80],
+    address: u8,
+    auto_increment: bool,
+    /// The channel being updated, counting down from channel 8
+    channel: usize,
+    timer: u32,
+    /// The last sample times volume of every channel
+    outputs: [u8; CHANNELS],
+}
+
+impl N163Audio {
+    pub fn new() -> Self {
+        Self {
+            ram: [0; 0x80],
+            address: 0,
+            auto_increment: false,
+            channel: CHANNELS - 1,
+            timer: 0,
+            outputs: [0; CHANNELS],
+        }
+    }
+
+    /// Channels 8 down to 8 - n + 1 are active
+    fn active_channels(&self) -> usize {
+        ((self.ram[0x7F] >> 4) & 0x07) as usize + 1
+    }
+
+    fn update_channel(&mut self, channel: usize) {
+        let base = 0x40 + channel * 8;
+        let registers = &self.ram[base..base + 8];
+        let frequency = registers[0] as u32 | (registers[2] as u32) << 8 | ((registers[4] & 0x03) as u32) << 16;
+        let mut phase = registers[1] as u32 | (registers[3] as u32) << 8 | (registers[5] as u32) << 16;
+        let length = (256 - (registers[4] & 0xFC) as u32) << 16;
+        let wave_address = registers[6] as u32;
+        let volume = registers[7] & 0x0F;
+
+        phase = (phase + frequency) % length;
+        self.ram[base + 1] = phase as u8;
+        self.ram[base + 3] = (phase >> 8) as u8;
+        self.ram[base + 5] = (phase >> 16) as u8;
+
+        // Samples are packed two per byte, low nibble first
+        let sample_address = (wave_address + (phase >> 16)) & 0xFF;
+        let byte = self.ram[(sample_address as usize / 2) & 0x7F];
+        let sample = if sample_address & 0x01 == 0 { byte & 0x0F } else { byte >> 4 };
+        self.outputs[channel] = sample * volume;
+    }
+}
+
+impl Default for N163Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpansionAudio for N163Audio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["n163_1", "n163_2", "n163_3", "n163_4", "n163_5", "n163_6", "n163_7", "n163_8"]
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.ram[self.address as usize]),
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        let value = self.peek(addr)?;
+        if self.auto_increment {
+            self.address = (self.address + 1) & 0x7F;
+        }
+        Some(value)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => {
+                self.ram[self.address as usize] = value;
+                if self.auto_increment {
+                    self.address = (self.address + 1) & 0x7F;
+                }
+            }
+            0xF800..=0xFFFF => {
+                self.address = value & 0x7F;
+                self.auto_increment = value & 0x80 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        self.timer += 1;
+        if self.timer < CHANNEL_CYCLES {
+            return;
+        }
+        self.timer = 0;
+
+        let first = CHANNELS - self.active_channels();
+        self.channel = if self.channel <= first { CHANNELS - 1 } else { self.channel - 1 };
+        self.update_channel(self.channel);
+    }
+
+    /// Each channel is heard for its share of the time
+    fn channel_output(&self, channel: usize) -> f32 {
+        let active = self.active_channels();
+        if channel < CHANNELS - active {
+            return 0.0;
+        }
+        self.outputs[channel] as f32 * LEVEL / active as f32
+    }
+}
+
+impl Savestate for N163Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write(self.address);
+        state.write(self.auto_increment);
+        state.write(self.channel);
+        state.write(self.timer);
+        state.write_bytes(&self.outputs);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.ram)?;
+        self.address = state.read::<u8>()? & 0x7F;
+        self.auto_increment = state.read()?;
+        self.channel = state.read::<usize>()?.min(CHANNELS - 1);
+        self.timer = state.read()?;
+        state.read_bytes_into(&mut self.outputs)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use crate::system::nes::mapper::audio::{ExpansionAudio, PULSE_LEVEL};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// The tone and noise generators advance once every 16 CPU cycles
+const CLOCK_DIVIDER: u8 = 16;
+
+/// Output at one of the 32 envelope steps, 1.5 dB apart. The loudest is about as loud as an APU
+/// pulse at volume 15.
+fn level(step: u8) -> f32 {
+    if step == 0 {
+        return 0.0;
+    }
+    PULSE_LEVEL * 10f32.powf(-1.5 * (31 - step) as f32 / 20.0)
+}
+
+#[derive(Default)]
+struct Tone {
+    period: u16,
+    timer: u16,
+    high: bool,
+}
+
+impl Tone {
+    fn clock(&mut self) {
+        self.timer += 1;
+        if self.timer >= self.period {
+            self.timer = 0;
+            self.high = !self.high;
+        }
+    }
+}
+
+/// Sunsoft's 5B, a YM2149F: three square wave channels which can each be mixed with a shared noise
+/// generator and use a shared envelope. $C000 selects one of the 16 registers and $E000 writes it.
+pub struct Sunsoft5BAudio {
+    register: u8,
+    registers: [u8; 16],
+    tones: [Tone; 3],
+    divider: u8,
+    noise_timer: u8,
+    /// 17-bit LFSR
+    noise: u32,
+    noise_high: bool,
+    envelope_timer: u16,
+    /// 0-31 over one envelope cycle
+    envelope_step: u8,
+    envelope_holding: bool,
+    /// Alternating envelopes count down on every other cycle
+    envelope_falling: bool,
+}
+
+impl Sunsoft5BAudio {
+    pub fn new() -> Self {
+        Self {
+            register: 0,
+            registers: [0; 16],
+            tones: Default::default(),
+            divider: 0,
+            noise_timer: 0,
+            noise: 1,
+            noise_high: false,
+            envelope_timer: 0,
+            envelope_step: 0,
+            envelope_holding: false,
+            envelope_falling: false,
+        }
+    }
+
+    fn envelope_period(&self) -> u16 {
+        u16::from_le_bytes([self.registers[11], self.registers[12]])
+    }
+
+    fn envelope_shape(&self) -> u8 {
+        self.registers[13] & 0x0F
+    }
+
+    /// Shapes 0-3 are a single fall and 4-7 a single rise, both then silent
+    fn start_envelope(&mut self) {
+        self.envelope_timer = 0;
+        self.envelope_step = 0;
+        self.envelope_holding = false;
+        self.envelope_falling = self.envelope_shape() & 0x04 == 0;
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_holding {
+            let shape = self.envelope_shape();
+            // Continue (bit 3) with hold (bit 0) stays at the end of the cycle, flipped by alternate (bit 1)
+            let attack = shape & 0x04 != 0;
+            let end_high = shape & 0x08 != 0 && (attack != (shape & 0x02 != 0));
+            return if end_high { 31 } else { 0 };
+        }
+        if self.envelope_falling { 31 - self.envelope_step } else { self.envelope_step }
+    }
+
+    fn clock_envelope(&mut self) {
+        self.envelope_timer += 1;
+        if self.envelope_timer < self.envelope_period().max(1) || self.envelope_holding {
+            return;
+        }
+        self.envelope_timer = 0;
+
+        if self.envelope_step < 31 {
+            self.envelope_step += 1;
+            return;
+        }
+
+        let shape = self.envelope_shape();
+        if shape & 0x08 == 0 || shape & 0x01 != 0 {
+            self.envelope_holding = true;
+        } else {
+            self.envelope_step = 0;
+            if shape & 0x02 != 0 {
+                self.envelope_falling = !self.envelope_falling;
+            }
+        }
+    }
+
+    fn clock_noise(&mut self) {
+        // The noise period counts in units of two tone clocks
+        self.noise_timer += 1;
+        if self.noise_timer < (self.registers[6] & 0x1F).max(1) * 2 {
+            return;
+        }
+        self.noise_timer = 0;
+        let feedback = (self.noise ^ (self.noise >> 3)) & 0x01;
+        self.noise = (self.noise >> 1) | feedback << 16;
+        self.noise_high = self.noise & 0x01 != 0;
+    }
+}
+
+impl Default for Sunsoft5BAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpansionAudio for Sunsoft5BAudio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["5b_a", "5b_b", "5b_c"]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr & 0xE000 {
+            0xC000 => self.register = value & 0x0F,
+            0xE000 => {
+                let register = self.register as usize;
+                self.registers[register] = value;
+                match register {
+                    0..=5 => {
+                        let channel = register / 2;
+                        self.tones[channel].period = u16::from_le_bytes([self.registers[channel * 2], self.registers[channel * 2 + 1] & 0x0F]);
+                    }
+                    13 => self.start_envelope(),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        self.divider += 1;
+        if self.divider < CLOCK_DIVIDER {
+            return;
+        }
+        self.divider = 0;
+
+        for tone in &mut self.tones {
+            tone.clock();
+        }
+        self.clock_noise();
+        self.clock_envelope();
+    }
+
+    fn channel_output(&self, channel: usize) -> f32 {
+        // The mixer's bits disable the tone (0-2) and the noise (3-5) of a channel
+        let mixer = self.registers[7];
+        let tone = mixer & (1 << channel) != 0 || self.tones[channel].high;
+        let noise = mixer & (8 << channel) != 0 || self.noise_high;
+        if !tone || !noise {
+            return 0.0;
+        }
+
+        let volume = self.registers[8 + channel];
+        if volume & 0x10 != 0 {
+            level(self.envelope_level())
+        } else {
+            // The envelope has twice the volume's resolution, volume n matches step 2n + 1
+            match volume & 0x0F {
+                0 => 0.0,
+                volume => level(volume * 2 + 1),
+            }
+        }
+    }
+}
+
+impl Savestate for Sunsoft5BAudio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.register);
+        state.write_bytes(&self.registers);
+        for tone in &self.tones {
+            state.write(tone.timer);
+            state.write(tone.high);
+        }
+        state.write(self.divider);
+        state.write(self.noise_timer);
+        state.write(self.noise);
+        state.write(self.noise_high);
+        state.write(self.envelope_timer);
+        state.write(self.envelope_step);
+        state.write(self.envelope_holding);
+        state.write(self.envelope_falling);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.register = state.read::<u8>()? & 0x0F;
+        state.read_bytes_into(&mut self.registers)?;
+        for (channel, tone) in self.tones.iter_mut().enumerate() {
+            tone.period = u16::from_le_bytes([self.registers[channel * 2], self.registers[channel * 2 + 1] & 0x0F]);
+            tone.timer = state.read()?;
+            tone.high = state.read()?;
+        }
+        self.divider = state.read()?;
+        self.noise_timer = state.read()?;
+        self.noise = state.read()?;
+        self.noise_high = state.read()?;
+        self.envelope_timer = state.read()?;
+        self.envelope_step = state.read()?;
+        self.envelope_holding = state.read()?;
+        self.envelope_falling = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use crate::system::nes::mapper::audio::{ExpansionAudio, PULSE_LEVEL};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// A VRC6 pulse at volume 15 is about as loud as an APU pulse
+const LEVEL: f32 = PULSE_LEVEL / 15.0;
+
+#[derive(Default)]
+struct Pulse {
+    /// Ignores the duty and outputs the volume constantly
+    digitized: bool,
+    duty: u8,
+    volume: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.digitized = value & 0x80 != 0;
+                self.duty = (value >> 4) & 0x07;
+                self.volume = value & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && (self.digitized || self.step <= self.duty) { self.volume } else { 0 }
+    }
+}
+
+#[derive(Default)]
+struct Sawtooth {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    /// Counts the 14 timer clocks of one ramp, the accumulator grows on every other one
+    step: u8,
+    accumulator: u8,
+}
+
+impl Sawtooth {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.rate = value & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period >> shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step.is_multiple_of(2) {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    /// The top 5 bits of the accumulator
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+/// Konami's VRC6 sound: two pulse channels with 8 duty cycles at $9000-$9002 and $A000-$A002 and a
+/// sawtooth at $B000-$B002. $9003 halts all channels or speeds them up 16 or 256 times.
+#[derive(Default)]
+pub struct VRC6Audio {
+    pulses: [Pulse; 2],
+    sawtooth: Sawtooth,
+    halted: bool,
+    /// Right shift of the periods from $9003
+    shift: u8,
+}
+
+impl VRC6Audio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ExpansionAudio for VRC6Audio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["vrc6_pulse1", "vrc6_pulse2", "vrc6_saw"]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x9003 => {
+                self.halted = value & 0x01 != 0;
+                self.shift = if value & 0x04 != 0 { 8 } else if value & 0x02 != 0 { 4 } else { 0 };
+            }
+            0x9000..=0x9002 => self.pulses[0].write(addr - 0x9000, value),
+            0xA000..=0xA002 => self.pulses[1].write(addr - 0xA000, value),
+            0xB000..=0xB002 => self.sawtooth.write(addr - 0xB000, value),
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.halted {
+            return;
+        }
+        for pulse in &mut self.pulses {
+            pulse.clock(self.shift);
+        }
+        self.sawtooth.clock(self.shift);
+    }
+
+    fn channel_output(&self, channel: usize) -> f32 {
+        let output = match channel {
+            0 | 1 => self.pulses[channel].output(),
+            _ => self.sawtooth.output(),
+        };
+        output as f32 * LEVEL
+    }
+}
+
+impl Savestate for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.digitized);
+        state.write(self.duty);
+        state.write(self.volume);
+        state.write(self.period);
+        state.write(self.enabled);
+        state.write(self.timer);
+        state.write(self.step);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.digitized = state.read()?;
+        self.duty = state.read()?;
+        self.volume = state.read()?;
+        self.period = state.read()?;
+        self.enabled = state.read()?;
+        self.timer = state.read()?;
+        self.step = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for Sawtooth {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.rate);
+        state.write(self.period);
+        state.write(self.enabled);
+        state.write(self.timer);
+        state.write(self.step);
+        state.write(self.accumulator);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.rate = state.read()?;
+        self.period = state.read()?;
+        self.enabled = state.read()?;
+        self.timer = state.read()?;
+        self.step = state.read()?;
+        self.accumulator = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for VRC6Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        for pulse in &self.pulses {
+            pulse.save_state(state);
+        }
+        self.sawtooth.save_state(state);
+        state.write(self.halted);
+        state.write(self.shift);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for pulse in &mut self.pulses {
+            pulse.load_state(state)?;
+        }
+        self.sawtooth.load_state(state)?;
+        self.halted = state.read()?;
+        self.shift = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+use std::f32::consts::TAU;
+
+use crate::system::nes::mapper::audio::{ExpansionAudio, PULSE_LEVEL};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const CHANNELS: usize = 6;
+/// The chip produces a sample every 36 CPU cycles, at about 49716 Hz
+const SAMPLE_CYCLES: u8 = 36;
+const SAMPLE_RATE: f32 = 49716.0;
+/// A channel at full volume peaks about as high as an APU pulse at volume 15
+const LEVEL: f32 = PULSE_LEVEL;
+
+/// Attenuation at which an operator is silent
+const MAX_ATTENUATION: f32 = 48.0;
+/// Seconds an envelope takes to decay over its whole range at rate 1, halving every 4 rates
+const DECAY_TIME: f32 = 19.6;
+const ATTACK_TIME: f32 = 2.8;
+/// Release rate of channels with their sustain bit set
+const SUSTAIN_RELEASE_RATE: u8 = 5;
+
+const MULTIPLIERS: [f32; 16] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0];
+/// Key scaling attenuation of the top 4 bits of the frequency in the highest block, in dB
+const KSL_BASE: [f32; 16] = [0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25, 42.0];
+/// 0, 1.5, 3 and 6 dB per octave
+const KSL_FACTORS: [f32; 4] = [0.0, 0.25, 0.5, 1.0];
+
+/// Peak phase deviation the modulator adds to the carrier, in cycles
+const MODULATION_DEPTH: f32 = 2.0;
+const AM_DEPTH: f32 = 4.8;
+const AM_RATE: f32 = 3.7;
+/// Vibrato of ±7 cents
+const VIBRATO_DEPTH: f32 = 7.0 / 1200.0;
+const VIBRATO_RATE: f32 = 6.4;
+
+/// The built-in instruments 1-15, instrument 0 is defined by registers $00-$07
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+impl Stage {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Stage::Attack,
+            1 => Stage::Decay,
+            2 => Stage::Sustain,
+            3 => Stage::Release,
+            _ => Stage::Off,
+        }
+    }
+}
+
+/// The parameters of one operator, taken from an instrument
+struct OperatorPatch {
+    am: bool,
+    vibrato: bool,
+    /// Holds the sustain level while the key is on, otherwise keeps decaying at the release rate
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: f32,
+    key_scale_level: f32,
+    rectified: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: f32,
+    release: u8,
+}
+
+impl OperatorPatch {
+    fn new(patch: &[u8; 8], operator: usize) -> Self {
+        let flags = patch[operator];
+        Self {
+            am: flags & 0x80 != 0,
+            vibrato: flags & 0x40 != 0,
+            sustained: flags & 0x20 != 0,
+            key_scale_rate: flags & 0x10 != 0,
+            multiplier: MULTIPLIERS[(flags & 0x0F) as usize],
+            key_scale_level: KSL_FACTORS[(patch[2 + operator] >> 6) as usize],
+            rectified: patch[3] & (0x08 << operator) != 0,
+            attack: patch[4 + operator] >> 4,
+            decay: patch[4 + operator] & 0x0F,
+            sustain_level: (patch[6 + operator] >> 4) as f32 * 3.0,
+            release: patch[6 + operator] & 0x0F,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Operator {
+    /// In cycles, 0.0..1.0
+    phase: f32,
+    stage: Stage,
+    /// In dB
+    attenuation: f32,
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.stage = Stage::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.stage != Stage::Off {
+            self.stage = Stage::Release;
+        }
+    }
+
+    /// Attenuation change per sample of an envelope which covers its whole range in `time` seconds at rate 1
+    fn rate_step(rate: u8, key_scale: u8, time: f32) -> f32 {
+        if rate == 0 {
+            return 0.0;
+        }
+        let rate = (rate * 4 + key_scale).min(63);
+        MAX_ATTENUATION * 2f32.powf(rate as f32 / 4.0 - 1.0) / (time * SAMPLE_RATE)
+    }
+
+    fn clock_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, sustain: bool) {
+        let key_scale = if patch.key_scale_rate { key_scale } else { key_scale >> 2 };
+        match self.stage {
+            Stage::Attack => {
+                self.attenuation -= Self::rate_step(patch.attack, key_scale, ATTACK_TIME);
+                if patch.attack == 15 || self.attenuation <= 0.0 {
+                    self.attenuation = 0.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.attenuation += Self::rate_step(patch.decay, key_scale, DECAY_TIME);
+                if self.attenuation >= patch.sustain_level {
+                    self.attenuation = patch.sustain_level;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain if patch.sustained => {}
+            Stage::Sustain | Stage::Release => {
+                let rate = if self.stage == Stage::Release && sustain { SUSTAIN_RELEASE_RATE } else { patch.release };
+                self.attenuation += Self::rate_step(rate, key_scale, DECAY_TIME);
+                if self.attenuation >= MAX_ATTENUATION {
+                    self.attenuation = MAX_ATTENUATION;
+                    self.stage = Stage::Off;
+                }
+            }
+            Stage::Off => self.attenuation = MAX_ATTENUATION,
+        }
+    }
+
+    /// The operator's output for a phase offset in cycles, -1.0..=1.0
+    fn output(&self, patch: &OperatorPatch, offset: f32, attenuation: f32) -> f32 {
+        let attenuation = self.attenuation + attenuation;
+        if attenuation >= MAX_ATTENUATION {
+            return 0.0;
+        }
+        let wave = (TAU * (self.phase + offset)).sin();
+        let wave = if patch.rectified { wave.max(0.0) } else { wave };
+        wave * 10f32.powf(-attenuation / 20.0)
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    /// The modulator, then the carrier
+    operators: [Operator; 2],
+    /// The modulator's last two outputs, which it's fed back
+    feedback: [f32; 2],
+    output: f32,
+}
+
+/// Konami's VRC7 sound, a cut down YM2413 (OPLL) with six FM channels of two operators each.
+/// $9010 selects a register and $9030 writes it. This is an approximation working in floating
+/// point, not a bit exact model of the chip's log-sine tables.
+pub struct VRC7Audio {
+    register: u8,
+    registers: [u8; 0x40],
+    channels: [Channel; CHANNELS],
+    divider: u8,
+    /// Phases of the tremolo and vibrato LFOs, in cycles
+    am_phase: f32,
+    vibrato_phase: f32,
+}
+
+impl VRC7Audio {
+    pub fn new() -> Self {
+        Self {
+            register: 0,
+            registers: [0; 0x40],
+            channels: Default::default(),
+            divider: 0,
+            am_phase: 0.0,
+            vibrato_phase: 0.0,
+        }
+    }
+
+    fn patch(&self, channel: usize) -> [u8; 8] {
+        match self.registers[0x30 + channel] >> 4 {
+            0 => self.registers[..8].try_into().unwrap(),
+            instrument => PATCHES[instrument as usize - 1],
+        }
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) {
+        let register = register as usize;
+        if register >= 0x40 {
+            return;
+        }
+        let old = self.registers[register];
+        self.registers[register] = value;
+
+        if let 0x20..=0x25 = register {
+            let channel = &mut self.channels[register - 0x20];
+            let was_on = old & 0x10 != 0;
+            let is_on = value & 0x10 != 0;
+            if is_on && !was_on {
+                channel.operators.iter_mut().for_each(Operator::key_on);
+            } else if !is_on && was_on {
+                channel.operators.iter_mut().for_each(Operator::key_off);
+            }
+        }
+    }
+
+    fn render_sample(&mut self) {
+        self.am_phase = (self.am_phase + AM_RATE / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE / SAMPLE_RATE).fract();
+        let am = AM_DEPTH * (1.0 - (TAU * self.am_phase).cos()) / 2.0;
+        let vibrato = 2f32.powf(VIBRATO_DEPTH * (TAU * self.vibrato_phase).sin());
+
+        for index in 0..CHANNELS {
+            let patch = self.patch(index);
+            let operators = [OperatorPatch::new(&patch, 0), OperatorPatch::new(&patch, 1)];
+            let fnum = self.registers[0x10 + index] as u16 | ((self.registers[0x20 + index] & 0x01) as u16) << 8;
+            let block = (self.registers[0x20 + index] >> 1) & 0x07;
+            let sustain = self.registers[0x20 + index] & 0x20 != 0;
+            let volume = (self.registers[0x30 + index] & 0x0F) as f32 * 3.0;
+            let key_scale = block << 1 | (fnum >> 8) as u8;
+            let ksl = (KSL_BASE[(fnum >> 5) as usize] - 6.0 * (7 - block) as f32).max(0.0);
+            let base_step = (fnum as u32) << block;
+
+            let channel = &mut self.channels[index];
+            for (operator, patch) in channel.operators.iter_mut().zip(&operators) {
+                operator.clock_envelope(patch, key_scale, sustain);
+                let step = base_step as f32 * patch.multiplier / (1 << 19) as f32;
+                let step = if patch.vibrato { step * vibrato } else { step };
+                operator.phase = (operator.phase + step).fract();
+            }
+
+            let [modulator_patch, carrier_patch] = &operators;
+            let tremolo = |patch: &OperatorPatch| if patch.am { am } else { 0.0 };
+
+            let feedback_level = patch[3] & 0x07;
+            let feedback = if feedback_level == 0 {
+                0.0
+            } else {
+                (channel.feedback[0] + channel.feedback[1]) / 2.0 * 2f32.powi(feedback_level as i32) / 64.0
+            };
+            let total_level = (patch[2] & 0x3F) as f32 * 0.75;
+            let modulator = channel.operators[0].output(
+                modulator_patch,
+                feedback,
+                total_level + ksl * modulator_patch.key_scale_level + tremolo(modulator_patch),
+            );
+            channel.feedback = [channel.feedback[1], modulator];
+
+            channel.output = channel.operators[1].output(
+                carrier_patch,
+                modulator * MODULATION_DEPTH,
+                volume + ksl * carrier_patch.key_scale_level + tremolo(carrier_patch),
+            );
+        }
+    }
+}
+
+impl Default for VRC7Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpansionAudio for VRC7Audio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["vrc7_fm1", "vrc7_fm2", "vrc7_fm3", "vrc7_fm4", "vrc7_fm5", "vrc7_fm6"]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x9010 => self.register = value,
+            0x9030 => self.write_register(self.register, value),
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        self.divider += 1;
+        if self.divider == SAMPLE_CYCLES {
+            self.divider = 0;
+            self.render_sample();
+        }
+    }
+
+    fn channel_output(&self, channel: usize) -> f32 {
+        self.channels[channel].output * LEVEL
+    }
+}
+
+impl Savestate for Channel {
+    fn save_state(&self, state: &mut StateWriter) {
+        for operator in &self.operators {
+            state.write(operator.phase);
+            state.write(operator.stage as u8);
+            state.write(operator.attenuation);
+        }
+        state.write(self.feedback[0]);
+        state.write(self.feedback[1]);
+        state.write(self.output);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for operator in &mut self.operators {
+            operator.phase = state.read()?;
+            operator.stage = Stage::from_u8(state.read()?);
+            operator.attenuation = state.read()?;
+        }
+        self.feedback[0] = state.read()?;
+        self.feedback[1] = state.read()?;
+        self.output = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for VRC7Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.register);
+        state.write_bytes(&self.registers);
+        for channel in &self.channels {
+            channel.save_state(state);
+        }
+        state.write(self.divider);
+        state.write(self.am_phase);
+        state.write(self.vibrato_phase);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.register = state.read()?;
+        state.read_bytes_into(&mut self.registers)?;
+        for channel in &mut self.channels {
+            channel.load_state(state)?;
+        }
+        self.divider = state.read()?;
+        self.am_phase = state.read()?;
+        self.vibrato_phase = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 7: switchable 32 KiB PRG bank and single-screen mirroring selected by bit 4
+pub struct AxROM {
+    cartridge: Cartridge,
+    bank: usize,
+    mirroring: Mirroring,
+}
+
+impl AxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            cartridge,
+            bank: 0,
+            mirroring: Mirroring::SingleScreenLower,
+        }
+    }
+}
+
+impl Mapper for AxROM {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.cartridge.read_prg(self.bank, 0x8000, addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x8000..=0xFFFF = addr {
+            self.bank = (value & 0x07) as usize;
+            self.mirroring = if value & 0x10 != 0 {
+                Mirroring::SingleScreenUpper
+            } else {
+                Mirroring::SingleScreenLower
+            };
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(0, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(0, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Savestate for AxROM {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.bank);
+        state.write(self.mirroring);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.bank = state.read()?;
+        self.mirroring = state.read()?;
+        Ok(())
+    }
+}
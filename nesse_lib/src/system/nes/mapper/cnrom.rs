@@ -0,0 +1,55 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 3: fixed PRG ROM with a switchable 8 KiB CHR bank
+pub struct CNROM {
+    cartridge: Cartridge,
+    chr_bank: usize,
+}
+
+impl CNROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self { cartridge, chr_bank: 0 }
+    }
+}
+
+impl Mapper for CNROM {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.cartridge.read_prg(0, 0x8000, addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x8000..=0xFFFF = addr {
+            self.chr_bank = value as usize;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(self.chr_bank, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(self.chr_bank, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring
+    }
+}
+
+impl Savestate for CNROM {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.chr_bank = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,532 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::bail;
+use log::{debug, info};
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::fds::audio::FDSAudio;
+use crate::system::nes::mapper::fds::disk::{finish_crc, raw_to_side, side_to_raw, update_crc, FDSImage};
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::patch::Patch;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+pub mod audio;
+pub mod disk;
+
+pub const BIOS_SIZE: usize = 0x2000;
+const PRG_RAM_SIZE: usize = 0x8000;
+const CHR_RAM_SIZE: usize = 0x2000;
+
+/// CPU cycles the drive takes per byte, about 96.4 kbit/s
+const CYCLES_PER_BYTE: u32 = 150;
+/// CPU cycles from the motor starting until the head reaches the start of the disk
+const HEAD_RETURN_CYCLES: u32 = 50000;
+/// CPU cycles a newly inserted side reads as absent, so the BIOS notices the swap
+const INSERT_DELAY_CYCLES: u32 = 1_789_773;
+
+/// The Famicom Disk System RAM adapter: BIOS, PRG/CHR RAM, the disk drive, a timer IRQ and wavetable audio.
+pub struct FDS {
+    bios: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr_ram: Vec<u8>,
+
+    original_image: FDSImage,
+    raw_sides: Vec<Vec<u8>>,
+    side: Option<usize>,
+    insert_delay: u32,
+    diff_path: Option<PathBuf>,
+
+    disk_io_enabled: bool,
+    sound_io_enabled: bool,
+    mirroring: Mirroring,
+    external_output: u8,
+
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_repeat: bool,
+    irq_enabled: bool,
+    timer_irq: bool,
+
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    transfer_start: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+    transfer_complete: bool,
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+    previous_crc_control: bool,
+    position: usize,
+    delay: u32,
+    crc: u16,
+    read_data: u8,
+    write_data: u8,
+
+    audio: FDSAudio,
+}
+
+impl FDS {
+    /// Creates the RAM adapter with side 0 of the image inserted.
+    /// Disk writes are kept in memory and persisted to `diff_path` as an IPS patch of the image.
+    pub fn new(bios: Vec<u8>, mut image: FDSImage, diff_path: Option<PathBuf>) -> anyhow::Result<Self> {
+        if bios.len() != BIOS_SIZE {
+            bail!("FDS BIOS must be {BIOS_SIZE} bytes, got {}", bios.len());
+        }
+
+        let original_image = image.clone();
+        if let Some(path) = diff_path.as_ref().filter(|path| path.is_file()) {
+            info!("Applying FDS disk changes from {path:?}");
+            let patched = Patch::new(path)?.apply(&image.to_bytes())?;
+            image = FDSImage::from_bytes(&patched)?;
+        }
+
+        Ok(Self {
+            bios,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr_ram: vec![0; CHR_RAM_SIZE],
+            original_image,
+            raw_sides: image.sides.iter().map(|side| side_to_raw(side)).collect(),
+            side: Some(0),
+            insert_delay: 0,
+            diff_path,
+            disk_io_enabled: false,
+            sound_io_enabled: false,
+            mirroring: Mirroring::Horizontal,
+            external_output: 0,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_repeat: false,
+            irq_enabled: false,
+            timer_irq: false,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            transfer_start: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+            transfer_complete: false,
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            previous_crc_control: false,
+            position: 0,
+            delay: 0,
+            crc: 0,
+            read_data: 0,
+            write_data: 0,
+            audio: FDSAudio::new(),
+        })
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.raw_sides.len()
+    }
+
+    pub fn side(&self) -> Option<usize> {
+        self.side
+    }
+
+    /// Inserts a disk side. The drive reports no disk for a moment so the BIOS notices the swap.
+    pub fn insert_side(&mut self, side: usize) -> anyhow::Result<()> {
+        if side >= self.side_count() {
+            bail!("Disk side {side} does not exist, the image has {} sides", self.side_count());
+        }
+
+        debug!("Inserting FDS disk side {side}");
+        self.side = Some(side);
+        self.insert_delay = INSERT_DELAY_CYCLES;
+        self.end_of_head = true;
+        Ok(())
+    }
+
+    pub fn eject(&mut self) {
+        debug!("Ejecting FDS disk");
+        self.side = None;
+    }
+
+    /// The current disk contents in the .fds layout, including writes made by the game
+    pub fn image(&self) -> FDSImage {
+        FDSImage {
+            sides: self.raw_sides.iter().map(|raw| raw_to_side(raw)).collect(),
+        }
+    }
+
+    /// CRC32 of the disk image as loaded, before any writes
+    pub fn checksum(&self) -> u32 {
+        crc32fast::hash(&self.original_image.to_bytes())
+    }
+
+    /// Writes the disk changes to the diff file, leaving the original image untouched
+    pub fn save_changes(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.diff_path else {
+            bail!("No FDS diff file path was set");
+        };
+
+        let diff = Patch::create_ips(&self.original_image.to_bytes(), &self.image().to_bytes());
+        fs::write(path, diff)?;
+        info!("Saved FDS disk changes to {path:?}");
+        Ok(())
+    }
+
+    fn disk_inserted(&self) -> bool {
+        self.side.is_some() && self.insert_delay == 0
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.irq_enabled || !self.disk_io_enabled {
+            return;
+        }
+
+        if self.irq_counter == 0 {
+            self.timer_irq = true;
+            self.irq_counter = self.irq_reload;
+            if !self.irq_repeat {
+                self.irq_enabled = false;
+            }
+        } else {
+            self.irq_counter -= 1;
+        }
+    }
+
+    fn clock_drive(&mut self) {
+        if self.insert_delay > 0 {
+            self.insert_delay -= 1;
+            return;
+        }
+
+        let Some(side) = self.side.filter(|_| self.motor_on) else {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        };
+
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+
+        if self.end_of_head {
+            self.delay = HEAD_RETURN_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        let raw = &mut self.raw_sides[side];
+        let mut raise_irq = self.disk_irq_enabled;
+
+        if self.read_mode {
+            let data = raw.get(self.position).copied().unwrap_or(0);
+
+            if !self.transfer_start {
+                self.gap_ended = false;
+                self.crc = 0;
+            } else if data != 0 && !self.gap_ended {
+                // The block start mark ends the gap without raising an IRQ
+                self.gap_ended = true;
+                raise_irq = false;
+            }
+
+            if self.gap_ended {
+                self.crc = update_crc(self.crc, data);
+                self.transfer_complete = true;
+                self.read_data = data;
+                if raise_irq {
+                    self.disk_irq = true;
+                }
+            }
+        } else {
+            if !self.crc_control {
+                self.transfer_complete = true;
+                if raise_irq {
+                    self.disk_irq = true;
+                }
+            }
+
+            let data = if !self.transfer_start {
+                self.crc = 0;
+                0
+            } else if self.crc_control {
+                if !self.previous_crc_control {
+                    self.crc = finish_crc(self.crc);
+                }
+                let data = self.crc as u8;
+                self.crc >>= 8;
+                data
+            } else {
+                self.crc = update_crc(self.crc, self.write_data);
+                self.write_data
+            };
+
+            if let Some(byte) = raw.get_mut(self.position) {
+                *byte = data;
+            }
+            self.gap_ended = false;
+        }
+
+        self.previous_crc_control = self.crc_control;
+        self.position += 1;
+        if self.position >= raw.len() {
+            self.motor_on = false;
+            self.end_of_head = true;
+        } else {
+            self.delay = CYCLES_PER_BYTE;
+        }
+    }
+
+    fn read_register(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4030 => {
+                let value = self.peek_register(addr);
+                self.timer_irq = false;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                value
+            }
+            0x4031 => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                self.read_data
+            }
+            _ => self.peek_register(addr),
+        }
+    }
+
+    fn peek_register(&self, addr: u16) -> u8 {
+        if !self.disk_io_enabled {
+            return 0x40;
+        }
+
+        match addr {
+            // Images are trusted, so reads never report CRC errors
+            0x4030 => {
+                (self.timer_irq as u8)
+                    | (self.transfer_complete as u8) << 1
+                    | ((self.mirroring == Mirroring::Horizontal) as u8) << 3
+                    | (self.end_of_head as u8) << 6
+            }
+            0x4031 => self.read_data,
+            0x4032 => {
+                let not_inserted = !self.disk_inserted() as u8;
+                let not_ready = (!self.disk_inserted() || !self.scanning) as u8;
+                0x40 | not_inserted | not_ready << 1 | not_inserted << 2
+            }
+            // Bit 7 reports a good battery
+            0x4033 => 0x80,
+            _ => 0x40,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        if addr == 0x4023 {
+            self.disk_io_enabled = value & 0x01 != 0;
+            self.sound_io_enabled = value & 0x02 != 0;
+            if !self.disk_io_enabled {
+                self.irq_enabled = false;
+                self.timer_irq = false;
+                self.disk_irq = false;
+            }
+            return;
+        }
+
+        if !self.disk_io_enabled {
+            return;
+        }
+
+        match addr {
+            0x4020 => self.irq_reload = (self.irq_reload & 0xFF00) | value as u16,
+            0x4021 => self.irq_reload = (self.irq_reload & 0x00FF) | (value as u16) << 8,
+            0x4022 => {
+                self.irq_repeat = value & 0x01 != 0;
+                self.irq_enabled = value & 0x02 != 0;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = value;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.disk_irq = false;
+                self.motor_on = value & 0x01 != 0;
+                self.reset_transfer = value & 0x02 != 0;
+                self.read_mode = value & 0x04 != 0;
+                self.mirroring = if value & 0x08 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.crc_control = value & 0x10 != 0;
+                self.transfer_start = value & 0x40 != 0;
+                self.disk_irq_enabled = value & 0x80 != 0;
+            }
+            0x4026 => self.external_output = value,
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for FDS {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x4030..=0x4033 => self.peek_register(addr),
+            0x4040..=0x4097 if self.sound_io_enabled => self.audio.read(addr),
+            0x6000..=0xDFFF => self.prg_ram[(addr - 0x6000) as usize],
+            0xE000..=0xFFFF => self.bios[(addr - 0xE000) as usize],
+            _ => 0,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4030..=0x4033 => self.read_register(addr),
+            _ => self.cpu_peek(addr),
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4020..=0x4026 => self.write_register(addr, value),
+            0x4040..=0x4097 if self.sound_io_enabled => self.audio.write(addr, value),
+            0x6000..=0xDFFF => self.prg_ram[(addr - 0x6000) as usize] = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr_ram[addr as usize & (CHR_RAM_SIZE - 1)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr_ram[addr as usize & (CHR_RAM_SIZE - 1)] = value;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn clock_cpu(&mut self) {
+        self.clock_timer();
+        self.clock_drive();
+        self.audio.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn audio_channels(&self) -> &[&'static str] {
+        &["fds"]
+    }
+
+    fn audio_channel_output(&self, _channel: usize) -> f32 {
+        self.audio.output()
+    }
+
+    fn fds(&self) -> Option<&FDS> {
+        Some(self)
+    }
+
+    fn fds_mut(&mut self) -> Option<&mut FDS> {
+        Some(self)
+    }
+}
+
+/// The RAM, the disk contents including the game's writes, and the adapter's registers
+impl Savestate for FDS {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.prg_ram);
+        state.write_bytes(&self.chr_ram);
+        state.write(self.raw_sides.len());
+        for raw in &self.raw_sides {
+            state.write_bytes(raw);
+        }
+        state.write(self.side);
+        state.write(self.insert_delay);
+        state.write(self.disk_io_enabled);
+        state.write(self.sound_io_enabled);
+        state.write(self.mirroring);
+        state.write(self.external_output);
+        state.write(self.irq_reload);
+        state.write(self.irq_counter);
+        state.write(self.irq_repeat);
+        state.write(self.irq_enabled);
+        state.write(self.timer_irq);
+        state.write(self.motor_on);
+        state.write(self.reset_transfer);
+        state.write(self.read_mode);
+        state.write(self.crc_control);
+        state.write(self.transfer_start);
+        state.write(self.disk_irq_enabled);
+        state.write(self.disk_irq);
+        state.write(self.transfer_complete);
+        state.write(self.end_of_head);
+        state.write(self.scanning);
+        state.write(self.gap_ended);
+        state.write(self.previous_crc_control);
+        state.write(self.position);
+        state.write(self.delay);
+        state.write(self.crc);
+        state.write(self.read_data);
+        state.write(self.write_data);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.prg_ram)?;
+        state.read_bytes_into(&mut self.chr_ram)?;
+        if state.read::<usize>()? != self.raw_sides.len() {
+            bail!("Save state is for a disk with a different number of sides");
+        }
+        for raw in &mut self.raw_sides {
+            *raw = state.read_bytes()?.to_vec();
+        }
+        self.side = state.read()?;
+        self.insert_delay = state.read()?;
+        self.disk_io_enabled = state.read()?;
+        self.sound_io_enabled = state.read()?;
+        self.mirroring = state.read()?;
+        self.external_output = state.read()?;
+        self.irq_reload = state.read()?;
+        self.irq_counter = state.read()?;
+        self.irq_repeat = state.read()?;
+        self.irq_enabled = state.read()?;
+        self.timer_irq = state.read()?;
+        self.motor_on = state.read()?;
+        self.reset_transfer = state.read()?;
+        self.read_mode = state.read()?;
+        self.crc_control = state.read()?;
+        self.transfer_start = state.read()?;
+        self.disk_irq_enabled = state.read()?;
+        self.disk_irq = state.read()?;
+        self.transfer_complete = state.read()?;
+        self.end_of_head = state.read()?;
+        self.scanning = state.read()?;
+        self.gap_ended = state.read()?;
+        self.previous_crc_control = state.read()?;
+        self.position = state.read()?;
+        self.delay = state.read()?;
+        self.crc = state.read()?;
+        self.read_data = state.read()?;
+        self.write_data = state.read()?;
+        self.audio.load_state(state)?;
+        Ok(())
+    }
+}
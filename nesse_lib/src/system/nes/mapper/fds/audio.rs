@@ -0,0 +1,339 @@
+use crate::system::nes::mapper::audio::ExpansionAudio;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Modulation table steps, where 4 resets the mod counter
+const MOD_STEPS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+const MOD_RESET: u8 = 4;
+
+/// Master volume, as a fraction of 30
+const MASTER_VOLUME: [u32; 4] = [30, 20, 15, 12];
+
+/// Highest possible output, a 6-bit sample at the maximum gain of 32
+const MAX_OUTPUT: f32 = (63 * 32) as f32;
+
+#[derive(Default)]
+struct Envelope {
+    /// Bit 7 disables the envelope, bit 6 selects the direction and bits 0-5 hold the speed
+    control: u8,
+    gain: u8,
+    counter: u32,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8, master_speed: u8) {
+        self.control = value;
+        if value & 0x80 != 0 {
+            self.gain = value & 0x3F;
+        }
+        self.reset_counter(master_speed);
+    }
+
+    fn reset_counter(&mut self, master_speed: u8) {
+        self.counter = 8 * ((self.control & 0x3F) as u32 + 1) * master_speed as u32;
+    }
+
+    fn tick(&mut self, master_speed: u8) {
+        if self.control & 0x80 != 0 || master_speed == 0 {
+            return;
+        }
+
+        if self.counter > 0 {
+            self.counter -= 1;
+            return;
+        }
+
+        self.reset_counter(master_speed);
+        if self.control & 0x40 != 0 {
+            if self.gain < 32 {
+                self.gain += 1;
+            }
+        } else if self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+/// The FDS wavetable channel at $4040-$4092, with its frequency modulation unit.
+pub struct FDSAudio {
+    wave_table: [u8; 64],
+    wave_write_enabled: bool,
+    master_volume: u8,
+    wave_frequency: u16,
+    wave_halted: bool,
+    envelopes_halted: bool,
+    wave_accumulator: u32,
+    wave_position: usize,
+    volume: Envelope,
+    master_envelope_speed: u8,
+    output_level: u32,
+
+    mod_table: [u8; 64],
+    mod_position: usize,
+    mod_frequency: u16,
+    mod_halted: bool,
+    mod_accumulator: u32,
+    /// 7-bit signed counter
+    mod_counter: i8,
+    modulation: Envelope,
+}
+
+impl FDSAudio {
+    pub fn new() -> Self {
+        Self {
+            wave_table: [0; 64],
+            wave_write_enabled: false,
+            master_volume: 0,
+            wave_frequency: 0,
+            wave_halted: true,
+            envelopes_halted: true,
+            wave_accumulator: 0,
+            wave_position: 0,
+            volume: Envelope::default(),
+            master_envelope_speed: 0xE8,
+            output_level: 0,
+            mod_table: [0; 64],
+            mod_position: 0,
+            mod_frequency: 0,
+            mod_halted: true,
+            mod_accumulator: 0,
+            mod_counter: 0,
+            modulation: Envelope::default(),
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x4040..=0x407F => self.wave_table[(addr - 0x4040) as usize] | 0x40,
+            0x4090 => self.volume.gain | 0x40,
+            0x4092 => self.modulation.gain | 0x40,
+            _ => 0x40,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4040..=0x407F if self.wave_write_enabled => {
+                self.wave_table[(addr - 0x4040) as usize] = value & 0x3F;
+            }
+            0x4080 => self.volume.write(value, self.master_envelope_speed),
+            0x4082 => self.wave_frequency = (self.wave_frequency & 0x0F00) | value as u16,
+            0x4083 => {
+                self.wave_frequency = (self.wave_frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.wave_halted = value & 0x80 != 0;
+                self.envelopes_halted = value & 0x40 != 0;
+                if self.wave_halted {
+                    self.wave_accumulator = 0;
+                    self.wave_position = 0;
+                }
+                if self.envelopes_halted {
+                    self.volume.reset_counter(self.master_envelope_speed);
+                    self.modulation.reset_counter(self.master_envelope_speed);
+                }
+            }
+            0x4084 => self.modulation.write(value, self.master_envelope_speed),
+            0x4085 => self.mod_counter = sign_extend_7bit(value),
+            0x4086 => self.mod_frequency = (self.mod_frequency & 0x0F00) | value as u16,
+            0x4087 => {
+                self.mod_frequency = (self.mod_frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.mod_halted = value & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            // The table can only be written while the modulator is halted, two entries at a time
+            0x4088 if self.mod_halted => {
+                self.mod_table[self.mod_position] = value & 0x07;
+                self.mod_table[(self.mod_position + 1) & 0x3F] = value & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            }
+            0x4089 => {
+                self.wave_write_enabled = value & 0x80 != 0;
+                self.master_volume = value & 0x03;
+            }
+            0x408A => self.master_envelope_speed = value,
+            _ => {}
+        }
+    }
+
+    /// Advances the channel by one CPU cycle
+    pub fn clock(&mut self) {
+        if !self.wave_halted && !self.envelopes_halted {
+            self.volume.tick(self.master_envelope_speed);
+            self.modulation.tick(self.master_envelope_speed);
+        }
+
+        if !self.mod_halted && self.mod_frequency != 0 {
+            self.mod_accumulator += self.mod_frequency as u32;
+            if self.mod_accumulator >= 0x10000 {
+                self.mod_accumulator -= 0x10000;
+                self.step_modulator();
+            }
+        }
+
+        if self.wave_halted {
+            self.update_output();
+            return;
+        }
+
+        let pitch = self.modulated_pitch();
+        if pitch > 0 && !self.wave_write_enabled {
+            self.wave_accumulator += pitch as u32;
+            if self.wave_accumulator >= 0x10000 {
+                self.wave_accumulator -= 0x10000;
+                self.wave_position = (self.wave_position + 1) & 0x3F;
+            }
+        }
+        self.update_output();
+    }
+
+    /// Current output in the range 0.0..=1.0
+    pub fn output(&self) -> f32 {
+        self.output_level as f32 / MAX_OUTPUT
+    }
+
+    fn step_modulator(&mut self) {
+        let step = self.mod_table[self.mod_position];
+        self.mod_position = (self.mod_position + 1) & 0x3F;
+
+        if step == MOD_RESET {
+            self.mod_counter = 0;
+        } else {
+            let counter = self.mod_counter as i16 + MOD_STEPS[step as usize] as i16;
+            self.mod_counter = sign_extend_7bit(counter as u8);
+        }
+    }
+
+    /// Pitch after frequency modulation, following the hardware's rounding behaviour
+    fn modulated_pitch(&self) -> i32 {
+        let pitch = self.wave_frequency as i32;
+
+        let mut temp = self.mod_counter as i32 * self.modulation.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            if self.mod_counter < 0 {
+                temp -= 1;
+            } else {
+                temp += 2;
+            }
+        }
+
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+
+        temp *= pitch;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+
+        pitch + temp
+    }
+
+    fn update_output(&mut self) {
+        // The output is held while the wave table is being written
+        if self.wave_write_enabled {
+            return;
+        }
+
+        let gain = self.volume.gain.min(32) as u32;
+        let sample = self.wave_table[self.wave_position] as u32;
+        self.output_level = sample * gain * MASTER_VOLUME[self.master_volume as usize] / 30;
+    }
+}
+
+impl Default for FDSAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpansionAudio for FDSAudio {
+    fn channels(&self) -> &'static [&'static str] {
+        &["fds"]
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        matches!(addr, 0x4040..=0x4097).then(|| FDSAudio::read(self, addr))
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        FDSAudio::write(self, addr, value);
+    }
+
+    fn clock(&mut self) {
+        FDSAudio::clock(self);
+    }
+
+    fn channel_output(&self, _channel: usize) -> f32 {
+        FDSAudio::output(self)
+    }
+}
+
+fn sign_extend_7bit(value: u8) -> i8 {
+    ((value << 1) as i8) >> 1
+}
+
+impl Savestate for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.control);
+        state.write(self.gain);
+        state.write(self.counter);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.control = state.read()?;
+        self.gain = state.read()?;
+        self.counter = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for FDSAudio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.wave_table);
+        state.write(self.wave_write_enabled);
+        state.write(self.master_volume);
+        state.write(self.wave_frequency);
+        state.write(self.wave_halted);
+        state.write(self.envelopes_halted);
+        state.write(self.wave_accumulator);
+        state.write(self.wave_position);
+        self.volume.save_state(state);
+        state.write(self.master_envelope_speed);
+        state.write(self.output_level);
+        state.write_bytes(&self.mod_table);
+        state.write(self.mod_position);
+        state.write(self.mod_frequency);
+        state.write(self.mod_halted);
+        state.write(self.mod_accumulator);
+        state.write(self.mod_counter);
+        self.modulation.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.wave_table)?;
+        self.wave_write_enabled = state.read()?;
+        self.master_volume = state.read()?;
+        self.wave_frequency = state.read()?;
+        self.wave_halted = state.read()?;
+        self.envelopes_halted = state.read()?;
+        self.wave_accumulator = state.read()?;
+        self.wave_position = state.read::<usize>()? & 0x3F;
+        self.volume.load_state(state)?;
+        self.master_envelope_speed = state.read()?;
+        self.output_level = state.read()?;
+        state.read_bytes_into(&mut self.mod_table)?;
+        self.mod_position = state.read::<usize>()? & 0x3F;
+        self.mod_frequency = state.read()?;
+        self.mod_halted = state.read()?;
+        self.mod_accumulator = state.read()?;
+        self.mod_counter = state.read()?;
+        self.modulation.load_state(state)?;
+        Ok(())
+    }
+}
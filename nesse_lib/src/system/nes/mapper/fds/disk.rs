@@ -0,0 +1,180 @@
+use anyhow::bail;
+use log::warn;
+
+const FWNES_MAGIC: &[u8] = b"FDS\x1A";
+const FWNES_HEADER_SIZE: usize = 16;
+const DISK_INFO_MAGIC: &[u8] = b"\x01*NINTENDO-HVC*";
+
+/// Size of a disk side in the .fds layout, where blocks are stored without gaps or CRCs
+pub const SIDE_SIZE: usize = 65500;
+/// Size of a disk side in the .qd layout, where every block is followed by its CRC
+const QD_SIDE_SIZE: usize = 0x10000;
+
+/// The gap before the first block is about 28300 bits long, the gaps between blocks 976 bits
+const LEADING_GAP: usize = 28300 / 8;
+const BLOCK_GAP: usize = 976 / 8;
+/// Roughly the length of a physical disk track, including gaps
+pub const RAW_SIDE_SIZE: usize = 75500;
+
+const BLOCK_START_MARK: u8 = 0x80;
+
+/// A disk image with every side stored in the .fds layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FDSImage {
+    pub sides: Vec<Vec<u8>>,
+}
+
+impl FDSImage {
+    pub fn is_fds(data: &[u8]) -> bool {
+        data.starts_with(FWNES_MAGIC) || data.starts_with(DISK_INFO_MAGIC)
+    }
+
+    /// Parses .fds images with or without fwNES header, and .qd images
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let data = if data.starts_with(FWNES_MAGIC) {
+            &data[FWNES_HEADER_SIZE.min(data.len())..]
+        } else {
+            data
+        };
+
+        let sides: Vec<Vec<u8>> = if data.len() % QD_SIDE_SIZE == 0 && data.len() % SIDE_SIZE != 0 {
+            data.chunks(QD_SIDE_SIZE).map(qd_side_to_fds).collect()
+        } else {
+            data.chunks(SIDE_SIZE)
+                .map(|side| {
+                    let mut side = side.to_vec();
+                    side.resize(SIDE_SIZE, 0);
+                    side
+                })
+                .collect()
+        };
+
+        if sides.is_empty() {
+            bail!("FDS image contains no disk sides");
+        }
+        for (i, side) in sides.iter().enumerate() {
+            if !side.starts_with(DISK_INFO_MAGIC) {
+                bail!("FDS disk side {i} has no disk info block");
+            }
+        }
+
+        Ok(Self { sides })
+    }
+
+    /// Serializes the sides in the headerless .fds layout
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.sides.concat()
+    }
+}
+
+/// Splits a side into its blocks, each starting with the block type.
+/// QD images store a CRC of `crc_size` bytes after every block, .fds images don't.
+fn blocks(side: &[u8], crc_size: usize) -> Vec<&[u8]> {
+    let mut blocks = vec![];
+    let mut pos = 0;
+    let mut file_size = 0;
+
+    while let Some(&block_type) = side.get(pos) {
+        let len = match block_type {
+            1 => 56,
+            2 => 2,
+            3 => 16,
+            4 => 1 + file_size,
+            _ => break,
+        };
+        let Some(block) = side.get(pos..pos + len) else {
+            warn!("Truncated FDS block of type {block_type} at 0x{pos:X}");
+            break;
+        };
+
+        if block_type == 3 {
+            file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+        }
+        blocks.push(block);
+        pos += len + crc_size;
+    }
+
+    blocks
+}
+
+fn qd_side_to_fds(side: &[u8]) -> Vec<u8> {
+    let mut fds = blocks(side, 2).concat();
+    fds.resize(SIDE_SIZE, 0);
+    fds
+}
+
+pub fn update_crc(crc: u16, value: u8) -> u16 {
+    let mut crc = crc;
+    for bit in 0..8 {
+        let carry = crc & 1 != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if value & (1 << bit) != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
+
+/// Flushes the CRC register with two zero bytes, yielding the CRC which is stored after a block
+pub fn finish_crc(crc: u16) -> u16 {
+    update_crc(update_crc(crc, 0), 0)
+}
+
+/// Lays a side out the way the drive head sees it: gaps, block start marks, blocks and CRCs
+pub fn side_to_raw(side: &[u8]) -> Vec<u8> {
+    let mut raw = vec![0; LEADING_GAP];
+
+    for block in blocks(side, 0) {
+        let crc = block.iter().fold(update_crc(0, BLOCK_START_MARK), |crc, &b| update_crc(crc, b));
+
+        raw.push(BLOCK_START_MARK);
+        raw.extend_from_slice(block);
+        raw.extend_from_slice(&finish_crc(crc).to_le_bytes());
+        raw.resize(raw.len() + BLOCK_GAP, 0);
+    }
+
+    if raw.len() < RAW_SIDE_SIZE {
+        raw.resize(RAW_SIDE_SIZE, 0);
+    }
+    raw
+}
+
+/// Recovers the .fds layout from a raw side, picking up blocks written by the BIOS
+pub fn raw_to_side(raw: &[u8]) -> Vec<u8> {
+    let mut side = Vec::with_capacity(SIDE_SIZE);
+    let mut pos = 0;
+    let mut file_size = 0;
+
+    loop {
+        while raw.get(pos) == Some(&0) {
+            pos += 1;
+        }
+        if raw.get(pos) != Some(&BLOCK_START_MARK) {
+            break;
+        }
+        pos += 1;
+
+        let len = match raw.get(pos) {
+            Some(1) => 56,
+            Some(2) => 2,
+            Some(3) => 16,
+            Some(4) => 1 + file_size,
+            _ => break,
+        };
+        let Some(block) = raw.get(pos..pos + len) else {
+            break;
+        };
+
+        if block[0] == 3 {
+            file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+        }
+        side.extend_from_slice(block);
+        pos += len + 2;
+    }
+
+    side.resize(SIDE_SIZE.max(side.len()), 0);
+    side
+}
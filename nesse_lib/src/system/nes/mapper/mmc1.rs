@@ -0,0 +1,146 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 1: registers are loaded serially through a 5-bit shift register
+pub struct MMC1 {
+    cartridge: Cartridge,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    prg_bank: u8,
+}
+
+impl MMC1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            cartridge,
+            shift: 0,
+            shift_count: 0,
+            // Power-up state fixes the last bank at $C000
+            control: 0x0C,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank0 = value,
+            0xC000..=0xDFFF => self.chr_bank1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+
+    /// SUROM and SXROM use CHR bank bit 4 to select the 256 KiB PRG half
+    fn prg_outer_bank(&self) -> usize {
+        if self.cartridge.prg_rom.len() > 0x40000 {
+            (self.chr_bank0 & 0x10) as usize
+        } else {
+            0
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+}
+
+impl Mapper for MMC1 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.cartridge.read_prg_ram(addr),
+            0x8000..=0xFFFF => {
+                let bank = (self.prg_bank & 0x0F) as usize;
+                let outer = self.prg_outer_bank();
+                match ((self.control >> 2) & 0x03, addr) {
+                    (0 | 1, _) => self.cartridge.read_prg((outer | bank) >> 1, 0x8000, addr),
+                    (2, 0x8000..=0xBFFF) => self.cartridge.read_prg(outer, 0x4000, addr),
+                    (2, _) => self.cartridge.read_prg(outer | bank, 0x4000, addr),
+                    (_, 0x8000..=0xBFFF) => self.cartridge.read_prg(outer | bank, 0x4000, addr),
+                    (_, _) => self.cartridge.read_prg(outer | 0x0F, 0x4000, addr),
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.cartridge.write_prg_ram(addr, value),
+            0x8000..=0xFFFF => {
+                if value & 0x80 != 0 {
+                    self.shift = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                self.shift |= (value & 0x01) << self.shift_count;
+                self.shift_count += 1;
+                if self.shift_count == 5 {
+                    self.write_register(addr, self.shift);
+                    self.shift = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        if self.control & 0x10 == 0 {
+            self.cartridge.read_chr((self.chr_bank0 >> 1) as usize, 0x2000, addr)
+        } else if addr < 0x1000 {
+            self.cartridge.read_chr(self.chr_bank0 as usize, 0x1000, addr)
+        } else {
+            self.cartridge.read_chr(self.chr_bank1 as usize, 0x1000, addr)
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.control & 0x10 == 0 {
+            self.cartridge.write_chr((self.chr_bank0 >> 1) as usize, 0x2000, addr, value);
+        } else if addr < 0x1000 {
+            self.cartridge.write_chr(self.chr_bank0 as usize, 0x1000, addr, value);
+        } else {
+            self.cartridge.write_chr(self.chr_bank1 as usize, 0x1000, addr, value);
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
+
+impl Savestate for MMC1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.shift);
+        state.write(self.shift_count);
+        state.write(self.control);
+        state.write(self.chr_bank0);
+        state.write(self.chr_bank1);
+        state.write(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.shift = state.read()?;
+        self.shift_count = state.read()?;
+        self.control = state.read()?;
+        self.chr_bank0 = state.read()?;
+        self.chr_bank1 = state.read()?;
+        self.prg_bank = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 201, the NovelDiamond multicarts: the low byte of the address written to selects both
+/// the 32 KiB PRG bank and the 8 KiB CHR bank of a game
+pub struct NovelDiamond {
+    cartridge: Cartridge,
+    bank: usize,
+}
+
+impl NovelDiamond {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self { cartridge, bank: 0 }
+    }
+}
+
+impl Mapper for NovelDiamond {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.cartridge.read_prg(self.bank, 0x8000, addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, _value: u8) {
+        if let 0x8000..=0xFFFF = addr {
+            self.bank = (addr & 0xFF) as usize;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(self.bank, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(self.bank, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring
+    }
+}
+
+impl Savestate for NovelDiamond {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.bank = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 0: up to 32 KiB PRG ROM, with 16 KiB ROMs mirrored into $C000-$FFFF
+pub struct NROM {
+    cartridge: Cartridge,
+}
+
+impl NROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self { cartridge }
+    }
+}
+
+impl Mapper for NROM {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_prg_ram(addr),
+            0x8000..=0xFFFF => self.cartridge.read_prg(0, 0x8000, addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.cartridge.write_prg_ram(addr, value);
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(0, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(0, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring
+    }
+}
+
+impl Savestate for NROM {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        Ok(())
+    }
+}
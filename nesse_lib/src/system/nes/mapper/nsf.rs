@@ -0,0 +1,225 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::audio::mmc5::MMC5Audio;
+use crate::system::nes::mapper::audio::n163::N163Audio;
+use crate::system::nes::mapper::audio::sunsoft5b::Sunsoft5BAudio;
+use crate::system::nes::mapper::audio::vrc6::VRC6Audio;
+use crate::system::nes::mapper::audio::vrc7::VRC7Audio;
+use crate::system::nes::mapper::audio::ExpansionAudio;
+use crate::system::nes::mapper::fds::audio::FDSAudio;
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::nsf::{NSFExpansion, NSFFile};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const BANK_SIZE: usize = 0x1000;
+/// 4 KiB slots from $6000 to $FFFF, the first two only switchable for FDS tunes
+const SLOT_COUNT: usize = 10;
+
+type ChipConstructor = fn() -> Box<dyn ExpansionAudio>;
+
+/// The board an NSF tune runs on: 4 KiB banks switched through $5FF8-$5FFF and 8 KiB of RAM at $6000.
+/// FDS tunes get RAM from $6000-$FFFF which banks are copied into. The sound chips the tune asks for
+/// are all present at once, and MMC5 tunes also get its multiplier and ExRAM.
+pub struct NSFMapper {
+    data: Vec<u8>,
+    banks: [usize; SLOT_COUNT],
+    ram: Vec<u8>,
+    fds: bool,
+    /// In the order of the header's expansion bits
+    chips: Vec<Box<dyn ExpansionAudio>>,
+    /// The channels of all chips
+    channels: Vec<&'static str>,
+    mmc5: bool,
+    /// Operands of the MMC5's multiplier at $5205/$5206
+    multiplier: [u8; 2],
+    /// The MMC5's ExRAM, RAM at $5C00-$5FF5 for NSF tunes
+    exram: Vec<u8>,
+}
+
+impl NSFMapper {
+    pub fn new(nsf: &NSFFile) -> Self {
+        let fds = nsf.expansion.contains(NSFExpansion::FDS);
+        let mmc5 = nsf.expansion.contains(NSFExpansion::MMC5);
+        let mut banks = [0; SLOT_COUNT];
+
+        let padding = if nsf.is_bankswitched() {
+            for (slot, &bank) in banks[2..].iter_mut().zip(nsf.bank_init.iter()) {
+                *slot = bank as usize;
+            }
+            if fds {
+                // $5FF6/$5FF7 start out with the banks of $E000/$F000
+                banks[0] = nsf.bank_init[6] as usize;
+                banks[1] = nsf.bank_init[7] as usize;
+            }
+            nsf.load_addr as usize & (BANK_SIZE - 1)
+        } else {
+            // Map the data linearly from $6000 for FDS tunes, from $8000 otherwise
+            let base = if fds { 0x6000 } else { 0x8000 };
+            for (slot, bank) in banks.iter_mut().enumerate() {
+                *bank = if fds { slot } else { slot.wrapping_sub(2) };
+            }
+            nsf.load_addr as usize - base
+        };
+
+        let mut data = vec![0; padding];
+        data.extend_from_slice(&nsf.data);
+
+        let mut mapper = Self {
+            data,
+            banks,
+            ram: vec![0; if fds { SLOT_COUNT * BANK_SIZE } else { 0x2000 }],
+            fds,
+            chips: vec![],
+            channels: vec![],
+            mmc5,
+            multiplier: [0; 2],
+            exram: vec![0; if mmc5 { 0x400 } else { 0 }],
+        };
+        let chips: [(NSFExpansion, ChipConstructor); 6] = [
+            (NSFExpansion::VRC6, || Box::new(VRC6Audio::new())),
+            (NSFExpansion::VRC7, || Box::new(VRC7Audio::new())),
+            (NSFExpansion::FDS, || Box::new(FDSAudio::new())),
+            (NSFExpansion::MMC5, || Box::new(MMC5Audio::new())),
+            (NSFExpansion::N163, || Box::new(N163Audio::new())),
+            (NSFExpansion::Sunsoft5B, || Box::new(Sunsoft5BAudio::new())),
+        ];
+        for (expansion, new_chip) in chips {
+            if nsf.expansion.contains(expansion) {
+                let chip = new_chip();
+                mapper.channels.extend_from_slice(chip.channels());
+                mapper.chips.push(chip);
+            }
+        }
+        if fds {
+            for slot in 0..SLOT_COUNT {
+                mapper.copy_bank_to_ram(slot);
+            }
+        }
+        mapper
+    }
+
+    fn read_bank(&self, bank: usize, offset: usize) -> u8 {
+        bank.checked_mul(BANK_SIZE)
+            .and_then(|start| self.data.get(start + offset))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn switch_bank(&mut self, slot: usize, bank: u8) {
+        self.banks[slot] = bank as usize;
+        if self.fds {
+            self.copy_bank_to_ram(slot);
+        }
+    }
+
+    fn copy_bank_to_ram(&mut self, slot: usize) {
+        for offset in 0..BANK_SIZE {
+            self.ram[slot * BANK_SIZE + offset] = self.read_bank(self.banks[slot], offset);
+        }
+    }
+}
+
+impl Mapper for NSFMapper {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        if let Some(value) = self.chips.iter().find_map(|chip| chip.peek(addr)) {
+            return value;
+        }
+        match addr {
+            0x5205 | 0x5206 if self.mmc5 => {
+                let product = self.multiplier[0] as u16 * self.multiplier[1] as u16;
+                product.to_le_bytes()[addr as usize - 0x5205]
+            }
+            0x5C00..=0x5FF5 if self.mmc5 => self.exram[addr as usize - 0x5C00],
+            0x6000..=0xFFFF if self.fds => self.ram[addr as usize - 0x6000],
+            0x6000..=0x7FFF => self.ram[addr as usize - 0x6000],
+            0x8000..=0xFFFF => {
+                let slot = (addr as usize - 0x6000) / BANK_SIZE;
+                self.read_bank(self.banks[slot], addr as usize & (BANK_SIZE - 1))
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        if let Some(value) = self.chips.iter_mut().find_map(|chip| chip.read(addr)) {
+            return value;
+        }
+        self.cpu_peek(addr)
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        for chip in &mut self.chips {
+            chip.write(addr, value);
+        }
+        match addr {
+            0x5205 | 0x5206 if self.mmc5 => self.multiplier[addr as usize - 0x5205] = value,
+            0x5C00..=0x5FF5 if self.mmc5 => self.exram[addr as usize - 0x5C00] = value,
+            0x5FF6..=0x5FF7 if self.fds => self.switch_bank(addr as usize - 0x5FF6, value),
+            0x5FF8..=0x5FFF => self.switch_bank(addr as usize - 0x5FF6, value),
+            0x6000..=0xFFFF if self.fds => self.ram[addr as usize - 0x6000] = value,
+            0x6000..=0x7FFF => self.ram[addr as usize - 0x6000] = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _value: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn clock_cpu(&mut self) {
+        for chip in &mut self.chips {
+            chip.clock();
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.chips.iter().map(|chip| chip.output()).sum()
+    }
+
+    fn audio_channels(&self) -> &[&'static str] {
+        &self.channels
+    }
+
+    fn audio_channel_output(&self, mut channel: usize) -> f32 {
+        for chip in &self.chips {
+            let count = chip.channels().len();
+            if channel < count {
+                return chip.channel_output(channel);
+            }
+            channel -= count;
+        }
+        0.0
+    }
+}
+
+impl Savestate for NSFMapper {
+    fn save_state(&self, state: &mut StateWriter) {
+        for bank in self.banks {
+            state.write(bank);
+        }
+        state.write_bytes(&self.ram);
+        state.write_bytes(&self.multiplier);
+        state.write_bytes(&self.exram);
+        for chip in &self.chips {
+            chip.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        for bank in &mut self.banks {
+            *bank = state.read()?;
+        }
+        state.read_bytes_into(&mut self.ram)?;
+        state.read_bytes_into(&mut self.multiplier)?;
+        state.read_bytes_into(&mut self.exram)?;
+        for chip in &mut self.chips {
+            chip.load_state(state)?;
+        }
+        Ok(())
+    }
+}
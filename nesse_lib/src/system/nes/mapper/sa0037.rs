@@ -0,0 +1,63 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 148, Sachen SA-0037: 32 KiB PRG bank in bit 3 and 8 KiB CHR bank in bits 0-2
+pub struct SA0037 {
+    cartridge: Cartridge,
+    prg_bank: usize,
+    chr_bank: usize,
+}
+
+impl SA0037 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            cartridge,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for SA0037 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.cartridge.read_prg(self.prg_bank, 0x8000, addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x8000..=0xFFFF = addr {
+            self.prg_bank = ((value >> 3) & 0x01) as usize;
+            self.chr_bank = (value & 0x07) as usize;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(self.chr_bank, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(self.chr_bank, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring
+    }
+}
+
+impl Savestate for SA0037 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.prg_bank);
+        state.write(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.prg_bank = state.read()?;
+        self.chr_bank = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::system::nes::file::Mirroring;
+use crate::system::nes::mapper::{Cartridge, Mapper};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Mapper 2: switchable 16 KiB bank at $8000, last bank fixed at $C000
+pub struct UxROM {
+    cartridge: Cartridge,
+    bank: usize,
+}
+
+impl UxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self { cartridge, bank: 0 }
+    }
+}
+
+impl Mapper for UxROM {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_prg_ram(addr),
+            0x8000..=0xBFFF => self.cartridge.read_prg(self.bank, 0x4000, addr),
+            0xC000..=0xFFFF => {
+                let last_bank = (self.cartridge.prg_rom.len() / 0x4000).saturating_sub(1);
+                self.cartridge.read_prg(last_bank, 0x4000, addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.write_prg_ram(addr, value),
+            0x8000..=0xFFFF => self.bank = value as usize,
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.cartridge.read_chr(0, 0x2000, addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.cartridge.write_chr(0, 0x2000, addr, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring
+    }
+}
+
+impl Savestate for UxROM {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge.save_state(state);
+        state.write(self.bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.cartridge.load_state(state)?;
+        self.bank = state.read()?;
+        Ok(())
+    }
+}
@@ -1,24 +1,36 @@
 use std::mem::size_of;
 use anyhow::format_err;
 use num_traits::{FromBytes, ToBytes};
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
 
 pub struct Memory {
     ram: [u8; 0x800],             // 0x0000 - 0x07FF mirrored to 0x1FFF - 0x1FFF
-    ppu: [u8; 0x8],               // 0x2000 - 0x2007
     apu_io_registers: [u8; 0x18], // 0x4000 - 0x4017
-    cartridge_map: [u8; 0xBFE0],  // 0x4020 - 0xFFFF
+    cartridge: Option<Box<dyn Mapper>>, // 0x4020 - 0xFFFF
 }
 
 impl Memory {
     pub fn new() -> Self {
         Self {
             ram: [0; 0x800],
-            ppu: [0; 0x8],
             apu_io_registers: [0; 0x18],
-            cartridge_map: [0; 0xBFE0],
+            cartridge: None,
         }
     }
 
+    pub fn insert_cartridge(&mut self, cartridge: Box<dyn Mapper>) {
+        self.cartridge = Some(cartridge);
+    }
+
+    pub fn cartridge(&self) -> Option<&dyn Mapper> {
+        self.cartridge.as_deref()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut dyn Mapper> {
+        self.cartridge.as_mut().map(|cartridge| cartridge.as_mut() as &mut dyn Mapper)
+    }
+
     pub fn write<T: ToBytes>(&mut self, mut dst: u16, value: T) -> anyhow::Result<()> {
         let size = size_of::<T>();
         if dst as usize + size > 0x10000 {
@@ -28,24 +40,13 @@ impl Memory {
         let byte_data = binding.as_ref();
 
         for byte in byte_data {
-            if dst <= 0x07FF {
-                self.ram[dst as usize] = *byte;
-            } else if dst >= 0x0800 && dst <= 0x1FFF {
-                self.ram[dst as usize - 0x0800] = *byte;
-            } else if dst >= 0x2000 && dst <= 0x2007 {
-                self.ppu[dst as usize - 0x2000] = *byte;
-            } else if dst >= 0x4000 && dst <= 0x4017 {
-                self.apu_io_registers[dst as usize - 0x4000] = *byte;
-            } else if dst >= 0x4020 {
-                self.cartridge_map[dst as usize - 0x4020] = *byte;
-            }
-
+            self.write_byte(dst, *byte);
             dst += 1;
         }
 
         Ok(())
     }
-    
+
     pub fn write_slice(&mut self, dst: u16, data: &[u8]) -> anyhow::Result<()> {
         if dst as usize + data.len() > 0x10000 {
             return Err(format_err!("Out of Bounds Write to 0x{dst:x?} of size {}", data.len()));
@@ -53,46 +54,84 @@ impl Memory {
 
         for (i, byte) in data.iter().enumerate() {
             let cur_dst = dst + i as u16;
-            if cur_dst <= 0x07FF {
-                self.ram[cur_dst as usize] = *byte;
-            } else if cur_dst >= 0x0800 && cur_dst <= 0x1FFF {
-                self.ram[cur_dst as usize - 0x0800] = *byte;
-            } else if cur_dst >= 0x2000 && cur_dst <= 0x2007 {
-                self.ppu[cur_dst as usize - 0x2000] = *byte;
-            } else if cur_dst >= 0x4000 && cur_dst <= 0x4017 {
-                self.apu_io_registers[cur_dst as usize - 0x4000] = *byte;
-            } else if cur_dst >= 0x4020 {
-                self.cartridge_map[cur_dst as usize - 0x4020] = *byte;
-            } else {
+            if (0x4018..0x4020).contains(&cur_dst) {
                 return Err(format_err!("Invalid Memory Address 0x{cur_dst:x?}"));
             }
+            self.write_byte(cur_dst, *byte);
         }
 
         Ok(())
     }
 
-    pub fn read<T: FromBytes + Copy + bytemuck::Pod>(&self, addr: u16) -> anyhow::Result<T> {
+    pub fn read<T: FromBytes + Copy + bytemuck::Pod>(&mut self, addr: u16) -> anyhow::Result<T> {
+        let size = size_of::<T>();
+        if addr as usize + size > 0x10000 {
+            return Err(format_err!("Out of Bounds Read at 0x{addr:x?}"));
+        }
+
+        let mut value = T::zeroed();
+        for (i, byte) in bytemuck::bytes_of_mut(&mut value).iter_mut().enumerate() {
+            *byte = self.read_byte(addr + i as u16)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads like `read`, but without triggering side effects on the cartridge
+    pub fn peek<T: FromBytes + Copy + bytemuck::Pod>(&self, addr: u16) -> anyhow::Result<T> {
         let size = size_of::<T>();
         if addr as usize + size > 0x10000 {
             return Err(format_err!("Out of Bounds Read at 0x{addr:x?}"));
         }
 
-        let bytes;
-
-        if addr <= 0x07FF {
-            bytes = &self.ram[addr as usize..addr as usize + size];
-        } else if addr >= 0x0800 && addr <= 0x1FFF {
-            bytes = &self.ram[addr as usize - 0x0800.. addr as usize - 0x0800 + size];
-        } else if addr >= 0x2000 && addr <= 0x2007 {
-            bytes = &self.ppu[addr as usize - 0x2000..addr as usize - 0x2000 + size];
-        } else if addr >= 0x4000 && addr <= 0x4017 {
-            bytes = &self.apu_io_registers[addr as usize - 0x4000..addr as usize - 0x4000 + size];
-        } else if addr >= 0x4020 {
-            bytes = &self.cartridge_map[addr as usize - 0x4020..addr as usize - 0x4020 + size];
-        } else {
-            return Err(format_err!("Invalid Memory Address 0x{addr:x?}"));
+        let mut value = T::zeroed();
+        for (i, byte) in bytemuck::bytes_of_mut(&mut value).iter_mut().enumerate() {
+            *byte = self.peek_byte(addr + i as u16)?;
         }
 
-        Ok(*bytemuck::from_bytes(bytes))
+        Ok(value)
     }
-}
\ No newline at end of file
+
+    fn write_byte(&mut self, dst: u16, byte: u8) {
+        match dst {
+            0x0000..=0x1FFF => self.ram[dst as usize & 0x07FF] = byte,
+            0x4000..=0x4017 => self.apu_io_registers[dst as usize - 0x4000] = byte,
+            0x4020..=0xFFFF => {
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.cpu_write(dst, byte);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_byte(&mut self, addr: u16) -> anyhow::Result<u8> {
+        match addr {
+            0x4020..=0xFFFF => Ok(self.cartridge.as_mut().map_or(0, |cartridge| cartridge.cpu_read(addr))),
+            _ => self.peek_byte(addr),
+        }
+    }
+
+    fn peek_byte(&self, addr: u16) -> anyhow::Result<u8> {
+        match addr {
+            0x0000..=0x1FFF => Ok(self.ram[addr as usize & 0x07FF]),
+            0x4000..=0x4017 => Ok(self.apu_io_registers[addr as usize - 0x4000]),
+            0x4020..=0xFFFF => Ok(self.cartridge.as_ref().map_or(0, |cartridge| cartridge.cpu_peek(addr))),
+            _ => Err(format_err!("Invalid Memory Address 0x{addr:x?}")),
+        }
+    }
+}
+
+/// Internal RAM and the register shadow, the cartridge is saved on its own
+impl Savestate for Memory {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bytes(&self.apu_io_registers);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.ram)?;
+        state.read_bytes_into(&mut self.apu_io_registers)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{bail, format_err};
+use bitflags::bitflags;
+use log::debug;
+use crate::system::nes::file::Region;
+
+pub mod player;
+
+const NSF_MAGIC: &[u8] = b"NESM\x1A";
+const NSFE_MAGIC: &[u8] = b"NSFE";
+const NSF_HEADER_SIZE: usize = 0x80;
+
+/// Default PLAY periods in microseconds, used when a file leaves its speed at 0
+pub const NTSC_PLAY_PERIOD: u16 = 16639;
+pub const PAL_PLAY_PERIOD: u16 = 19997;
+
+bitflags! {
+    /// Expansion sound chips a tune writes to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct NSFExpansion : u8 {
+        const VRC6 = 0b0000_0001;
+        const VRC7 = 0b0000_0010;
+        const FDS = 0b0000_0100;
+        const MMC5 = 0b0000_1000;
+        const N163 = 0b0001_0000;
+        const Sunsoft5B = 0b0010_0000;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NSFTrack {
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+    pub fade: Option<Duration>,
+}
+
+/// A tune in the NSF (including NSF2) or chunked NSFe format.
+#[derive(Debug)]
+pub struct NSFFile {
+    pub version: u8,
+    /// 0-based index of the track to play first
+    pub starting_song: u8,
+    pub load_addr: u16,
+    pub init_addr: u16,
+    pub play_addr: u16,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    pub ripper: Option<String>,
+    /// PLAY periods in microseconds
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    /// Initial values of $5FF8-$5FFF, all zero if the tune isn't bankswitched
+    pub bank_init: [u8; 8],
+    pub region: Region,
+    pub expansion: NSFExpansion,
+    pub data: Vec<u8>,
+    pub tracks: Vec<NSFTrack>,
+    /// NSFe play order, as track indices
+    pub playlist: Option<Vec<u8>>,
+}
+
+impl NSFFile {
+    pub fn new<P: AsRef<Path>>(nsf_path: &P) -> anyhow::Result<Self> {
+        Self::from_bytes(&fs::read(nsf_path)?)
+    }
+
+    pub fn is_nsf(data: &[u8]) -> bool {
+        data.starts_with(NSF_MAGIC) || data.starts_with(NSFE_MAGIC)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if data.starts_with(NSF_MAGIC) {
+            Self::from_nsf(data)
+        } else if data.starts_with(NSFE_MAGIC) {
+            Self::from_nsfe(data)
+        } else {
+            bail!("Invalid NSF file");
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            version: 1,
+            starting_song: 0,
+            load_addr: 0,
+            init_addr: 0,
+            play_addr: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ripper: None,
+            ntsc_speed: NTSC_PLAY_PERIOD,
+            pal_speed: PAL_PLAY_PERIOD,
+            bank_init: [0; 8],
+            region: Region::NTSC,
+            expansion: NSFExpansion::empty(),
+            data: vec![],
+            tracks: vec![],
+            playlist: None,
+        }
+    }
+
+    fn from_nsf(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < NSF_HEADER_SIZE {
+            bail!("NSF header is truncated");
+        }
+
+        let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let mut nsf = Self::empty();
+        nsf.version = data[0x05];
+        nsf.tracks = vec![NSFTrack::default(); data[0x06] as usize];
+        nsf.starting_song = data[0x07].saturating_sub(1);
+        nsf.load_addr = read_u16(0x08);
+        nsf.init_addr = read_u16(0x0A);
+        nsf.play_addr = read_u16(0x0C);
+        nsf.title = read_string(&data[0x0E..0x2E]);
+        nsf.artist = read_string(&data[0x2E..0x4E]);
+        nsf.copyright = read_string(&data[0x4E..0x6E]);
+        nsf.set_speeds(read_u16(0x6E), read_u16(0x78));
+        nsf.bank_init.copy_from_slice(&data[0x70..0x78]);
+        nsf.region = region_from_flags(data[0x7A]);
+        nsf.expansion = NSFExpansion::from_bits_truncate(data[0x7B]);
+
+        // NSF2 may store the data length, followed by NSFe metadata chunks
+        let data_len = u32::from_le_bytes([data[0x7D], data[0x7E], data[0x7F], 0]) as usize;
+        let program = &data[NSF_HEADER_SIZE..];
+        if nsf.version >= 2 && data_len != 0 && data_len < program.len() {
+            nsf.data = program[..data_len].to_vec();
+            nsf.read_chunks(&program[data_len..])?;
+        } else {
+            nsf.data = program.to_vec();
+        }
+
+        nsf.validate()?;
+        Ok(nsf)
+    }
+
+    fn from_nsfe(data: &[u8]) -> anyhow::Result<Self> {
+        let mut nsf = Self::empty();
+        let chunks = nsf.read_chunks(&data[NSFE_MAGIC.len()..])?;
+        if !chunks.contains(b"INFO") || !chunks.contains(b"DATA") {
+            bail!("NSFe file is missing its INFO or DATA chunk");
+        }
+
+        nsf.validate()?;
+        Ok(nsf)
+    }
+
+    /// Reads NSFe chunks until `NEND`, returning the IDs of the chunks that were found
+    fn read_chunks(&mut self, data: &[u8]) -> anyhow::Result<Vec<[u8; 4]>> {
+        let mut found = vec![];
+        let mut pos = 0;
+        while pos + 8 <= data.len() {
+            let len = u32::from_le_bytes(data[pos..pos + 4].try_into()?) as usize;
+            let id: [u8; 4] = data[pos + 4..pos + 8].try_into()?;
+            let chunk = data
+                .get(pos + 8..pos + 8 + len)
+                .ok_or_else(|| format_err!("Truncated NSFe chunk {}", String::from_utf8_lossy(&id)))?;
+            pos += 8 + len;
+            found.push(id);
+
+            match &id {
+                b"INFO" => self.read_info(chunk)?,
+                b"DATA" => self.data = chunk.to_vec(),
+                b"BANK" => {
+                    self.bank_init = [0; 8];
+                    let len = chunk.len().min(8);
+                    self.bank_init[..len].copy_from_slice(&chunk[..len]);
+                }
+                b"RATE" => {
+                    let speed = |offset: usize| chunk.get(offset..offset + 2).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]));
+                    self.set_speeds(speed(0), speed(2));
+                }
+                b"auth" => {
+                    let mut strings = chunk.split(|&b| b == 0).map(read_string);
+                    self.title = strings.next().unwrap_or_default();
+                    self.artist = strings.next().unwrap_or_default();
+                    self.copyright = strings.next().unwrap_or_default();
+                    self.ripper = strings.next().filter(|ripper| !ripper.is_empty());
+                }
+                b"tlbl" => {
+                    for (track, title) in self.tracks.iter_mut().zip(chunk.split(|&b| b == 0)) {
+                        track.title = Some(read_string(title)).filter(|title| !title.is_empty());
+                    }
+                }
+                b"time" => {
+                    for (track, time) in self.tracks.iter_mut().zip(read_times(chunk)) {
+                        track.duration = time;
+                    }
+                }
+                b"fade" => {
+                    for (track, fade) in self.tracks.iter_mut().zip(read_times(chunk)) {
+                        track.fade = fade;
+                    }
+                }
+                b"plst" => self.playlist = Some(chunk.to_vec()),
+                b"NEND" => break,
+                // Chunks starting with an uppercase letter must be understood to play the file
+                _ if id[0].is_ascii_uppercase() => bail!("Unsupported NSFe chunk {}", String::from_utf8_lossy(&id)),
+                _ => debug!("Ignoring NSFe chunk {}", String::from_utf8_lossy(&id)),
+            }
+        }
+
+        Ok(found)
+    }
+
+    fn read_info(&mut self, chunk: &[u8]) -> anyhow::Result<()> {
+        if chunk.len() < 9 {
+            bail!("NSFe INFO chunk is truncated");
+        }
+
+        let read_u16 = |offset: usize| u16::from_le_bytes([chunk[offset], chunk[offset + 1]]);
+        self.load_addr = read_u16(0);
+        self.init_addr = read_u16(2);
+        self.play_addr = read_u16(4);
+        self.region = region_from_flags(chunk[6]);
+        self.expansion = NSFExpansion::from_bits_truncate(chunk[7]);
+        self.tracks.resize(chunk[8] as usize, NSFTrack::default());
+        self.starting_song = chunk.get(9).copied().unwrap_or(0);
+        Ok(())
+    }
+
+    fn set_speeds(&mut self, ntsc_speed: u16, pal_speed: u16) {
+        self.ntsc_speed = if ntsc_speed == 0 { NTSC_PLAY_PERIOD } else { ntsc_speed };
+        self.pal_speed = if pal_speed == 0 { PAL_PLAY_PERIOD } else { pal_speed };
+    }
+
+    fn validate(&mut self) -> anyhow::Result<()> {
+        if self.tracks.is_empty() {
+            bail!("NSF file has no tracks");
+        }
+        if self.data.is_empty() {
+            bail!("NSF file has no program data");
+        }
+
+        // Without bankswitching, the data is loaded as is and has to fit below $10000
+        let lowest_addr = if self.expansion.contains(NSFExpansion::FDS) { 0x6000 } else { 0x8000 };
+        if self.load_addr < lowest_addr {
+            bail!("NSF load address 0x{:04X} is below 0x{lowest_addr:04X}", self.load_addr);
+        }
+
+        if self.starting_song as usize >= self.tracks.len() {
+            self.starting_song = 0;
+        }
+        Ok(())
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.bank_init.iter().any(|&bank| bank != 0)
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+}
+
+fn region_from_flags(flags: u8) -> Region {
+    match flags & 0b11 {
+        0 => Region::NTSC,
+        1 => Region::PAL,
+        _ => Region::MultiRegion,
+    }
+}
+
+fn read_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_owned()
+}
+
+/// Track times in milliseconds, where negative values mean the player's default
+fn read_times(chunk: &[u8]) -> impl Iterator<Item = Option<Duration>> + '_ {
+    chunk.chunks_exact(4).map(|time| {
+        let ms = i32::from_le_bytes(time.try_into().unwrap());
+        (ms >= 0).then(|| Duration::from_millis(ms as u64))
+    })
+}
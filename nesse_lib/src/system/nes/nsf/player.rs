@@ -0,0 +1,195 @@
+use std::path::Path;
+use std::time::Duration;
+use anyhow::bail;
+use log::info;
+use crate::system::nes::file::Region;
+use crate::system::nes::iobus::IOBus;
+use crate::system::nes::mapper::nsf::NSFMapper;
+use crate::system::nes::nsf::NSFFile;
+use crate::system::nes::NES;
+
+pub use crate::system::nes::apu::resampler::DEFAULT_SAMPLE_RATE;
+
+/// INIT and PLAY are called like a JSR from here, the routine is done once PC returns to it
+const RETURN_ADDR: u16 = 0x5FF0;
+
+/// Used for tracks without an NSFe duration
+pub const DEFAULT_TRACK_DURATION: Duration = Duration::from_secs(150);
+
+/// Plays NSF/NSFe tunes by calling their INIT and PLAY routines on the CPU core, resampling the audio output to PCM.
+pub struct NsfPlayer {
+    pub nes: NES,
+    pub nsf: NSFFile,
+    region: Region,
+    track: u8,
+    sample_rate: u32,
+    in_routine: bool,
+    play_period: f64,
+    cycles_until_play: f64,
+    samples_rendered: u64,
+}
+
+impl NsfPlayer {
+    pub fn new<P: AsRef<Path>>(nsf_path: &P) -> anyhow::Result<Self> {
+        Self::from_nsf(NSFFile::new(nsf_path)?)
+    }
+
+    /// Creates a player with the tune's starting track selected
+    pub fn from_nsf(nsf: NSFFile) -> anyhow::Result<Self> {
+        info!("NSF \"{}\" by {}, {} tracks", nsf.title, nsf.artist, nsf.track_count());
+
+        let region = if nsf.region == Region::PAL { Region::PAL } else { Region::NTSC };
+        let mut player = Self {
+            nes: NES::new(),
+            region,
+            track: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            in_routine: false,
+            play_period: 0.0,
+            cycles_until_play: 0.0,
+            samples_rendered: 0,
+            nsf,
+        };
+        player.select_track(player.nsf.starting_song)?;
+
+        Ok(player)
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.nsf.track_count()
+    }
+
+    /// The track order, which NSFe files may override with a playlist
+    pub fn playlist(&self) -> Vec<u8> {
+        match &self.nsf.playlist {
+            Some(playlist) => playlist.clone(),
+            None => (0..self.track_count() as u8).collect(),
+        }
+    }
+
+    /// The currently selected track, 0-based
+    pub fn track(&self) -> u8 {
+        self.track
+    }
+
+    pub fn track_title(&self, track: u8) -> Option<&str> {
+        self.nsf.tracks.get(track as usize)?.title.as_deref()
+    }
+
+    pub fn track_duration(&self, track: u8) -> Option<Duration> {
+        self.nsf.tracks.get(track as usize)?.duration
+    }
+
+    pub fn track_fade(&self, track: u8) -> Option<Duration> {
+        self.nsf.tracks.get(track as usize)?.fade
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.nes.set_sample_rate(sample_rate);
+    }
+
+    /// How long the current track has been playing
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.samples_rendered as f64 / self.sample_rate as f64)
+    }
+
+    /// Resets the console and calls INIT for the given 0-based track
+    pub fn select_track(&mut self, track: u8) -> anyhow::Result<()> {
+        if track as usize >= self.track_count() {
+            bail!("Track {track} is out of range, the NSF has {} tracks", self.track_count());
+        }
+        info!("Playing track {track}: {}", self.track_title(track).unwrap_or("untitled"));
+
+        // A recording spans all tracks played
+        let recorder = self.nes.bus.recorder.take();
+        self.nes.bus = IOBus::new();
+        self.nes.bus.recorder = recorder;
+        self.nes.bus.set_region(self.region);
+        self.nes.set_sample_rate(self.sample_rate);
+        self.nes.bus.memory.insert_cartridge(Box::new(NSFMapper::new(&self.nsf)));
+
+        for addr in 0x4000..=0x4013 {
+            self.nes.bus.write(addr, 0u8)?;
+        }
+        self.nes.bus.write(0x4015, 0x00u8)?;
+        self.nes.bus.write(0x4015, 0x0Fu8)?;
+        self.nes.bus.write(0x4017, 0x40u8)?;
+
+        self.nes.bus.cpu.reset(RETURN_ADDR);
+        self.nes.bus.cpu.a = track;
+        self.nes.bus.cpu.x = (self.region == Region::PAL) as u8;
+
+        let speed = match self.region {
+            Region::PAL => self.nsf.pal_speed,
+            _ => self.nsf.ntsc_speed,
+        };
+        self.play_period = self.region.cpu_clock() as f64 * speed as f64 / 1_000_000.0;
+        self.cycles_until_play = self.play_period;
+        self.samples_rendered = 0;
+        self.track = track;
+
+        self.call(self.nsf.init_addr)
+    }
+
+    /// Pushes a return address like JSR does and jumps to the routine
+    fn call(&mut self, addr: u16) -> anyhow::Result<()> {
+        let return_addr = RETURN_ADDR.wrapping_sub(1);
+        let cpu = &mut self.nes.bus.cpu;
+
+        self.nes.bus.memory.write(cpu.stack_addr(), (return_addr >> 8) as u8)?;
+        cpu.sp = cpu.sp.wrapping_sub(1);
+        self.nes.bus.memory.write(cpu.stack_addr(), (return_addr & 0xFF) as u8)?;
+        cpu.sp = cpu.sp.wrapping_sub(1);
+
+        cpu.pc = addr;
+        self.in_routine = true;
+        Ok(())
+    }
+
+    /// Runs the CPU for one instruction while INIT or PLAY is running, otherwise idles for a cycle
+    fn step(&mut self) -> anyhow::Result<u16> {
+        let cycles = if self.in_routine {
+            let cycles = self.nes.execute()?;
+            if self.nes.bus.cpu.pc == RETURN_ADDR {
+                self.in_routine = false;
+            }
+            cycles
+        } else {
+            self.nes.bus.tick(1);
+            1
+        };
+
+        // PLAY isn't called until INIT returned, and is skipped by tunes driving themselves from INIT
+        self.cycles_until_play -= cycles as f64;
+        if self.cycles_until_play <= 0.0 && !self.in_routine && self.nsf.play_addr != 0 {
+            self.cycles_until_play = (self.cycles_until_play + self.play_period).max(0.0);
+            self.call(self.nsf.play_addr)?;
+        }
+
+        Ok(cycles)
+    }
+
+    /// Fills the buffer with mono samples in the range -1.0..=1.0 at the player's sample rate
+    pub fn render(&mut self, buffer: &mut [f32]) -> anyhow::Result<()> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            while self.nes.bus.apu.resampler.available() == 0 {
+                self.step()?;
+            }
+            // Samples that don't fit are kept for the next call
+            filled += self.nes.bus.apu.resampler.read_samples(&mut buffer[filled..]);
+        }
+
+        self.samples_rendered += buffer.len() as u64;
+        Ok(())
+    }
+}
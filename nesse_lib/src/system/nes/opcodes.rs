@@ -1,34 +1,21 @@
 use crate::system::nes::cpu::CPUFlagStruct;
 use crate::system::nes::NES;
+use crate::system::nes::mnemonics::{ABSOLUTE, ABSOLUTE_INDEXED_X, ABSOLUTE_INDEXED_Y, ACCUMULATOR, IMMEDIATE, ZERO_PAGE, ZERO_PAGE_INDEXED_INDIRECT, ZERO_PAGE_INDEXED_X, ZERO_PAGE_INDEXED_Y, ZERO_PAGE_INDIRECT_INDEXED_Y};
+use anyhow::bail;
 
 macro_rules! update_register {
     ($nes:ident, $field:ident, $value:expr) => {
         $nes.bus.cpu.$field = $value;
         $nes.bus.cpu.set_zero($value == 0);
-        $nes.bus.cpu.set_negative($value == 0);
+        $nes.bus.cpu.set_negative($value & 0x80 != 0);
     }
 }
 
 // 0x00
 pub fn brk_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
-    let pc_high = (nes.bus.cpu.pc >> 8) as u8;
-    let pc_low  = (nes.bus.cpu.pc & 0xFF) as u8;
-
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, pc_high)?;
-    nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, pc_low)?;
-    nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
-
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, nes.bus.cpu.p)?;
-    nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
-
-    nes.bus.cpu.flags &= CPUFlagStruct::Break;
-    nes.bus.cpu.flags &= CPUFlagStruct::InterruptDisable;
-
-    // Read new PC from 0xFFFE (little-endian)
-    let new_pc = nes.bus.memory.read(0xFFFE)?;
-    nes.bus.cpu.pc = new_pc;
-    Ok(())
+    // PC already points past the opcode, BRK pushes the address after its padding byte
+    nes.bus.cpu.pc = nes.bus.cpu.pc.wrapping_add(1);
+    nes.interrupt(0xFFFE, true)
 }
 
 pub fn lda_immediate(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
@@ -38,13 +25,13 @@ pub fn lda_immediate(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()>
 
 pub fn sta_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
-    nes.bus.memory.write(addr, nes.bus.cpu.a)?;
+    nes.bus.write(addr, nes.bus.cpu.a)?;
     Ok(())
 }
 
 pub fn lda_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
-    let value = nes.bus.memory.read(addr)?;
+    let value = nes.bus.read(addr)?;
     update_register!(nes, a, value);
     Ok(())
 }
@@ -80,7 +67,8 @@ pub fn ldx_immediate(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()>
 }
 
 pub fn txs_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
-    update_register!(nes, sp, nes.bus.cpu.x);
+    // TXS is the only transfer that leaves the flags alone
+    nes.bus.cpu.sp = nes.bus.cpu.x;
     Ok(())
 }
 
@@ -110,44 +98,36 @@ pub fn bne_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()>
 
 pub fn lda_absolute_ix(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
-    let value = nes.bus.memory.read::<u8>(addr)?.wrapping_add(nes.bus.cpu.x);
+    let value = nes.bus.read::<u8>(addr.wrapping_add(nes.bus.cpu.x as u16))?;
     update_register!(nes, a, value);
     Ok(())
 }
 
 pub fn cmp_absolute_ix(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
-    let value = nes.bus.memory.read::<u8>(addr)?.wrapping_add(nes.bus.cpu.x);
-
-    let other_value = nes.bus.cpu.a;
-    let subtraction = (value as i16) - (other_value as i16);
-
-    nes.bus.cpu.set_carry(value >= other_value);
-    nes.bus.cpu.set_zero(subtraction == 0);
-    nes.bus.cpu.set_negative((subtraction as i8) < 0);
+    let value = nes.bus.read::<u8>(addr.wrapping_add(nes.bus.cpu.x as u16))?;
+    cmp(nes, value);
     Ok(())
 }
 
 pub fn sta_absolute_ix(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
-    nes.bus.memory.write(addr.wrapping_add(nes.bus.cpu.x as u16), nes.bus.cpu.a)?;
+    nes.bus.write(addr.wrapping_add(nes.bus.cpu.x as u16), nes.bus.cpu.a)?;
     Ok(())
 }
 
 pub fn jsr_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = (byte2 as u16) << 8 | (byte1 as u16);
 
-    // For a typical 6502 JSR, we push the address-1 of the last byte of the JSR instruction.
-    // But your C++ code had something like: `uint16_t returnAddr = ioBus->mCPU.PC - 2;`
-    // We'll replicate that directly.
-    let return_addr = nes.bus.cpu.pc.wrapping_sub(2);
+    // JSR pushes the address of its last byte, PC already points to the next instruction
+    let return_addr = nes.bus.cpu.pc.wrapping_sub(1);
 
     let hi = (return_addr >> 8) as u8;
     let lo = (return_addr & 0xFF) as u8;
 
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, hi)?;
+    nes.bus.write(nes.bus.cpu.stack_addr(), hi)?;
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, lo)?;
+    nes.bus.write(nes.bus.cpu.stack_addr(), lo)?;
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
 
     nes.bus.cpu.pc = addr;
@@ -158,10 +138,9 @@ pub fn nop_immediate(_nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<(
     Ok(())
 }
 
-pub fn ora_indirect_y(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
-    let addr_base = nes.bus.memory.read::<u8>(byte1 as u16)? as u16;
-    let addr = addr_base.wrapping_add(nes.bus.cpu.y as u16);
-    let value = nes.bus.memory.read::<u8>(addr)?;
+pub fn ora_indirect_y(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+    let addr = operand_address(nes, ZERO_PAGE_INDIRECT_INDEXED_Y, byte1, byte2)?;
+    let value = nes.bus.read::<u8>(addr)?;
 
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
@@ -179,11 +158,11 @@ pub fn ora_indirect_x(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()
     let zp_addr = (byte1.wrapping_add(nes.bus.cpu.x)) as u16;
 
     // Low and high bytes of the target address
-    let low: u16 = nes.bus.memory.read(zp_addr)?;
-    let high: u16 = nes.bus.memory.read((zp_addr.wrapping_add(1)) & 0xFF)?;
+    let low: u16 = nes.bus.read(zp_addr)?;
+    let high: u16 = nes.bus.read((zp_addr.wrapping_add(1)) & 0xFF)?;
 
     let final_addr = ((high) << 8) | (low);
-    let value: u8 = nes.bus.memory.read(final_addr)?;
+    let value: u8 = nes.bus.read(final_addr)?;
 
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
@@ -196,7 +175,7 @@ pub fn ora_indirect_x(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()
 /// ORA Zero Page - opcode 0x05
 pub fn ora_zero_page(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     let addr = byte1 as u16;
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
 
@@ -208,7 +187,7 @@ pub fn ora_zero_page(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()>
 /// ORA Absolute - opcode 0x0D
 pub fn ora_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = ((byte2 as u16) << 8) | (byte1 as u16);
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
 
@@ -220,7 +199,7 @@ pub fn ora_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
 /// ORA Zero Page,X - opcode 0x15
 pub fn ora_zp_x(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     let addr = (byte1.wrapping_add(nes.bus.cpu.x)) as u16;
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
 
@@ -233,7 +212,7 @@ pub fn ora_zp_x(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
 pub fn ora_absolute_y(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let base_addr = ((byte2 as u16) << 8) | (byte1 as u16);
     let addr = base_addr.wrapping_add(nes.bus.cpu.y as u16);
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
 
@@ -246,7 +225,7 @@ pub fn ora_absolute_y(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()>
 pub fn ora_absolute_x(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let base_addr = ((byte2 as u16) << 8) | (byte1 as u16);
     let addr = base_addr.wrapping_add(nes.bus.cpu.x as u16);
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a | value;
     nes.bus.cpu.a = result;
 
@@ -260,11 +239,11 @@ pub fn ora_absolute_x(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()>
 /// ASL Zero Page - opcode 0x06
 pub fn asl_zero_page(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     let addr = byte1 as u16;
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let carry = (value & 0x80) != 0;
 
     let shifted = value << 1;
-    nes.bus.memory.write(addr, shifted)?;
+    nes.bus.write(addr, shifted)?;
 
     nes.bus.cpu.set_carry(carry);
     nes.bus.cpu.set_zero(shifted == 0);
@@ -286,11 +265,11 @@ pub fn asl_accumulator(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<
 /// ASL Absolute - opcode 0x0E
 pub fn asl_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = ((byte2 as u16) << 8) | (byte1 as u16);
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let carry = (value & 0x80) != 0;
 
     let shifted = value << 1;
-    nes.bus.memory.write(addr, shifted)?;
+    nes.bus.write(addr, shifted)?;
 
     nes.bus.cpu.set_carry(carry);
     nes.bus.cpu.set_zero(shifted == 0);
@@ -303,11 +282,11 @@ pub fn asl_absolute_x(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()>
     let base = ((byte2 as u16) << 8) | (byte1 as u16);
     let addr = base.wrapping_add(nes.bus.cpu.x as u16);
 
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let carry = (value & 0x80) != 0;
 
     let shifted = value << 1;
-    nes.bus.memory.write(addr, shifted)?;
+    nes.bus.write(addr, shifted)?;
 
     nes.bus.cpu.set_carry(carry);
     nes.bus.cpu.set_zero(shifted == 0);
@@ -319,8 +298,8 @@ pub fn asl_absolute_x(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()>
 
 /// PHP (Push Processor Status) - opcode 0x08
 pub fn php_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
-    let p = nes.bus.cpu.p; // Processor status
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, p)?;
+    let p = nes.bus.cpu.status(true); // Processor status
+    nes.bus.write(nes.bus.cpu.stack_addr(), p)?;
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
     Ok(())
 }
@@ -328,15 +307,15 @@ pub fn php_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()>
 /// PLP (Pull Processor Status) - opcode 0x28
 pub fn plp_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let new_p = nes.bus.memory.read(nes.bus.cpu.sp as u16)?;
-    nes.bus.cpu.p = new_p;
+    let new_p = nes.bus.read(nes.bus.cpu.stack_addr())?;
+    nes.bus.cpu.set_status(new_p);
     Ok(())
 }
 
 /// PHA (Push A) - opcode 0x48
 pub fn pha_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     let a = nes.bus.cpu.a;
-    nes.bus.memory.write(nes.bus.cpu.sp as u16, a)?;
+    nes.bus.write(nes.bus.cpu.stack_addr(), a)?;
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_sub(1);
     Ok(())
 }
@@ -344,7 +323,7 @@ pub fn pha_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()>
 /// PLA (Pull A) - opcode 0x68
 pub fn pla_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let value = nes.bus.memory.read(nes.bus.cpu.sp as u16)?;
+    let value = nes.bus.read(nes.bus.cpu.stack_addr())?;
     update_register!(nes, a, value);
     Ok(())
 }
@@ -363,12 +342,18 @@ pub fn sec_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()>
     Ok(())
 }
 
+/// CLI (Clear Interrupt Disable) - opcode 0x58
+pub fn cli_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    nes.bus.cpu.set_interrupt_disable(false);
+    Ok(())
+}
+
 // ----------------------------------------------------------
 
 /// BIT Zero Page - opcode 0x24
 pub fn bit_zero_page(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     let addr = byte1 as u16;
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a & value;
 
     nes.bus.cpu.set_zero(result == 0);
@@ -381,7 +366,7 @@ pub fn bit_zero_page(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()>
 /// BIT Absolute - opcode 0x2C
 pub fn bit_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
     let addr = ((byte2 as u16) << 8) | (byte1 as u16);
-    let value: u8 = nes.bus.memory.read(addr)?;
+    let value: u8 = nes.bus.read(addr)?;
     let result = nes.bus.cpu.a & value;
 
     nes.bus.cpu.set_zero(result == 0);
@@ -397,16 +382,16 @@ pub fn bit_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
 pub fn rti_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     // Pull status
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let new_p = nes.bus.memory.read(nes.bus.cpu.sp as u16)?;
-    nes.bus.cpu.p = new_p;
+    let new_p = nes.bus.read(nes.bus.cpu.stack_addr())?;
+    nes.bus.cpu.set_status(new_p);
 
     // Pull low PC
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let pcl: u16 = nes.bus.memory.read::<u8>(nes.bus.cpu.sp as u16)? as u16;
+    let pcl: u16 = nes.bus.read::<u8>(nes.bus.cpu.stack_addr())? as u16;
 
     // Pull high PC
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let pch: u16 = nes.bus.memory.read::<u8>(nes.bus.cpu.sp as u16)? as u16;
+    let pch: u16 = nes.bus.read::<u8>(nes.bus.cpu.stack_addr())? as u16;
 
     nes.bus.cpu.pc = (pch << 8) | pcl;
     Ok(())
@@ -417,11 +402,11 @@ pub fn rti_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()>
 pub fn rts_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     // Pull low PC
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let pcl = nes.bus.memory.read::<u8>(nes.bus.cpu.sp as u16)? as u16;
+    let pcl = nes.bus.read::<u8>(nes.bus.cpu.stack_addr())? as u16;
 
     // Pull high PC
     nes.bus.cpu.sp = nes.bus.cpu.sp.wrapping_add(1);
-    let pch = nes.bus.memory.read::<u8>(nes.bus.cpu.sp as u16)? as u16;
+    let pch = nes.bus.read::<u8>(nes.bus.cpu.stack_addr())? as u16;
 
     nes.bus.cpu.pc = ((pch << 8) | pcl).wrapping_add(1);
     Ok(())
@@ -430,17 +415,390 @@ pub fn rts_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()>
 // 0xF0
 pub fn beq_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
     if nes.bus.cpu.flags.contains(CPUFlagStruct::Zero) {
-        nes.bus.cpu.pc = nes.bus.cpu.pc + byte1 as u16;
+        nes.bus.cpu.pc = nes.bus.cpu.pc.wrapping_add(byte1 as i8 as u16);
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------
+
+/// The address an instruction operates on, zero page indexing and pointers wrap within zero page
+fn operand_address(nes: &mut NES, mode: u8, byte1: u8, byte2: u8) -> anyhow::Result<u16> {
+    let absolute = (byte2 as u16) << 8 | (byte1 as u16);
+    Ok(match mode {
+        ZERO_PAGE => byte1 as u16,
+        ZERO_PAGE_INDEXED_X => byte1.wrapping_add(nes.bus.cpu.x) as u16,
+        ZERO_PAGE_INDEXED_Y => byte1.wrapping_add(nes.bus.cpu.y) as u16,
+        ABSOLUTE => absolute,
+        ABSOLUTE_INDEXED_X => absolute.wrapping_add(nes.bus.cpu.x as u16),
+        ABSOLUTE_INDEXED_Y => absolute.wrapping_add(nes.bus.cpu.y as u16),
+        ZERO_PAGE_INDEXED_INDIRECT => zero_page_pointer(nes, byte1.wrapping_add(nes.bus.cpu.x))?,
+        ZERO_PAGE_INDIRECT_INDEXED_Y => zero_page_pointer(nes, byte1)?.wrapping_add(nes.bus.cpu.y as u16),
+        _ => bail!("Addressing mode {} has no operand address", mode),
+    })
+}
+
+fn zero_page_pointer(nes: &mut NES, addr: u8) -> anyhow::Result<u16> {
+    let low: u8 = nes.bus.read(addr as u16)?;
+    let high: u8 = nes.bus.read(addr.wrapping_add(1) as u16)?;
+    Ok(u16::from_le_bytes([low, high]))
+}
+
+fn operand(nes: &mut NES, mode: u8, byte1: u8, byte2: u8) -> anyhow::Result<u8> {
+    if mode == IMMEDIATE {
+        return Ok(byte1);
     }
+    let addr = operand_address(nes, mode, byte1, byte2)?;
+    nes.bus.read(addr)
+}
+
+/// Instructions which only read their operand
+macro_rules! read_instruction {
+    ($name:ident, $mode:expr, $operation:ident) => {
+        pub fn $name(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+            let value = operand(nes, $mode, byte1, byte2)?;
+            $operation(nes, value);
+            Ok(())
+        }
+    };
+}
+
+macro_rules! store_instruction {
+    ($name:ident, $mode:expr, $register:ident) => {
+        pub fn $name(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+            let addr = operand_address(nes, $mode, byte1, byte2)?;
+            nes.bus.write(addr, nes.bus.cpu.$register)
+        }
+    };
+}
+
+/// Read-modify-write instructions, on A or in memory
+macro_rules! modify_instruction {
+    ($name:ident, $mode:expr, $operation:ident) => {
+        pub fn $name(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+            if $mode == ACCUMULATOR {
+                let a = nes.bus.cpu.a;
+                nes.bus.cpu.a = $operation(nes, a);
+                return Ok(());
+            }
+            let addr = operand_address(nes, $mode, byte1, byte2)?;
+            let value: u8 = nes.bus.read(addr)?;
+            let result = $operation(nes, value);
+            nes.bus.write(addr, result)
+        }
+    };
+}
+
+fn set_zero_negative(nes: &mut NES, value: u8) {
+    nes.bus.cpu.set_zero(value == 0);
+    nes.bus.cpu.set_negative(value & 0x80 != 0);
+}
+
+fn and(nes: &mut NES, value: u8) {
+    let result = nes.bus.cpu.a & value;
+    update_register!(nes, a, result);
+}
+
+fn ora(nes: &mut NES, value: u8) {
+    let result = nes.bus.cpu.a | value;
+    update_register!(nes, a, result);
+}
+
+fn eor(nes: &mut NES, value: u8) {
+    let result = nes.bus.cpu.a ^ value;
+    update_register!(nes, a, result);
+}
+
+/// The 2A03 has no decimal mode, ADC and SBC are always binary
+fn adc(nes: &mut NES, value: u8) {
+    let a = nes.bus.cpu.a;
+    let sum = a as u16 + value as u16 + nes.bus.cpu.carry() as u16;
+    let result = sum as u8;
+
+    nes.bus.cpu.set_carry(sum > 0xFF);
+    // Overflow when both inputs have the same sign and the result doesn't
+    nes.bus.cpu.set_overflow((a ^ result) & (value ^ result) & 0x80 != 0);
+    update_register!(nes, a, result);
+}
+
+fn sbc(nes: &mut NES, value: u8) {
+    adc(nes, !value);
+}
+
+fn lda(nes: &mut NES, value: u8) {
+    update_register!(nes, a, value);
+}
+
+fn ldx(nes: &mut NES, value: u8) {
+    update_register!(nes, x, value);
+}
+
+fn ldy(nes: &mut NES, value: u8) {
+    update_register!(nes, y, value);
+}
+
+fn compare(nes: &mut NES, register: u8, value: u8) {
+    nes.bus.cpu.set_carry(register >= value);
+    set_zero_negative(nes, register.wrapping_sub(value));
+}
+
+fn cmp(nes: &mut NES, value: u8) {
+    let a = nes.bus.cpu.a;
+    compare(nes, a, value);
+}
+
+fn cpx(nes: &mut NES, value: u8) {
+    let x = nes.bus.cpu.x;
+    compare(nes, x, value);
+}
+
+fn cpy(nes: &mut NES, value: u8) {
+    let y = nes.bus.cpu.y;
+    compare(nes, y, value);
+}
+
+fn asl(nes: &mut NES, value: u8) -> u8 {
+    let result = value << 1;
+    nes.bus.cpu.set_carry(value & 0x80 != 0);
+    set_zero_negative(nes, result);
+    result
+}
+
+fn lsr(nes: &mut NES, value: u8) -> u8 {
+    let result = value >> 1;
+    nes.bus.cpu.set_carry(value & 0x01 != 0);
+    set_zero_negative(nes, result);
+    result
+}
+
+fn rol(nes: &mut NES, value: u8) -> u8 {
+    let result = value << 1 | nes.bus.cpu.carry() as u8;
+    nes.bus.cpu.set_carry(value & 0x80 != 0);
+    set_zero_negative(nes, result);
+    result
+}
+
+fn ror(nes: &mut NES, value: u8) -> u8 {
+    let result = value >> 1 | (nes.bus.cpu.carry() as u8) << 7;
+    nes.bus.cpu.set_carry(value & 0x01 != 0);
+    set_zero_negative(nes, result);
+    result
+}
+
+fn inc(nes: &mut NES, value: u8) -> u8 {
+    let result = value.wrapping_add(1);
+    set_zero_negative(nes, result);
+    result
+}
+
+fn dec(nes: &mut NES, value: u8) -> u8 {
+    let result = value.wrapping_sub(1);
+    set_zero_negative(nes, result);
+    result
+}
+
+read_instruction!(ora_immediate, IMMEDIATE, ora);
+
+read_instruction!(and_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, and);
+read_instruction!(and_zero_page, ZERO_PAGE, and);
+read_instruction!(and_immediate, IMMEDIATE, and);
+read_instruction!(and_absolute, ABSOLUTE, and);
+read_instruction!(and_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, and);
+read_instruction!(and_zp_x, ZERO_PAGE_INDEXED_X, and);
+read_instruction!(and_absolute_y, ABSOLUTE_INDEXED_Y, and);
+read_instruction!(and_absolute_x, ABSOLUTE_INDEXED_X, and);
+
+read_instruction!(eor_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, eor);
+read_instruction!(eor_zero_page, ZERO_PAGE, eor);
+read_instruction!(eor_immediate, IMMEDIATE, eor);
+read_instruction!(eor_absolute, ABSOLUTE, eor);
+read_instruction!(eor_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, eor);
+read_instruction!(eor_zp_x, ZERO_PAGE_INDEXED_X, eor);
+read_instruction!(eor_absolute_y, ABSOLUTE_INDEXED_Y, eor);
+read_instruction!(eor_absolute_x, ABSOLUTE_INDEXED_X, eor);
+
+read_instruction!(adc_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, adc);
+read_instruction!(adc_zero_page, ZERO_PAGE, adc);
+read_instruction!(adc_immediate, IMMEDIATE, adc);
+read_instruction!(adc_absolute, ABSOLUTE, adc);
+read_instruction!(adc_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, adc);
+read_instruction!(adc_zp_x, ZERO_PAGE_INDEXED_X, adc);
+read_instruction!(adc_absolute_y, ABSOLUTE_INDEXED_Y, adc);
+read_instruction!(adc_absolute_x, ABSOLUTE_INDEXED_X, adc);
+
+read_instruction!(sbc_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, sbc);
+read_instruction!(sbc_zero_page, ZERO_PAGE, sbc);
+read_instruction!(sbc_immediate, IMMEDIATE, sbc);
+read_instruction!(sbc_absolute, ABSOLUTE, sbc);
+read_instruction!(sbc_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, sbc);
+read_instruction!(sbc_zp_x, ZERO_PAGE_INDEXED_X, sbc);
+read_instruction!(sbc_absolute_y, ABSOLUTE_INDEXED_Y, sbc);
+read_instruction!(sbc_absolute_x, ABSOLUTE_INDEXED_X, sbc);
+
+read_instruction!(cmp_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, cmp);
+read_instruction!(cmp_zero_page, ZERO_PAGE, cmp);
+read_instruction!(cmp_immediate, IMMEDIATE, cmp);
+read_instruction!(cmp_absolute, ABSOLUTE, cmp);
+read_instruction!(cmp_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, cmp);
+read_instruction!(cmp_zp_x, ZERO_PAGE_INDEXED_X, cmp);
+read_instruction!(cmp_absolute_y, ABSOLUTE_INDEXED_Y, cmp);
+
+read_instruction!(cpx_immediate, IMMEDIATE, cpx);
+read_instruction!(cpx_zero_page, ZERO_PAGE, cpx);
+read_instruction!(cpx_absolute, ABSOLUTE, cpx);
+
+read_instruction!(cpy_immediate, IMMEDIATE, cpy);
+read_instruction!(cpy_zero_page, ZERO_PAGE, cpy);
+read_instruction!(cpy_absolute, ABSOLUTE, cpy);
+
+read_instruction!(lda_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, lda);
+read_instruction!(lda_zero_page, ZERO_PAGE, lda);
+read_instruction!(lda_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, lda);
+read_instruction!(lda_zp_x, ZERO_PAGE_INDEXED_X, lda);
+read_instruction!(lda_absolute_y, ABSOLUTE_INDEXED_Y, lda);
+
+read_instruction!(ldx_zero_page, ZERO_PAGE, ldx);
+read_instruction!(ldx_absolute, ABSOLUTE, ldx);
+read_instruction!(ldx_zp_y, ZERO_PAGE_INDEXED_Y, ldx);
+read_instruction!(ldx_absolute_y, ABSOLUTE_INDEXED_Y, ldx);
+
+read_instruction!(ldy_immediate, IMMEDIATE, ldy);
+read_instruction!(ldy_zero_page, ZERO_PAGE, ldy);
+read_instruction!(ldy_absolute, ABSOLUTE, ldy);
+read_instruction!(ldy_zp_x, ZERO_PAGE_INDEXED_X, ldy);
+read_instruction!(ldy_absolute_x, ABSOLUTE_INDEXED_X, ldy);
+
+store_instruction!(sta_indirect_x, ZERO_PAGE_INDEXED_INDIRECT, a);
+store_instruction!(sta_zero_page, ZERO_PAGE, a);
+store_instruction!(sta_indirect_y, ZERO_PAGE_INDIRECT_INDEXED_Y, a);
+store_instruction!(sta_zp_x, ZERO_PAGE_INDEXED_X, a);
+store_instruction!(sta_absolute_y, ABSOLUTE_INDEXED_Y, a);
+
+store_instruction!(stx_zero_page, ZERO_PAGE, x);
+store_instruction!(stx_absolute, ABSOLUTE, x);
+store_instruction!(stx_zp_y, ZERO_PAGE_INDEXED_Y, x);
+
+store_instruction!(sty_zero_page, ZERO_PAGE, y);
+store_instruction!(sty_absolute, ABSOLUTE, y);
+store_instruction!(sty_zp_x, ZERO_PAGE_INDEXED_X, y);
+
+modify_instruction!(asl_zp_x, ZERO_PAGE_INDEXED_X, asl);
+
+modify_instruction!(lsr_accumulator, ACCUMULATOR, lsr);
+modify_instruction!(lsr_zero_page, ZERO_PAGE, lsr);
+modify_instruction!(lsr_absolute, ABSOLUTE, lsr);
+modify_instruction!(lsr_zp_x, ZERO_PAGE_INDEXED_X, lsr);
+modify_instruction!(lsr_absolute_x, ABSOLUTE_INDEXED_X, lsr);
+
+modify_instruction!(rol_accumulator, ACCUMULATOR, rol);
+modify_instruction!(rol_zero_page, ZERO_PAGE, rol);
+modify_instruction!(rol_absolute, ABSOLUTE, rol);
+modify_instruction!(rol_zp_x, ZERO_PAGE_INDEXED_X, rol);
+modify_instruction!(rol_absolute_x, ABSOLUTE_INDEXED_X, rol);
+
+modify_instruction!(ror_accumulator, ACCUMULATOR, ror);
+modify_instruction!(ror_zero_page, ZERO_PAGE, ror);
+modify_instruction!(ror_absolute, ABSOLUTE, ror);
+modify_instruction!(ror_zp_x, ZERO_PAGE_INDEXED_X, ror);
+modify_instruction!(ror_absolute_x, ABSOLUTE_INDEXED_X, ror);
+
+modify_instruction!(inc_zero_page, ZERO_PAGE, inc);
+modify_instruction!(inc_absolute, ABSOLUTE, inc);
+modify_instruction!(inc_zp_x, ZERO_PAGE_INDEXED_X, inc);
+modify_instruction!(inc_absolute_x, ABSOLUTE_INDEXED_X, inc);
+
+modify_instruction!(dec_zero_page, ZERO_PAGE, dec);
+modify_instruction!(dec_absolute, ABSOLUTE, dec);
+modify_instruction!(dec_zp_x, ZERO_PAGE_INDEXED_X, dec);
+modify_instruction!(dec_absolute_x, ABSOLUTE_INDEXED_X, dec);
+
+// ----------------------------------------------------------
+
+pub fn jmp_absolute(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+    nes.bus.cpu.pc = (byte2 as u16) << 8 | (byte1 as u16);
+    Ok(())
+}
+
+/// The pointer's high byte is read from the start of its page when the low byte is at $xxFF
+pub fn jmp_indirect(nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
+    let low: u8 = nes.bus.read((byte2 as u16) << 8 | (byte1 as u16))?;
+    let high: u8 = nes.bus.read((byte2 as u16) << 8 | (byte1.wrapping_add(1) as u16))?;
+    nes.bus.cpu.pc = u16::from_le_bytes([low, high]);
+    Ok(())
+}
+
+fn branch(nes: &mut NES, condition: bool, byte1: u8) -> anyhow::Result<()> {
+    if condition {
+        nes.bus.cpu.pc = nes.bus.cpu.pc.wrapping_add(byte1 as i8 as u16);
+    }
+    Ok(())
+}
+
+pub fn bvc_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    branch(nes, !nes.bus.cpu.overflow(), byte1)
+}
+
+pub fn bvs_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    branch(nes, nes.bus.cpu.overflow(), byte1)
+}
+
+pub fn bcc_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    branch(nes, !nes.bus.cpu.carry(), byte1)
+}
+
+pub fn bcs_relative(nes: &mut NES, byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    branch(nes, nes.bus.cpu.carry(), byte1)
+}
+
+pub fn clv_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    nes.bus.cpu.set_overflow(false);
+    Ok(())
+}
+
+/// Sets D, which the 2A03 ignores
+pub fn sed_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    nes.bus.cpu.set_decimal(true);
+    Ok(())
+}
+
+pub fn tax_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    update_register!(nes, x, nes.bus.cpu.a);
+    Ok(())
+}
+
+pub fn tay_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    update_register!(nes, y, nes.bus.cpu.a);
+    Ok(())
+}
+
+pub fn tya_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    update_register!(nes, a, nes.bus.cpu.y);
+    Ok(())
+}
+
+pub fn tsx_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    update_register!(nes, x, nes.bus.cpu.sp);
+    Ok(())
+}
+
+pub fn iny_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    let new_val = nes.bus.cpu.y.wrapping_add(1);
+    update_register!(nes, y, new_val);
+    Ok(())
+}
 
+pub fn dey_implied(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    let new_val = nes.bus.cpu.y.wrapping_sub(1);
+    update_register!(nes, y, new_val);
     Ok(())
 }
 
-pub fn ins_nullfunc(_nes: &mut NES, byte1: u8, byte2: u8) -> anyhow::Result<()> {
-    panic!(
-        "INVALID! Game called NULLFUNC with 0x{:02X} and 0x{:02X}",
-        byte1, byte2
-    );
+/// Unofficial opcodes which aren't emulated, PC already points past the opcode
+pub fn ins_nullfunc(nes: &mut NES, _byte1: u8, _byte2: u8) -> anyhow::Result<()> {
+    let addr = nes.bus.cpu.pc.wrapping_sub(1);
+    let opcode: u8 = nes.bus.peek(addr)?;
+    bail!("Unimplemented opcode 0x{:02X} at 0x{:04X}", opcode, addr)
 }
 
 // function and cycle amount
@@ -455,266 +813,266 @@ pub const OPCODES: [(fn(&mut NES, u8, u8) -> anyhow::Result<()>, u8); 256] = [
     (asl_zero_page, 5),		// 0x06;
     (ins_nullfunc, 0),		// 0x07
     (php_implied, 3),		// 0x08
-    (ins_nullfunc, 0),		// 0x09
+    (ora_immediate, 2),	// 0x09
     (asl_accumulator, 2),   // 0x0A
     (ins_nullfunc, 0),		// 0x0B
     (nop_immediate, 4),		// 0x0C
-    (ora_absolute, 2),		// 0x0D
-    (asl_absolute, 4),	    // 0x0E
+    (ora_absolute, 4),		// 0x0D
+    (asl_absolute, 6),	    // 0x0E
     (ins_nullfunc, 0),		// 0x0F
 ////////////// 10 /////////////
-    (bpl_relative, 1),		// 0x10
+    (bpl_relative, 2),		// 0x10
     (ora_indirect_y, 5),	// 0x11
     (ins_nullfunc, 0),		// 0x12
     (ins_nullfunc, 0),		// 0x13
     (nop_immediate, 4),		// 0x14
     (ora_zp_x, 4),		    // 0x15
-    (ins_nullfunc, 0),		// 0x16
+    (asl_zp_x, 6),	// 0x16
     (ins_nullfunc, 0),		// 0x17
     (clc_implied, 2),		// 0x18
     (ora_absolute_y, 4),	// 0x19
     (nop_immediate, 2),		// 0x1A
     (ins_nullfunc, 0),		// 0x1B
     (nop_immediate, 4),		// 0x1C
-    (ins_nullfunc, 0),		// 0x1D
-    (ins_nullfunc, 0),		// 0x1E
+    (ora_absolute_x, 4),	// 0x1D
+    (asl_absolute_x, 7),	// 0x1E
     (ins_nullfunc, 0),		// 0x1F
 ////////////// 20 /////////////
-    (jsr_absolute, 1),		// 0x20
-    (ins_nullfunc, 0),		// 0x21
+    (jsr_absolute, 6),		// 0x20
+    (and_indirect_x, 6),	// 0x21
     (ins_nullfunc, 0),		// 0x22
     (ins_nullfunc, 0),		// 0x23
     (bit_zero_page, 3),		// 0x24
-    (ins_nullfunc, 0),		// 0x25
-    (ins_nullfunc, 0),		// 0x26
+    (and_zero_page, 3),	// 0x25
+    (rol_zero_page, 5),	// 0x26
     (ins_nullfunc, 0),		// 0x27
     (plp_implied, 4),		// 0x28
-    (ins_nullfunc, 0),		// 0x29
-    (ins_nullfunc, 0),		// 0x2A
+    (and_immediate, 2),	// 0x29
+    (rol_accumulator, 2),	// 0x2A
     (ins_nullfunc, 0),		// 0x2B
     (bit_absolute, 4),		// 0x2C
-    (ins_nullfunc, 0),		// 0x2D
-    (ins_nullfunc, 0),		// 0x2E
+    (and_absolute, 4),	// 0x2D
+    (rol_absolute, 6),	// 0x2E
     (ins_nullfunc, 0),		// 0x2F
 ////////////// 30 /////////////
-    (bmi_relative, 1),		// 0x30
-    (ins_nullfunc, 0),		// 0x31
+    (bmi_relative, 2),		// 0x30
+    (and_indirect_y, 5),	// 0x31
     (ins_nullfunc, 0),		// 0x32
     (ins_nullfunc, 0),		// 0x33
     (nop_immediate, 4),		// 0x34
-    (ins_nullfunc, 0),		// 0x35
-    (ins_nullfunc, 0),		// 0x36
+    (and_zp_x, 4),	// 0x35
+    (rol_zp_x, 6),	// 0x36
     (ins_nullfunc, 0),		// 0x37
     (sec_implied, 2),		// 0x38
-    (ins_nullfunc, 0),		// 0x39
+    (and_absolute_y, 4),	// 0x39
     (nop_immediate, 2),		// 0x3A
     (ins_nullfunc, 0),		// 0x3B
     (nop_immediate, 4),		// 0x3C
-    (ins_nullfunc, 0),		// 0x3D
-    (ins_nullfunc, 0),		// 0x3E
+    (and_absolute_x, 4),	// 0x3D
+    (rol_absolute_x, 7),	// 0x3E
     (ins_nullfunc, 0),		// 0x3F
 ////////////// 40 /////////////
     (rti_implied, 6),		// 0x40
-    (ins_nullfunc, 0),		// 0x41
+    (eor_indirect_x, 6),	// 0x41
     (ins_nullfunc, 0),		// 0x42
     (ins_nullfunc, 0),		// 0x43
     (nop_immediate, 3),		// 0x44
-    (ins_nullfunc, 0),		// 0x45
-    (ins_nullfunc, 0),		// 0x46
+    (eor_zero_page, 3),	// 0x45
+    (lsr_zero_page, 5),	// 0x46
     (ins_nullfunc, 0),		// 0x47
     (pha_implied, 3),		// 0x48
-    (ins_nullfunc, 0),		// 0x49
-    (ins_nullfunc, 0),		// 0x4A
+    (eor_immediate, 2),	// 0x49
+    (lsr_accumulator, 2),	// 0x4A
     (ins_nullfunc, 0),		// 0x4B
-    (ins_nullfunc, 0),		// 0x4C
-    (ins_nullfunc, 0),		// 0x4D
-    (ins_nullfunc, 0),		// 0x4E
+    (jmp_absolute, 3),	// 0x4C
+    (eor_absolute, 4),	// 0x4D
+    (lsr_absolute, 6),	// 0x4E
     (ins_nullfunc, 0),		// 0x4F
 ////////////// 50 /////////////
-    (ins_nullfunc, 1),		// 0x50
-    (ins_nullfunc, 0),		// 0x51
+    (bvc_relative, 2),	// 0x50
+    (eor_indirect_y, 5),	// 0x51
     (ins_nullfunc, 0),		// 0x52
     (ins_nullfunc, 0),		// 0x53
     (nop_immediate, 4),		// 0x54
-    (ins_nullfunc, 0),		// 0x55
-    (ins_nullfunc, 0),		// 0x56
+    (eor_zp_x, 4),	// 0x55
+    (lsr_zp_x, 6),	// 0x56
     (ins_nullfunc, 0),		// 0x57
-    (ins_nullfunc, 0),		// 0x58
-    (ins_nullfunc, 0),		// 0x59
+    (cli_implied, 2),		// 0x58
+    (eor_absolute_y, 4),	// 0x59
     (nop_immediate, 2),		// 0x5A
     (ins_nullfunc, 0),		// 0x5B
     (nop_immediate, 4),		// 0x5C
-    (ins_nullfunc, 0),		// 0x5D
-    (ins_nullfunc, 0),		// 0x5E
+    (eor_absolute_x, 4),	// 0x5D
+    (lsr_absolute_x, 7),	// 0x5E
     (ins_nullfunc, 0),		// 0x5F
 ////////////// 60 /////////////
     (rts_implied, 6),		// 0x60
-    (ins_nullfunc, 0),		// 0x61
+    (adc_indirect_x, 6),	// 0x61
     (ins_nullfunc, 0),		// 0x62
     (ins_nullfunc, 0),		// 0x63
     (nop_immediate, 3),		// 0x64
-    (ins_nullfunc, 0),		// 0x65
-    (ins_nullfunc, 0),		// 0x66
+    (adc_zero_page, 3),	// 0x65
+    (ror_zero_page, 5),	// 0x66
     (ins_nullfunc, 0),		// 0x67
     (pla_implied, 4),		// 0x68
-    (ins_nullfunc, 0),		// 0x69
-    (ins_nullfunc, 0),		// 0x6A
+    (adc_immediate, 2),	// 0x69
+    (ror_accumulator, 2),	// 0x6A
     (ins_nullfunc, 0),		// 0x6B
-    (ins_nullfunc, 0),		// 0x6C
-    (ins_nullfunc, 0),		// 0x6D
-    (ins_nullfunc, 0),		// 0x6E
+    (jmp_indirect, 5),	// 0x6C
+    (adc_absolute, 4),	// 0x6D
+    (ror_absolute, 6),	// 0x6E
     (ins_nullfunc, 0),		// 0x6F
 ////////////// 70 /////////////
-    (ins_nullfunc, 1),		// 0x70
-    (ins_nullfunc, 0),		// 0x71
+    (bvs_relative, 2),	// 0x70
+    (adc_indirect_y, 5),	// 0x71
     (ins_nullfunc, 0),		// 0x72
     (ins_nullfunc, 0),		// 0x73
     (nop_immediate, 4),		// 0x74
-    (ins_nullfunc, 0),		// 0x75
-    (ins_nullfunc, 0),		// 0x76
+    (adc_zp_x, 4),	// 0x75
+    (ror_zp_x, 6),	// 0x76
     (ins_nullfunc, 0),		// 0x77
-    (sei_implied, 1),		// 0x78
-    (ins_nullfunc, 0),		// 0x79
+    (sei_implied, 2),		// 0x78
+    (adc_absolute_y, 4),	// 0x79
     (nop_immediate, 2),		// 0x7A
     (ins_nullfunc, 0),		// 0x7B
     (nop_immediate, 4),		// 0x7C
-    (ins_nullfunc, 0),		// 0x7D
-    (ins_nullfunc, 0),		// 0x7E
+    (adc_absolute_x, 4),	// 0x7D
+    (ror_absolute_x, 7),	// 0x7E
     (ins_nullfunc, 0),		// 0x7F
 ////////////// 80 /////////////
     (nop_immediate, 2),		// 0x80
-    (ins_nullfunc, 0),		// 0x81
+    (sta_indirect_x, 6),	// 0x81
     (nop_immediate, 2),		// 0x82
     (ins_nullfunc, 0),		// 0x83
-    (ins_nullfunc, 0),		// 0x84
-    (ins_nullfunc, 0),		// 0x85
-    (ins_nullfunc, 0),		// 0x86
+    (sty_zero_page, 3),	// 0x84
+    (sta_zero_page, 3),	// 0x85
+    (stx_zero_page, 3),	// 0x86
     (ins_nullfunc, 0),		// 0x87
-    (ins_nullfunc, 1),		// 0x88
+    (dey_implied, 2),	// 0x88
     (nop_immediate, 2),		// 0x89
-    (txa_implied, 1),		// 0x8A
+    (txa_implied, 2),		// 0x8A
     (ins_nullfunc, 0),		// 0x8B
-    (ins_nullfunc, 0),		// 0x8C
-    (sta_absolute, 1),		// 0x8D
-    (ins_nullfunc, 0),		// 0x8E
+    (sty_absolute, 4),	// 0x8C
+    (sta_absolute, 4),		// 0x8D
+    (stx_absolute, 4),	// 0x8E
     (ins_nullfunc, 0),		// 0x8F
 ////////////// 90 /////////////
-    (ins_nullfunc, 1),		// 0x90
-    (ins_nullfunc, 0),		// 0x91
+    (bcc_relative, 2),	// 0x90
+    (sta_indirect_y, 6),	// 0x91
     (ins_nullfunc, 0),		// 0x92
     (ins_nullfunc, 0),		// 0x93
-    (ins_nullfunc, 0),		// 0x94
-    (ins_nullfunc, 0),		// 0x95
-    (ins_nullfunc, 0),		// 0x96
+    (sty_zp_x, 4),	// 0x94
+    (sta_zp_x, 4),	// 0x95
+    (stx_zp_y, 4),	// 0x96
     (ins_nullfunc, 0),		// 0x97
-    (ins_nullfunc, 1),		// 0x98
-    (ins_nullfunc, 0),		// 0x99
-    (txs_implied, 1),		// 0x9A
+    (tya_implied, 2),	// 0x98
+    (sta_absolute_y, 5),	// 0x99
+    (txs_implied, 2),		// 0x9A
     (ins_nullfunc, 0),		// 0x9B
     (ins_nullfunc, 0),		// 0x9C
-    (sta_absolute_ix, 1),	// 0x9D
+    (sta_absolute_ix, 5),	// 0x9D
     (ins_nullfunc, 0),		// 0x9E
     (ins_nullfunc, 0),		// 0x9F
 ////////////// A0 /////////////
-    (ins_nullfunc, 0),		// 0xA0
-    (ins_nullfunc, 0),		// 0xA1
-    (ldx_immediate, 1),		// 0xA2
+    (ldy_immediate, 2),	// 0xA0
+    (lda_indirect_x, 6),	// 0xA1
+    (ldx_immediate, 2),		// 0xA2
     (ins_nullfunc, 0),		// 0xA3
-    (ins_nullfunc, 0),		// 0xA4
-    (ins_nullfunc, 0),		// 0xA5
-    (ins_nullfunc, 0),		// 0xA6
+    (ldy_zero_page, 3),	// 0xA4
+    (lda_zero_page, 3),	// 0xA5
+    (ldx_zero_page, 3),	// 0xA6
     (ins_nullfunc, 0),		// 0xA7
-    (ins_nullfunc, 0),		// 0xA8
-    (lda_immediate, 1),		// 0xA9
-    (ins_nullfunc, 0),		// 0xAA
+    (tay_implied, 2),	// 0xA8
+    (lda_immediate, 2),		// 0xA9
+    (tax_implied, 2),	// 0xAA
     (ins_nullfunc, 0),		// 0xAB
-    (ins_nullfunc, 0),		// 0xAC
-    (lda_absolute, 1),		// 0xAD
-    (ins_nullfunc, 0),		// 0xAE
+    (ldy_absolute, 4),	// 0xAC
+    (lda_absolute, 4),		// 0xAD
+    (ldx_absolute, 4),	// 0xAE
     (ins_nullfunc, 0),		// 0xAF
 ////////////// B0 /////////////
-    (ins_nullfunc, 0),		// 0xB0
-    (ins_nullfunc, 0),		// 0xB1
+    (bcs_relative, 2),	// 0xB0
+    (lda_indirect_y, 5),	// 0xB1
     (ins_nullfunc, 0),		// 0xB2
     (ins_nullfunc, 0),		// 0xB3
-    (ins_nullfunc, 0),		// 0xB4
-    (ins_nullfunc, 0),		// 0xB5
-    (ins_nullfunc, 0),		// 0xB6
+    (ldy_zp_x, 4),	// 0xB4
+    (lda_zp_x, 4),	// 0xB5
+    (ldx_zp_y, 4),	// 0xB6
     (ins_nullfunc, 0),		// 0xB7
-    (ins_nullfunc, 0),		// 0xB8
-    (ins_nullfunc, 0),		// 0xB9
-    (ins_nullfunc, 0),		// 0xBA
+    (clv_implied, 2),	// 0xB8
+    (lda_absolute_y, 4),	// 0xB9
+    (tsx_implied, 2),	// 0xBA
     (ins_nullfunc, 0),		// 0xBB
-    (ins_nullfunc, 0),		// 0xBC
-    (lda_absolute_ix, 1),	// 0xBD
-    (ins_nullfunc, 0),		// 0xBE
+    (ldy_absolute_x, 4),	// 0xBC
+    (lda_absolute_ix, 4),	// 0xBD
+    (ldx_absolute_y, 4),	// 0xBE
     (ins_nullfunc, 0),		// 0xBF
 ////////////// C0 /////////////
-    (ins_nullfunc, 0),		// 0xC0
-    (ins_nullfunc, 0),		// 0xC1
+    (cpy_immediate, 2),	// 0xC0
+    (cmp_indirect_x, 6),	// 0xC1
     (nop_immediate, 2),		// 0xC2
     (ins_nullfunc, 0),		// 0xC3
-    (ins_nullfunc, 0),		// 0xC4
-    (ins_nullfunc, 0),		// 0xC5
-    (ins_nullfunc, 0),		// 0xC6
+    (cpy_zero_page, 3),	// 0xC4
+    (cmp_zero_page, 3),	// 0xC5
+    (dec_zero_page, 5),	// 0xC6
     (ins_nullfunc, 0),		// 0xC7
-    (ins_nullfunc, 0),		// 0xC8
-    (ins_nullfunc, 0),		// 0xC9
-    (dex_implied, 1),		// 0xCA
+    (iny_implied, 2),	// 0xC8
+    (cmp_immediate, 2),	// 0xC9
+    (dex_implied, 2),		// 0xCA
     (ins_nullfunc, 0),		// 0xCB
-    (ins_nullfunc, 0),		// 0xCC
-    (ins_nullfunc, 0),		// 0xCD
-    (ins_nullfunc, 0),		// 0xCE
+    (cpy_absolute, 4),	// 0xCC
+    (cmp_absolute, 4),	// 0xCD
+    (dec_absolute, 6),	// 0xCE
     (ins_nullfunc, 0),		// 0xCF
 ////////////// D0 /////////////
-    (bne_relative, 1),		// 0xD0
-    (ins_nullfunc, 0),		// 0xD1
+    (bne_relative, 2),		// 0xD0
+    (cmp_indirect_y, 5),	// 0xD1
     (ins_nullfunc, 0),		// 0xD2
     (ins_nullfunc, 0),		// 0xD3
     (nop_immediate, 4),		// 0xD4
-    (ins_nullfunc, 0),		// 0xD5
-    (ins_nullfunc, 0),		// 0xD6
+    (cmp_zp_x, 4),	// 0xD5
+    (dec_zp_x, 6),	// 0xD6
     (ins_nullfunc, 0),		// 0xD7
-    (cld_implied, 1),		// 0xD8
-    (ins_nullfunc, 0),		// 0xD9
+    (cld_implied, 2),		// 0xD8
+    (cmp_absolute_y, 4),	// 0xD9
     (nop_immediate, 2),		// 0xDA
     (ins_nullfunc, 0),		// 0xDB
     (nop_immediate, 4),		// 0xDC
-    (cmp_absolute_ix, 1),	// 0xDD
-    (ins_nullfunc, 0),		// 0xDE
+    (cmp_absolute_ix, 4),	// 0xDD
+    (dec_absolute_x, 7),	// 0xDE
     (ins_nullfunc, 0),		// 0xDF
 ////////////// E0 /////////////
-    (ins_nullfunc, 0),		// 0xE0
-    (ins_nullfunc, 0),		// 0xE1
+    (cpx_immediate, 2),	// 0xE0
+    (sbc_indirect_x, 6),	// 0xE1
     (nop_immediate, 2),		// 0xE2
     (ins_nullfunc, 0),		// 0xE3
-    (ins_nullfunc, 0),		// 0xE4
-    (ins_nullfunc, 0),		// 0xE5
-    (ins_nullfunc, 0),		// 0xE6
+    (cpx_zero_page, 3),	// 0xE4
+    (sbc_zero_page, 3),	// 0xE5
+    (inc_zero_page, 5),	// 0xE6
     (ins_nullfunc, 0),		// 0xE7
-    (inx_implied, 1),		// 0xE8
-    (ins_nullfunc, 0),		// 0xE9
+    (inx_implied, 2),		// 0xE8
+    (sbc_immediate, 2),	// 0xE9
     (nop_immediate, 2),		// 0xEA
     (ins_nullfunc, 0),		// 0xEB
-    (ins_nullfunc, 0),		// 0xEC
-    (ins_nullfunc, 0),		// 0xED
-    (ins_nullfunc, 0),		// 0xEE
+    (cpx_absolute, 4),	// 0xEC
+    (sbc_absolute, 4),	// 0xED
+    (inc_absolute, 6),	// 0xEE
     (ins_nullfunc, 0),		// 0xEF
 ////////////// F0 /////////////
-    (beq_relative, 0),		// 0xF0
-    (ins_nullfunc, 0),		// 0xF1
+    (beq_relative, 2),		// 0xF0
+    (sbc_indirect_y, 5),	// 0xF1
     (ins_nullfunc, 0),		// 0xF2
     (ins_nullfunc, 0),		// 0xF3
     (nop_immediate, 4),		// 0xF4
-    (ins_nullfunc, 0),		// 0xF5
-    (ins_nullfunc, 0),		// 0xF6
+    (sbc_zp_x, 4),	// 0xF5
+    (inc_zp_x, 6),	// 0xF6
     (ins_nullfunc, 0),		// 0xF7
-    (ins_nullfunc, 0),		// 0xF8
-    (ins_nullfunc, 0),		// 0xF9
+    (sed_implied, 2),	// 0xF8
+    (sbc_absolute_y, 4),	// 0xF9
     (nop_immediate, 2),		// 0xFA
     (ins_nullfunc, 0),		// 0xFB
     (nop_immediate, 4),		// 0xFC
-    (ins_nullfunc, 0),		// 0xFD
-    (ins_nullfunc, 0),		// 0xFE
+    (sbc_absolute_x, 4),	// 0xFD
+    (inc_absolute_x, 7),	// 0xFE
     (ins_nullfunc, 0),		// 0xFF
 ];
\ No newline at end of file
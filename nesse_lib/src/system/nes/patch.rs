@@ -0,0 +1,359 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, format_err};
+use log::debug;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+/// Size of the source, target and patch CRC32 footer of UPS and BPS patches
+const CHECKSUM_FOOTER_SIZE: usize = 12;
+
+/// Largest image a UPS or BPS patch may produce, so a corrupt size can't exhaust memory
+const MAX_TARGET_SIZE: usize = 64 * 1024 * 1024;
+
+/// Extensions that are looked for next to a ROM when discovering patches, in order of preference
+pub const PATCH_EXTENSIONS: [&str; 3] = ["bps", "ups", "ips"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    IPS,
+    UPS,
+    BPS,
+}
+
+/// A soft-patch which is applied to the raw ROM image in memory, leaving the files on disk untouched.
+#[derive(Debug)]
+pub struct Patch {
+    pub format: PatchFormat,
+    data: Vec<u8>,
+}
+
+impl Patch {
+    pub fn new<P: AsRef<Path>>(patch_path: &P) -> anyhow::Result<Self> {
+        Self::from_bytes(fs::read(patch_path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        let format = if data.starts_with(IPS_MAGIC) {
+            PatchFormat::IPS
+        } else if data.starts_with(UPS_MAGIC) {
+            PatchFormat::UPS
+        } else if data.starts_with(BPS_MAGIC) {
+            PatchFormat::BPS
+        } else {
+            bail!("Unknown patch format");
+        };
+
+        Ok(Self { format, data })
+    }
+
+    /// Looks for a patch with the same file stem as the ROM in the ROM's directory
+    pub fn find_sibling<P: AsRef<Path>>(rom_path: &P) -> Option<PathBuf> {
+        let rom_path = rom_path.as_ref();
+        PATCH_EXTENSIONS
+            .iter()
+            .map(|ext| rom_path.with_extension(ext))
+            .find(|candidate| candidate != rom_path && candidate.is_file())
+    }
+
+    /// Creates an IPS patch turning `source` into `target`
+    pub fn create_ips(source: &[u8], target: &[u8]) -> Vec<u8> {
+        const MAX_RECORD_SIZE: usize = 0xFFFF;
+
+        let mut ips = IPS_MAGIC.to_vec();
+        let mut offset = 0;
+        while offset < target.len() {
+            if source.get(offset) == Some(&target[offset]) {
+                offset += 1;
+                continue;
+            }
+
+            let mut end = offset;
+            while end < target.len() && end - offset < MAX_RECORD_SIZE && source.get(end) != Some(&target[end]) {
+                end += 1;
+            }
+
+            ips.extend_from_slice(&(offset as u32).to_be_bytes()[1..]);
+            ips.extend_from_slice(&((end - offset) as u16).to_be_bytes());
+            ips.extend_from_slice(&target[offset..end]);
+            offset = end;
+        }
+        ips.extend_from_slice(IPS_EOF);
+
+        if target.len() < source.len() {
+            ips.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+        }
+
+        ips
+    }
+
+    pub fn apply(&self, source: &[u8]) -> anyhow::Result<Vec<u8>> {
+        debug!("Applying {:?} patch to {} bytes", self.format, source.len());
+
+        match self.format {
+            PatchFormat::IPS => self.apply_ips(source),
+            PatchFormat::UPS => self.apply_ups(source),
+            PatchFormat::BPS => self.apply_bps(source),
+        }
+    }
+
+    fn apply_ips(&self, source: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut reader = PatchReader::new(&self.data, IPS_MAGIC.len());
+        let mut target = source.to_vec();
+
+        loop {
+            if reader.remaining().starts_with(IPS_EOF) {
+                reader.skip(IPS_EOF.len());
+                break;
+            }
+
+            let offset = reader.read_be(3)?;
+            let size = reader.read_be(2)?;
+
+            if size == 0 {
+                // RLE record: a 16-bit run length followed by the byte to repeat
+                let run_length = reader.read_be(2)?;
+                let value = reader.read_u8()?;
+                write_at(&mut target, offset, &vec![value; run_length]);
+            } else {
+                let bytes = reader.read_slice(size)?;
+                write_at(&mut target, offset, bytes);
+            }
+        }
+
+        // Lunar IPS extension: an optional 24-bit size to truncate the output to
+        if reader.remaining().len() >= 3 {
+            let truncated_size = reader.read_be(3)?;
+            target.truncate(truncated_size);
+        }
+
+        Ok(target)
+    }
+
+    fn apply_ups(&self, source: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let (source_crc, target_crc) = self.verify_checksums(source)?;
+
+        let mut reader = PatchReader::new(&self.data[..self.data.len() - CHECKSUM_FOOTER_SIZE], UPS_MAGIC.len());
+        let source_size = reader.read_varint()?;
+        let target_size = reader.read_varint()?;
+        if source_size != source.len() {
+            bail!("UPS patch expects a source of {source_size} bytes, got {}", source.len());
+        }
+        check_target_size(target_size)?;
+
+        let source_at = |offset: usize| source.get(offset).copied().unwrap_or(0);
+        let mut target = vec![0u8; target_size];
+        let copied = source_size.min(target_size);
+        target[..copied].copy_from_slice(&source[..copied]);
+
+        let mut offset: usize = 0;
+        while !reader.is_empty() {
+            offset = offset
+                .checked_add(reader.read_varint()?)
+                .filter(|&offset| offset <= target_size)
+                .ok_or_else(|| format_err!("UPS hunk past the end of the target"))?;
+            loop {
+                let xor = reader.read_u8()?;
+                if offset < target_size {
+                    target[offset] = source_at(offset) ^ xor;
+                }
+                offset += 1;
+                if xor == 0 {
+                    break;
+                }
+            }
+        }
+
+        verify_target(&target, source_crc, target_crc)?;
+        Ok(target)
+    }
+
+    fn apply_bps(&self, source: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let (source_crc, target_crc) = self.verify_checksums(source)?;
+
+        let mut reader = PatchReader::new(&self.data[..self.data.len() - CHECKSUM_FOOTER_SIZE], BPS_MAGIC.len());
+        let source_size = reader.read_varint()?;
+        let target_size = reader.read_varint()?;
+        let metadata_size = reader.read_varint()?;
+        reader.skip(metadata_size);
+        if source_size != source.len() {
+            bail!("BPS patch expects a source of {source_size} bytes, got {}", source.len());
+        }
+        check_target_size(target_size)?;
+
+        let mut target = Vec::with_capacity(target_size);
+        let mut source_offset: usize = 0;
+        let mut target_offset: usize = 0;
+
+        while !reader.is_empty() {
+            let action = reader.read_varint()?;
+            let length = (action >> 2) + 1;
+            if target.len().checked_add(length).is_none_or(|end| end > target_size) {
+                bail!("BPS action at 0x{:X} writes past the end of the target", target.len());
+            }
+
+            match action & 0b11 {
+                // SourceRead
+                0 => {
+                    let start = target.len();
+                    let bytes = start
+                        .checked_add(length)
+                        .and_then(|end| source.get(start..end))
+                        .ok_or_else(|| format_err!("BPS SourceRead out of bounds at 0x{start:X}"))?;
+                    target.extend_from_slice(bytes);
+                }
+                // TargetRead
+                1 => target.extend_from_slice(reader.read_slice(length)?),
+                // SourceCopy
+                2 => {
+                    source_offset = apply_relative(source_offset, reader.read_varint()?)?;
+                    let bytes = source_offset
+                        .checked_add(length)
+                        .and_then(|end| source.get(source_offset..end))
+                        .ok_or_else(|| format_err!("BPS SourceCopy out of bounds at 0x{source_offset:X}"))?;
+                    target.extend_from_slice(bytes);
+                    source_offset += length;
+                }
+                // TargetCopy, which may overlap with the bytes it produces
+                _ => {
+                    target_offset = apply_relative(target_offset, reader.read_varint()?)?;
+                    for _ in 0..length {
+                        let byte = *target
+                            .get(target_offset)
+                            .ok_or_else(|| format_err!("BPS TargetCopy out of bounds at 0x{target_offset:X}"))?;
+                        target.push(byte);
+                        target_offset += 1;
+                    }
+                }
+            }
+        }
+
+        if target.len() != target_size {
+            bail!("BPS patch produced {} bytes, expected {target_size}", target.len());
+        }
+
+        verify_target(&target, source_crc, target_crc)?;
+        Ok(target)
+    }
+
+    /// Validates the patch and source CRC32s of UPS/BPS patches, returning the expected source and target CRC32s
+    fn verify_checksums(&self, source: &[u8]) -> anyhow::Result<(u32, u32)> {
+        if self.data.len() < 4 + CHECKSUM_FOOTER_SIZE {
+            bail!("{:?} patch is truncated", self.format);
+        }
+
+        let footer = &self.data[self.data.len() - CHECKSUM_FOOTER_SIZE..];
+        let source_crc = u32::from_le_bytes(footer[0..4].try_into()?);
+        let target_crc = u32::from_le_bytes(footer[4..8].try_into()?);
+        let patch_crc = u32::from_le_bytes(footer[8..12].try_into()?);
+
+        let actual_patch_crc = crc32fast::hash(&self.data[..self.data.len() - 4]);
+        if actual_patch_crc != patch_crc {
+            bail!("{:?} patch is corrupt: CRC32 0x{actual_patch_crc:08X} does not match 0x{patch_crc:08X}", self.format);
+        }
+
+        let actual_source_crc = crc32fast::hash(source);
+        if actual_source_crc != source_crc {
+            bail!("{:?} patch was made for a different ROM: CRC32 0x{actual_source_crc:08X} does not match 0x{source_crc:08X}", self.format);
+        }
+
+        Ok((source_crc, target_crc))
+    }
+}
+
+fn check_target_size(target_size: usize) -> anyhow::Result<()> {
+    if target_size > MAX_TARGET_SIZE {
+        bail!("Patch produces {target_size} bytes, more than the {MAX_TARGET_SIZE} supported");
+    }
+    Ok(())
+}
+
+fn verify_target(target: &[u8], source_crc: u32, target_crc: u32) -> anyhow::Result<()> {
+    let actual_target_crc = crc32fast::hash(target);
+    if actual_target_crc != target_crc {
+        bail!("Patched ROM CRC32 0x{actual_target_crc:08X} does not match 0x{target_crc:08X} (source 0x{source_crc:08X})");
+    }
+    Ok(())
+}
+
+/// Writes `bytes` at `offset`, growing the target if the patch writes past its end
+fn write_at(target: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+    if target.len() < offset + bytes.len() {
+        target.resize(offset + bytes.len(), 0);
+    }
+    target[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// BPS relative offsets store the sign in the lowest bit
+fn apply_relative(offset: usize, encoded: usize) -> anyhow::Result<usize> {
+    let delta = encoded >> 1;
+    let result = if encoded & 1 != 0 {
+        offset.checked_sub(delta)
+    } else {
+        offset.checked_add(delta)
+    };
+    result.ok_or_else(|| format_err!("BPS relative offset out of bounds"))
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.pos = self.pos.saturating_add(count);
+    }
+
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        let byte = *self.data.get(self.pos).ok_or_else(|| format_err!("Unexpected end of patch"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_slice(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(count))
+            .ok_or_else(|| format_err!("Unexpected end of patch"))?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn read_be(&mut self, count: usize) -> anyhow::Result<usize> {
+        Ok(self.read_slice(count)?.iter().fold(0, |acc, &b| (acc << 8) | b as usize))
+    }
+
+    /// Variable-length integer used by UPS and BPS, where each continuation adds an implicit offset
+    fn read_varint(&mut self) -> anyhow::Result<usize> {
+        let overflow = || format_err!("Patch number is too large");
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.read_u8()?;
+            let digit = ((byte & 0x7F) as usize).checked_mul(shift).ok_or_else(overflow)?;
+            value = value.checked_add(digit).ok_or_else(overflow)?;
+            if byte & 0x80 != 0 {
+                break;
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+        Ok(value)
+    }
+}
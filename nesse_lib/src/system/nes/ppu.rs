@@ -1,38 +1,532 @@
-use std::mem::size_of;
-use anyhow::format_err;
-use num_traits::ToBytes;
+use bitflags::bitflags;
+use crate::system::nes::file::{Mirroring, Region};
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::ppu::background::Background;
+use crate::system::nes::ppu::palette::Palette;
+use crate::system::nes::ppu::sprites::Sprites;
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+mod background;
+pub mod ntsc;
+pub mod palette;
+mod sprites;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+pub const DOTS_PER_SCANLINE: u16 = 341;
+/// Frames until a bit of the I/O latch that isn't refreshed decays to 0, roughly 600ms
+const IO_LATCH_DECAY_FRAMES: u64 = 36;
+
+bitflags! {
+    /// PPUCTRL ($2000)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PPUCtrl : u8 {
+        const NametableX = 0b0000_0001;
+        const NametableY = 0b0000_0010;
+        const Increment32 = 0b0000_0100;
+        const SpritePatternTable = 0b0000_1000;
+        const BackgroundPatternTable = 0b0001_0000;
+        const SpriteSize16 = 0b0010_0000;
+        const MasterSlave = 0b0100_0000;
+        const NMIEnable = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// PPUMASK ($2001)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PPUMask : u8 {
+        const Greyscale = 0b0000_0001;
+        const ShowBackgroundLeft = 0b0000_0010;
+        const ShowSpritesLeft = 0b0000_0100;
+        const ShowBackground = 0b0000_1000;
+        const ShowSprites = 0b0001_0000;
+        const EmphasizeRed = 0b0010_0000;
+        const EmphasizeGreen = 0b0100_0000;
+        const EmphasizeBlue = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// PPUSTATUS ($2002)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PPUStatus : u8 {
+        const SpriteOverflow = 0b0010_0000;
+        const Sprite0Hit = 0b0100_0000;
+        const VBlank = 0b1000_0000;
+    }
+}
 
 pub struct PPU {
-    memory: [u8; 0x3FFF],
+    /// Nametable RAM, 2 KiB on the console plus 2 KiB for four-screen cartridges
+    pub ciram: [u8; 0x1000],
+    pub palette: [u8; 0x20],
+    pub oam: [u8; 0x100],
+
+    pub ctrl: PPUCtrl,
+    pub mask: PPUMask,
+    pub status: PPUStatus,
+    pub oam_addr: u8,
+
+    /// Current VRAM address, doubling as the scroll position while rendering
+    pub v: u16,
+    /// Temporary VRAM address, the scroll position of the top left corner of the screen
+    pub t: u16,
+    /// Fine X scroll
+    pub x: u8,
+    /// First/second write toggle shared by $2005 and $2006
+    pub w: bool,
+    read_buffer: u8,
+    /// The last value written to or read from a register, returned for unused bits
+    io_latch: u8,
+    /// The frame each bit of the I/O latch was last driven in
+    io_latch_refreshed: [u64; 8],
+    /// Writes to PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR are ignored until the end of the first vblank
+    warmed_up: bool,
+
+    background: Background,
+    sprites: Sprites,
+    /// Colours of the last rendered frame: 6-bit palette indices with the emphasis bits of PPUMASK in bits 6-8
+    pub framebuffer: Vec<u16>,
+    /// Used to convert the framebuffer to RGB
+    pub rgb_palette: Palette,
+
+    /// Decides the frame length, when vblank starts and the order of the emphasis bits
+    pub region: Region,
+    pub scanline: u16,
+    pub dot: u16,
+    pub frame: u64,
+    nmi_line: bool,
+    nmi_pending: bool,
+    /// Set by a PPUSTATUS read that cancelled an NMI which was already raised
+    nmi_cancelled: bool,
+    /// Set by a PPUSTATUS read right before vblank starts, which keeps the flag from being set this frame
+    suppress_vblank: bool,
 }
 
 impl PPU {
     pub fn new() -> Self {
         Self {
-            memory: [0; 0x3FFF],
+            ciram: [0; 0x1000],
+            palette: [0; 0x20],
+            oam: [0; 0x100],
+            ctrl: PPUCtrl::empty(),
+            mask: PPUMask::empty(),
+            status: PPUStatus::empty(),
+            oam_addr: 0,
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            io_latch_refreshed: [0; 8],
+            warmed_up: false,
+            background: Background::default(),
+            sprites: Sprites::default(),
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            rgb_palette: Palette::ntsc(),
+            region: Region::NTSC,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            nmi_cancelled: false,
+            suppress_vblank: false,
         }
     }
 
-    pub fn write<T: ToBytes>(&mut self, dst: u16, data: &T) -> anyhow::Result<()> {
-        let size = size_of::<T>();
-        if dst as usize + size > self.memory.len() {
-            return Err(format_err!("Out of Bounds Write to 0x{dst:x?} of size {size}"));
+    /// The reset line clears the registers and ignores writes again until the end of vblank
+    pub fn reset(&mut self) {
+        self.ctrl = PPUCtrl::empty();
+        self.mask = PPUMask::empty();
+        self.t = 0;
+        self.x = 0;
+        self.w = false;
+        self.read_buffer = 0;
+        self.warmed_up = false;
+        self.update_nmi();
+    }
+
+    /// Whether writes to all registers take effect, which they don't right after power-on or reset
+    pub fn is_warmed_up(&self) -> bool {
+        self.warmed_up
+    }
+
+    /// Reads the PPU bus: pattern tables from the cartridge, nametables from CIRAM and palette RAM
+    pub fn bus_read(&mut self, addr: u16, cartridge: Option<&mut (dyn Mapper + '_)>) -> u8 {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => cartridge.map_or(0, |cartridge| cartridge.ppu_read(addr)),
+            0x2000..=0x3EFF => self.ciram[nametable_index(addr, mirroring(&cartridge))],
+            _ => self.read_palette(addr),
         }
+    }
 
-        let binding = data.to_be_bytes();
-        let bytes = binding.as_ref();
-        self.memory[dst as usize..dst as usize + size].copy_from_slice(&bytes);
+    /// Reads the PPU bus without side effects, for the debugger
+    pub fn bus_peek(&self, addr: u16, cartridge: Option<&dyn Mapper>) -> u8 {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => cartridge.map_or(0, |cartridge| cartridge.ppu_peek(addr)),
+            0x2000..=0x3EFF => {
+                let mirroring = cartridge.map_or(Mirroring::Horizontal, |cartridge| cartridge.mirroring());
+                self.ciram[nametable_index(addr, mirroring)]
+            }
+            _ => self.read_palette(addr),
+        }
+    }
 
-        Ok(())
+    pub fn bus_write(&mut self, addr: u16, value: u8, cartridge: Option<&mut (dyn Mapper + '_)>) {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => {
+                if let Some(cartridge) = cartridge {
+                    cartridge.ppu_write(addr, value);
+                }
+            }
+            0x2000..=0x3EFF => self.ciram[nametable_index(addr, mirroring(&cartridge))] = value,
+            _ => self.palette[palette_index(addr)] = value & 0x3F,
+        }
     }
-    
-    pub fn write_slice(&mut self, dst: u16, data: &[u8]) -> anyhow::Result<()> {
-        if dst as usize + data.len() > self.memory.len() {
-            return Err(format_err!("Out of Bounds Write to 0x{dst:x?} of size {}", data.len()));
+
+    pub fn read_palette(&self, addr: u16) -> u8 {
+        let value = self.palette[palette_index(addr)];
+        if self.mask.contains(PPUMask::Greyscale) {
+            value & 0x30
+        } else {
+            value
         }
+    }
+
+    /// Switches the timing and the RGB palette to the given region
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.rgb_palette = Palette::for_region(region);
+    }
+
+    pub fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines_per_frame() - 1
+    }
+
+    /// The last rendered frame as RGBA8, row by row
+    pub fn frame_rgba(&self) -> Vec<u8> {
+        self.framebuffer.iter().flat_map(|color| self.rgb_palette.rgba(*color)).collect()
+    }
+
+    /// The last rendered frame as RGB565, row by row
+    pub fn frame_rgb565(&self) -> Vec<u16> {
+        self.framebuffer.iter().map(|color| self.rgb_palette.rgb565(*color)).collect()
+    }
 
-        self.memory[dst as usize..dst as usize + data.len()].copy_from_slice(data);
+    /// CPU read of $2000-$3FFF, which mirrors the eight registers
+    pub fn read_register(&mut self, addr: u16, cartridge: Option<&mut (dyn Mapper + '_)>) -> u8 {
+        let latch = self.io_latch();
+        let (value, driven) = match addr & 0x07 {
+            // PPUSTATUS
+            2 => {
+                if self.scanline == self.region.vblank_scanline() {
+                    match self.dot {
+                        // Right before the flag is set, it reads as clear and stays clear for this frame
+                        1 => self.suppress_vblank = true,
+                        // Right after, it reads as set but the NMI is cancelled
+                        2 | 3 => {
+                            self.nmi_pending = false;
+                            self.nmi_cancelled = true;
+                        }
+                        _ => {}
+                    }
+                }
 
+                let value = self.status.bits() | (latch & 0x1F);
+                self.status.remove(PPUStatus::VBlank);
+                self.w = false;
+                self.update_nmi();
+                (value, 0xE0)
+            }
+            // OAMDATA, which reads $FF while secondary OAM is being cleared
+            4 if self.rendering_enabled() && (self.scanline as usize) < SCREEN_HEIGHT && (1..=64).contains(&self.dot) => (0xFF, 0xFF),
+            4 => (self.oam[self.oam_addr as usize], 0xFF),
+            // PPUDATA, delayed by the read buffer except for palette reads
+            7 => {
+                let addr = self.v & 0x3FFF;
+                let read = if addr >= 0x3F00 {
+                    // The buffer is filled with the nametable byte "under" the palette
+                    self.read_buffer = self.bus_read(addr - 0x1000, cartridge);
+                    (self.read_palette(addr) | (latch & 0xC0), 0x3F)
+                } else {
+                    let buffered = self.read_buffer;
+                    self.read_buffer = self.bus_read(addr, cartridge);
+                    (buffered, 0xFF)
+                };
+                self.increment_v();
+                read
+            }
+            // Write-only registers return the I/O latch
+            _ => (latch, 0x00),
+        };
+
+        self.refresh_io_latch(value, driven);
+        value
+    }
+
+    /// Returns whether a PPUSTATUS read cancelled an NMI since the last call, so the CPU can drop it
+    pub fn take_nmi_cancel(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_cancelled)
+    }
+
+    /// The I/O latch with the bits that haven't been driven for a while decayed to 0
+    fn io_latch(&self) -> u8 {
+        (0..8)
+            .filter(|bit| self.frame - self.io_latch_refreshed[*bit] < IO_LATCH_DECAY_FRAMES)
+            .fold(0, |latch, bit| latch | (self.io_latch & 1 << bit))
+    }
+
+    /// Updates the bits of the I/O latch driven by a read or write
+    fn refresh_io_latch(&mut self, value: u8, driven: u8) {
+        self.io_latch = (self.io_latch & !driven) | (value & driven);
+        for bit in 0..8 {
+            if driven & 1 << bit != 0 {
+                self.io_latch_refreshed[bit] = self.frame;
+            }
+        }
+    }
+
+    /// Reads a register without side effects, for the debugger
+    pub fn peek_register(&self, addr: u16) -> u8 {
+        match addr & 0x07 {
+            2 => self.status.bits() | (self.io_latch() & 0x1F),
+            4 => self.oam[self.oam_addr as usize],
+            7 => self.read_buffer,
+            _ => self.io_latch(),
+        }
+    }
+
+    /// CPU write of $2000-$3FFF
+    pub fn write_register(&mut self, addr: u16, value: u8, cartridge: Option<&mut (dyn Mapper + '_)>) {
+        self.refresh_io_latch(value, 0xFF);
+
+        if !self.warmed_up && matches!(addr & 0x07, 0 | 1 | 5 | 6) {
+            return;
+        }
+
+        match addr & 0x07 {
+            0 => {
+                self.ctrl = PPUCtrl::from_bits_retain(value);
+                self.t = (self.t & 0xF3FF) | ((value as u16 & 0x03) << 10);
+                self.update_nmi();
+            }
+            1 => self.mask = PPUMask::from_bits_retain(value),
+            2 => {}
+            3 => self.oam_addr = value,
+            4 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            // PPUSCROLL
+            5 => {
+                if !self.w {
+                    self.t = (self.t & 0xFFE0) | (value as u16 >> 3);
+                    self.x = value & 0x07;
+                } else {
+                    self.t = (self.t & 0x8C1F) | ((value as u16 & 0x07) << 12) | ((value as u16 & 0xF8) << 2);
+                }
+                self.w = !self.w;
+            }
+            // PPUADDR
+            6 => {
+                if !self.w {
+                    self.t = (self.t & 0x80FF) | ((value as u16 & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | value as u16;
+                    self.v = self.t;
+                }
+                self.w = !self.w;
+            }
+            // PPUDATA
+            _ => {
+                self.bus_write(self.v, value, cartridge);
+                self.increment_v();
+            }
+        }
+    }
+
+    fn increment_v(&mut self) {
+        let increment = if self.ctrl.contains(PPUCtrl::Increment32) { 32 } else { 1 };
+        self.v = self.v.wrapping_add(increment) & 0x7FFF;
+    }
+
+    /// NMI is raised on the rising edge of vblank AND the NMI enable bit
+    fn update_nmi(&mut self) {
+        let line = self.ctrl.contains(PPUCtrl::NMIEnable) && self.status.contains(PPUStatus::VBlank);
+        if line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = line;
+    }
+
+    /// Returns whether an NMI was raised since the last call
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask.intersects(PPUMask::ShowBackground | PPUMask::ShowSprites)
+    }
+
+    /// Advances the PPU by one dot
+    pub fn tick(&mut self, mut cartridge: Option<&mut (dyn Mapper + '_)>) {
+        let visible = (self.scanline as usize) < SCREEN_HEIGHT;
+        let pre_render = self.scanline == self.pre_render_scanline();
+        if self.rendering_enabled() && (visible || pre_render) {
+            self.render_background(cartridge.as_deref_mut());
+            self.render_sprites(cartridge);
+        }
+        if visible && (1..=SCREEN_WIDTH as u16).contains(&self.dot) {
+            self.output_pixel();
+        }
+
+        if self.dot == 1 && self.scanline == self.region.vblank_scanline() {
+            if !std::mem::take(&mut self.suppress_vblank) {
+                self.status.insert(PPUStatus::VBlank);
+                self.update_nmi();
+            }
+        } else if self.dot == 1 && pre_render {
+            self.status.remove(PPUStatus::VBlank | PPUStatus::Sprite0Hit | PPUStatus::SpriteOverflow);
+            self.update_nmi();
+            self.warmed_up = true;
+        }
+
+        self.dot += 1;
+        // Odd NTSC frames jump from the second to last dot of the pre-render line straight to the next frame
+        if pre_render && self.dot == DOTS_PER_SCANLINE - 1 && self.frame % 2 == 1
+            && self.rendering_enabled() && self.region.skips_odd_frame_dot() {
+            self.dot = DOTS_PER_SCANLINE;
+        }
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == self.region.scanlines_per_frame() {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+    }
+
+    fn output_pixel(&mut self) {
+        let x = self.dot - 1;
+        let (background, background_palette) = self.background_pixel(x);
+        let sprite = self.sprite_pixel(x);
+
+        let (pixel, palette) = match sprite {
+            Some(sprite) if background != 0 => {
+                // Sprite 0 hits on opaque pixels of both, except at the last dot
+                if sprite.sprite0 && x != 255 {
+                    self.status.insert(PPUStatus::Sprite0Hit);
+                }
+                if sprite.behind_background { (background, background_palette) } else { (sprite.pixel, sprite.palette) }
+            }
+            Some(sprite) => (sprite.pixel, sprite.palette),
+            None => (background, background_palette),
+        };
+        let palette_addr = if pixel == 0 { 0x3F00 } else { 0x3F00 | (palette as u16) << 2 | pixel as u16 };
+
+        let mut emphasis = self.mask.bits() as u16 >> 5;
+        if self.region.swaps_emphasis() {
+            emphasis = (emphasis & 0x04) | (emphasis & 0x01) << 1 | (emphasis & 0x02) >> 1;
+        }
+        let emphasis = emphasis << 6;
+        let index = self.scanline as usize * SCREEN_WIDTH + x as usize;
+        self.framebuffer[index] = self.read_palette(palette_addr) as u16 | emphasis;
+    }
+}
+
+fn mirroring(cartridge: &Option<&mut (dyn Mapper + '_)>) -> Mirroring {
+    cartridge.as_ref().map_or(Mirroring::Horizontal, |cartridge| cartridge.mirroring())
+}
+
+/// Maps $2000-$3EFF to CIRAM, where $3000-$3EFF mirrors $2000-$2EFF
+fn nametable_index(addr: u16, mirroring: Mirroring) -> usize {
+    let table = (addr as usize >> 10) & 0x03;
+    let offset = addr as usize & 0x03FF;
+    let page = match mirroring {
+        Mirroring::Horizontal => table >> 1,
+        Mirroring::Vertical => table & 0x01,
+        Mirroring::SingleScreenLower => 0,
+        Mirroring::SingleScreenUpper => 1,
+        Mirroring::FourScreen => table,
+    };
+    page * 0x400 + offset
+}
+
+/// $3F10/$3F14/$3F18/$3F1C alias the backdrop entries $3F00/$3F04/$3F08/$3F0C
+fn palette_index(addr: u16) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 {
+        index & 0x0F
+    } else {
+        index
+    }
+}
+
+/// Everything but the framebuffer, which the next frame redraws, and the region and palette, which are settings
+impl Savestate for PPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ciram);
+        state.write_bytes(&self.palette);
+        state.write_bytes(&self.oam);
+        state.write(self.ctrl.bits());
+        state.write(self.mask.bits());
+        state.write(self.status.bits());
+        state.write(self.oam_addr);
+        state.write(self.v);
+        state.write(self.t);
+        state.write(self.x);
+        state.write(self.w);
+        state.write(self.read_buffer);
+        state.write(self.io_latch);
+        for refreshed in self.io_latch_refreshed {
+            state.write(refreshed);
+        }
+        state.write(self.warmed_up);
+        self.background.save_state(state);
+        self.sprites.save_state(state);
+        state.write(self.scanline);
+        state.write(self.dot);
+        state.write(self.frame);
+        state.write(self.nmi_line);
+        state.write(self.nmi_pending);
+        state.write(self.nmi_cancelled);
+        state.write(self.suppress_vblank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.ciram)?;
+        state.read_bytes_into(&mut self.palette)?;
+        state.read_bytes_into(&mut self.oam)?;
+        self.ctrl = PPUCtrl::from_bits_retain(state.read()?);
+        self.mask = PPUMask::from_bits_retain(state.read()?);
+        self.status = PPUStatus::from_bits_retain(state.read()?);
+        self.oam_addr = state.read()?;
+        self.v = state.read()?;
+        self.t = state.read()?;
+        self.x = state.read()?;
+        self.w = state.read()?;
+        self.read_buffer = state.read()?;
+        self.io_latch = state.read()?;
+        for refreshed in &mut self.io_latch_refreshed {
+            *refreshed = state.read()?;
+        }
+        self.warmed_up = state.read()?;
+        self.background.load_state(state)?;
+        self.sprites.load_state(state)?;
+        self.scanline = state.read()?;
+        self.dot = state.read()?;
+        self.frame = state.read()?;
+        self.nmi_line = state.read()?;
+        self.nmi_pending = state.read()?;
+        self.nmi_cancelled = state.read()?;
+        self.suppress_vblank = state.read()?;
         Ok(())
     }
-}
\ No newline at end of file
+}
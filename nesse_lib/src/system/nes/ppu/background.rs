@@ -0,0 +1,152 @@
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::ppu::{PPUCtrl, PPUMask, PPU};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+/// Latches filled by the tile fetches and the shift registers feeding the pixel output
+#[derive(Default)]
+pub struct Background {
+    nametable: u8,
+    attribute: u8,
+    pattern_low: u8,
+    pattern_high: u8,
+    shift_pattern_low: u16,
+    shift_pattern_high: u16,
+    shift_attribute_low: u16,
+    shift_attribute_high: u16,
+}
+
+impl Background {
+    fn shift(&mut self) {
+        self.shift_pattern_low <<= 1;
+        self.shift_pattern_high <<= 1;
+        self.shift_attribute_low <<= 1;
+        self.shift_attribute_high <<= 1;
+    }
+
+    /// Moves the fetched tile into the low bytes of the shift registers
+    fn reload(&mut self) {
+        self.shift_pattern_low = (self.shift_pattern_low & 0xFF00) | self.pattern_low as u16;
+        self.shift_pattern_high = (self.shift_pattern_high & 0xFF00) | self.pattern_high as u16;
+        self.shift_attribute_low = (self.shift_attribute_low & 0xFF00) | if self.attribute & 0x01 != 0 { 0xFF } else { 0x00 };
+        self.shift_attribute_high = (self.shift_attribute_high & 0xFF00) | if self.attribute & 0x02 != 0 { 0xFF } else { 0x00 };
+    }
+
+    /// The 2-bit pixel and palette at the given fine X scroll
+    pub fn pixel(&self, fine_x: u8) -> (u8, u8) {
+        let bit = 0x8000 >> fine_x;
+        let pixel = ((self.shift_pattern_high & bit != 0) as u8) << 1 | (self.shift_pattern_low & bit != 0) as u8;
+        let palette = ((self.shift_attribute_high & bit != 0) as u8) << 1 | (self.shift_attribute_low & bit != 0) as u8;
+        (pixel, palette)
+    }
+}
+
+impl PPU {
+    /// Background fetches and scroll updates of one dot on a visible or the pre-render scanline
+    pub fn render_background(&mut self, mut cartridge: Option<&mut (dyn Mapper + '_)>) {
+        let dot = self.dot;
+
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.background.shift();
+        }
+
+        if (1..=256).contains(&dot) || (321..=336).contains(&dot) {
+            match (dot - 1) % 8 {
+                0 => {
+                    self.background.reload();
+                    self.background.nametable = self.bus_read(0x2000 | (self.v & 0x0FFF), cartridge.as_deref_mut());
+                }
+                2 => {
+                    let addr = 0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                    let shift = ((self.v >> 4) & 0x04) | (self.v & 0x02);
+                    self.background.attribute = (self.bus_read(addr, cartridge.as_deref_mut()) >> shift) & 0x03;
+                }
+                4 => self.background.pattern_low = self.bus_read(self.background_pattern_addr(), cartridge.as_deref_mut()),
+                6 => self.background.pattern_high = self.bus_read(self.background_pattern_addr() + 8, cartridge.as_deref_mut()),
+                7 => self.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        match dot {
+            256 => self.increment_y(),
+            257 => {
+                self.background.reload();
+                // Copy the horizontal scroll from t
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
+            }
+            // Copy the vertical scroll from t, repeatedly, at the end of the pre-render scanline
+            280..=304 if self.scanline == self.pre_render_scanline() => self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0),
+            // Unused nametable fetches, which some mappers watch
+            337 | 339 => self.background.nametable = self.bus_read(0x2000 | (self.v & 0x0FFF), cartridge),
+            _ => {}
+        }
+    }
+
+    /// The background pixel at the current dot, 0 if transparent or hidden
+    pub fn background_pixel(&self, x: u16) -> (u8, u8) {
+        if !self.mask.contains(PPUMask::ShowBackground) || (x < 8 && !self.mask.contains(PPUMask::ShowBackgroundLeft)) {
+            return (0, 0);
+        }
+        self.background.pixel(self.x)
+    }
+
+    fn background_pattern_addr(&self) -> u16 {
+        let table = if self.ctrl.contains(PPUCtrl::BackgroundPatternTable) { 0x1000 } else { 0 };
+        let fine_y = (self.v >> 12) & 0x07;
+        table | (self.background.nametable as u16) << 4 | fine_y
+    }
+
+    fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // Coarse Y in the attribute table wraps without switching nametables
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+}
+
+impl Savestate for Background {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.nametable);
+        state.write(self.attribute);
+        state.write(self.pattern_low);
+        state.write(self.pattern_high);
+        state.write(self.shift_pattern_low);
+        state.write(self.shift_pattern_high);
+        state.write(self.shift_attribute_low);
+        state.write(self.shift_attribute_high);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.nametable = state.read()?;
+        self.attribute = state.read()?;
+        self.pattern_low = state.read()?;
+        self.pattern_high = state.read()?;
+        self.shift_pattern_low = state.read()?;
+        self.shift_pattern_high = state.read()?;
+        self.shift_attribute_low = state.read()?;
+        self.shift_attribute_high = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::f32::consts::PI;
+use crate::system::nes::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Width of the filtered image, which stretches the 256 pixels to the 8:7 pixel aspect ratio
+pub const NTSC_OUTPUT_WIDTH: usize = 292;
+
+/// The PPU outputs 8 samples per pixel, and the colour subcarrier repeats every 12 samples
+const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_CYCLE: usize = 12;
+/// A scanline of 341 dots shifts the subcarrier phase by 4 samples
+const PHASE_PER_SCANLINE: usize = 341 * SAMPLES_PER_PIXEL % SAMPLES_PER_CYCLE;
+/// Black padding around each line so the decoding windows don't run off the edges
+const PADDING: usize = SAMPLES_PER_CYCLE * 2;
+
+/// Signal voltages of the four luma levels, low and high half of the colour wave
+const LEVELS_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+/// Aligns the decoded hues with the PPU's colour phases
+const HUE_OFFSET: f32 = 3.9;
+
+/// Synthesises the composite signal the PPU would output for a framebuffer and decodes it back to RGB,
+/// which reproduces the colour artifacts and dot crawl of an NTSC TV
+pub struct NtscFilter {
+    /// 0.0 blurs luma over two colour cycles, 1.0 over one
+    pub sharpness: f32,
+    /// 0.0 filters chroma over two colour cycles, 1.0 over one which gives stronger fringing
+    pub artifacts: f32,
+    /// Shifts the subcarrier phase every frame like the hardware, otherwise every frame uses the same phase
+    pub dot_crawl: bool,
+    cos: [f32; SAMPLES_PER_CYCLE],
+    sin: [f32; SAMPLES_PER_CYCLE],
+}
+
+impl NtscFilter {
+    pub fn new() -> Self {
+        let angle = |phase: usize| PI * (phase as f32 + HUE_OFFSET) / 6.0;
+        Self {
+            sharpness: 0.5,
+            artifacts: 0.5,
+            dot_crawl: true,
+            cos: std::array::from_fn(|phase| angle(phase).cos()),
+            sin: std::array::from_fn(|phase| angle(phase).sin()),
+        }
+    }
+
+    /// Filters a PPU framebuffer of the given frame into RGBA8 rows of `NTSC_OUTPUT_WIDTH` pixels
+    pub fn filter(&self, framebuffer: &[u16], frame: u64) -> Vec<u8> {
+        let mut output = Vec::with_capacity(NTSC_OUTPUT_WIDTH * SCREEN_HEIGHT * 4);
+        let frame_phase = if self.dot_crawl { (frame % 3) as usize * PHASE_PER_SCANLINE } else { 0 };
+        let mut signal = vec![0.0; SCREEN_WIDTH * SAMPLES_PER_PIXEL + PADDING * 2];
+
+        for (y, line) in framebuffer.chunks_exact(SCREEN_WIDTH).take(SCREEN_HEIGHT).enumerate() {
+            let line_phase = (frame_phase + y * PHASE_PER_SCANLINE) % SAMPLES_PER_CYCLE;
+            for (x, color) in line.iter().enumerate() {
+                for sample in 0..SAMPLES_PER_PIXEL {
+                    let phase = line_phase + x * SAMPLES_PER_PIXEL + sample;
+                    signal[PADDING + x * SAMPLES_PER_PIXEL + sample] = composite_signal(*color, phase);
+                }
+            }
+
+            for x in 0..NTSC_OUTPUT_WIDTH {
+                let center = PADDING + x * SCREEN_WIDTH * SAMPLES_PER_PIXEL / NTSC_OUTPUT_WIDTH;
+                // The padding is a whole number of colour cycles, so it doesn't shift the phase
+                let narrow = self.decode(&signal, center, SAMPLES_PER_CYCLE, line_phase);
+                let wide = self.decode(&signal, center, SAMPLES_PER_CYCLE * 2, line_phase);
+
+                let luma = lerp(wide[0], narrow[0], self.sharpness);
+                let i = lerp(wide[1], narrow[1], self.artifacts);
+                let q = lerp(wide[2], narrow[2], self.artifacts);
+                output.extend_from_slice(&yiq_to_rgba(luma, i, q));
+            }
+        }
+
+        output
+    }
+
+    /// Averages the luma and demodulates I and Q over a window of samples around the center
+    fn decode(&self, signal: &[f32], center: usize, width: usize, phase: usize) -> [f32; 3] {
+        let start = center - width / 2;
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        for (offset, sample) in signal[start..start + width].iter().enumerate() {
+            let phase = (phase + start + offset) % SAMPLES_PER_CYCLE;
+            y += sample;
+            i += sample * self.cos[phase];
+            q += sample * self.sin[phase];
+        }
+        let width = width as f32;
+        [y / width, i * 2.0 / width, q * 2.0 / width]
+    }
+}
+
+impl Default for NtscFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The normalized signal level of a colour (palette index with emphasis bits) at a subcarrier phase
+fn composite_signal(color: u16, phase: usize) -> f32 {
+    let hue = (color & 0x0F) as usize;
+    let emphasis = color >> 6;
+    let level = if hue > 0x0D { 1 } else { (color as usize >> 4) & 0x03 };
+    let in_phase = |hue: usize| (hue + phase) % SAMPLES_PER_CYCLE < 6;
+
+    let mut low = LEVELS_LOW[level];
+    let mut high = LEVELS_HIGH[level];
+    if hue == 0x00 {
+        low = high;
+    }
+    if hue > 0x0C {
+        high = low;
+    }
+
+    let mut signal = if in_phase(hue) { high } else { low };
+    if (emphasis & 0x01 != 0 && in_phase(0x0C)) || (emphasis & 0x02 != 0 && in_phase(0x04)) || (emphasis & 0x04 != 0 && in_phase(0x08)) {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+fn yiq_to_rgba(y: f32, i: f32, q: f32) -> [u8; 4] {
+    // The TV's gamma is a bit higher than the monitor's
+    let channel = |value: f32| (value.max(0.0).powf(2.2 / 1.8) * 255.0).min(255.0) as u8;
+    [
+        channel(y + 0.946_882 * i + 0.623_557 * q),
+        channel(y - 0.274_788 * i - 0.635_691 * q),
+        channel(y - 1.108_545 * i + 1.709_007 * q),
+        0xFF,
+    ]
+}
+
+fn lerp(from: f32, to: f32, amount: f32) -> f32 {
+    from + (to - from) * amount.clamp(0.0, 1.0)
+}
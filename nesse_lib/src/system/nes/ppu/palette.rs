@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+use anyhow::bail;
+use crate::system::nes::file::Region;
+
+/// Palette indices with every combination of the three PPUMASK emphasis bits
+pub const PALETTE_ENTRIES: usize = 64 * 8;
+
+/// How much an emphasis bit dims the other two colour channels
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+
+/// The 2C07's colour burst is offset from the 2C02's, which rotates every hue a bit
+const PAL_HUE_SHIFT: f32 = -15.0;
+
+/// A palette measured from the 2C02 composite output
+const NTSC_PALETTE: [u32; 64] = [
+    0x666666, 0x002A88, 0x1412A7, 0x3B00A4, 0x5C007E, 0x6E0040, 0x6C0600, 0x561D00,
+    0x333500, 0x0B4800, 0x005200, 0x004F08, 0x00404D, 0x000000, 0x000000, 0x000000,
+    0xADADAD, 0x155FD9, 0x4240FF, 0x7527FE, 0xA01ACC, 0xB71E7B, 0xB53120, 0x994E00,
+    0x6B6D00, 0x388700, 0x0C9300, 0x008F32, 0x007C8D, 0x000000, 0x000000, 0x000000,
+    0xFFFEFF, 0x64B0FF, 0x9290FF, 0xC676FF, 0xF36AFF, 0xFE6ECC, 0xFE8170, 0xEA9E22,
+    0xBCBE00, 0x88D800, 0x5CE430, 0x45E082, 0x48CDDE, 0x4F4F4F, 0x000000, 0x000000,
+    0xFFFEFF, 0xC0DFFF, 0xD3D2FF, 0xE8C8FF, 0xFBC2FF, 0xFEC4EA, 0xFECCC5, 0xF7D8A5,
+    0xE4E594, 0xCFEF96, 0xBDF4AB, 0xB3F3CC, 0xB5EBF2, 0xB8B8B8, 0x000000, 0x000000,
+];
+
+/// Maps the PPU's colours (6-bit palette index with the emphasis bits above it) to RGB
+#[derive(Clone)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Loads a .pal file
+    pub fn new<P: AsRef<Path>>(pal_path: &P) -> anyhow::Result<Self> {
+        Self::from_bytes(&fs::read(pal_path)?)
+    }
+
+    /// The built-in NTSC palette
+    pub fn ntsc() -> Self {
+        let colors = NTSC_PALETTE
+            .iter()
+            .map(|rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8])
+            .collect::<Vec<_>>();
+        Self::with_emphasis(&colors)
+    }
+
+    /// The built-in NTSC palette with its hues rotated like a 2C07
+    pub fn pal() -> Self {
+        let (sin, cos) = PAL_HUE_SHIFT.to_radians().sin_cos();
+        let colors = NTSC_PALETTE
+            .iter()
+            .map(|rgb| {
+                let [r, g, b] = [(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8].map(|channel| channel as f32);
+                let y = 0.299 * r + 0.587 * g + 0.114 * b;
+                let i = 0.596 * r - 0.274 * g - 0.322 * b;
+                let q = 0.211 * r - 0.523 * g + 0.312 * b;
+                let (i, q) = (i * cos - q * sin, i * sin + q * cos);
+                [
+                    y + 0.956 * i + 0.621 * q,
+                    y - 0.272 * i - 0.647 * q,
+                    y - 1.106 * i + 1.703 * q,
+                ].map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+            })
+            .collect::<Vec<_>>();
+        Self::with_emphasis(&colors)
+    }
+
+    pub fn for_region(region: Region) -> Self {
+        match region {
+            Region::PAL | Region::Dendy => Self::pal(),
+            _ => Self::ntsc(),
+        }
+    }
+
+    /// Parses a .pal file of 64 RGB triplets, or 512 with the emphasis combinations already included
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let colors = data
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect::<Vec<_>>();
+
+        match data.len() {
+            len if len == 64 * 3 => Ok(Self::with_emphasis(&colors)),
+            len if len == PALETTE_ENTRIES * 3 => Ok(Self { colors }),
+            len => bail!("Invalid palette of {len} bytes, expected 64 or 512 RGB entries"),
+        }
+    }
+
+    /// Extends 64 colours to all emphasis combinations by dimming the channels that aren't emphasized
+    fn with_emphasis(base: &[[u8; 3]]) -> Self {
+        let mut colors = Vec::with_capacity(PALETTE_ENTRIES);
+        for emphasis in 0..8u32 {
+            for color in base {
+                let mut color = *color;
+                for (channel, value) in color.iter_mut().enumerate() {
+                    // Emphasis bits are red, green and blue from the lowest up
+                    let dimmed = emphasis & !(1 << channel);
+                    if dimmed != 0 {
+                        *value = (*value as f32 * EMPHASIS_ATTENUATION.powi(dimmed.count_ones() as i32)) as u8;
+                    }
+                }
+                colors.push(color);
+            }
+        }
+        Self { colors }
+    }
+
+    pub fn rgb(&self, color: u16) -> [u8; 3] {
+        self.colors[color as usize % PALETTE_ENTRIES]
+    }
+
+    pub fn rgba(&self, color: u16) -> [u8; 4] {
+        let [r, g, b] = self.rgb(color);
+        [r, g, b, 0xFF]
+    }
+
+    pub fn rgb565(&self, color: u16) -> u16 {
+        let [r, g, b] = self.rgb(color);
+        (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::ntsc()
+    }
+}
@@ -0,0 +1,198 @@
+use crate::system::nes::mapper::Mapper;
+use crate::system::nes::ppu::{PPUCtrl, PPUMask, PPUStatus, PPU, SCREEN_HEIGHT};
+use crate::system::nes::state::{Savestate, StateReader, StateWriter};
+
+const MAX_SPRITES_PER_LINE: usize = 8;
+
+/// A sprite fetched for the scanline being drawn, with horizontal flipping already applied to its pattern
+#[derive(Default, Clone, Copy)]
+struct SpriteSlot {
+    x: u8,
+    attribute: u8,
+    pattern_low: u8,
+    pattern_high: u8,
+}
+
+#[derive(Default)]
+pub struct Sprites {
+    /// Sprites found for the next scanline, the rest of the 32 bytes is $FF
+    pub secondary_oam: [u8; 32],
+    found: usize,
+    sprite0_found: bool,
+    slots: [SpriteSlot; MAX_SPRITES_PER_LINE],
+    slot_count: usize,
+    sprite0_in_slots: bool,
+}
+
+/// An opaque sprite pixel
+pub struct SpritePixel {
+    pub pixel: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    pub sprite0: bool,
+}
+
+impl PPU {
+    pub fn sprite_height(&self) -> u16 {
+        if self.ctrl.contains(PPUCtrl::SpriteSize16) { 16 } else { 8 }
+    }
+
+    /// Sprite evaluation and fetches of one dot on a visible or the pre-render scanline
+    pub fn render_sprites(&mut self, cartridge: Option<&mut (dyn Mapper + '_)>) {
+        match self.dot {
+            256 if (self.scanline as usize) < SCREEN_HEIGHT => self.evaluate_sprites(),
+            256 => {
+                self.sprites.secondary_oam = [0xFF; 32];
+                self.sprites.found = 0;
+                self.sprites.sprite0_found = false;
+            }
+            257..=320 => {
+                self.oam_addr = 0;
+                let slot = (self.dot - 257) as usize / 8;
+                if (self.dot - 257) % 8 == 7 {
+                    self.fetch_sprite(slot, cartridge);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Finds the first 8 sprites on the current scanline, to be drawn on the next one.
+    /// Past 8 sprites, the hardware also increments the byte index within a sprite, so overflow
+    /// is detected from the wrong bytes.
+    fn evaluate_sprites(&mut self) {
+        let height = self.sprite_height();
+        let in_range = |y: u8| (self.scanline.wrapping_sub(y as u16)) < height;
+
+        let mut secondary_oam = [0xFF; 32];
+        let mut found = 0;
+        let mut sprite0_found = false;
+        let mut n = 0;
+        while n < 64 && found < MAX_SPRITES_PER_LINE {
+            if in_range(self.oam[n * 4]) {
+                secondary_oam[found * 4..found * 4 + 4].copy_from_slice(&self.oam[n * 4..n * 4 + 4]);
+                sprite0_found |= n == 0;
+                found += 1;
+            }
+            n += 1;
+        }
+
+        let mut m = 0;
+        while n < 64 {
+            if in_range(self.oam[n * 4 + m]) {
+                self.status.insert(PPUStatus::SpriteOverflow);
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 0x03;
+        }
+
+        self.sprites.secondary_oam = secondary_oam;
+        self.sprites.found = found;
+        self.sprites.sprite0_found = sprite0_found;
+    }
+
+    /// Fetches the pattern of a sprite in secondary OAM, empty slots fetch tile $FF like the hardware
+    fn fetch_sprite(&mut self, slot: usize, mut cartridge: Option<&mut (dyn Mapper + '_)>) {
+        let entry = &self.sprites.secondary_oam[slot * 4..slot * 4 + 4];
+        let (y, tile, attribute, x) = (entry[0], entry[1], entry[2], entry[3]);
+        let height = self.sprite_height();
+
+        let mut row = self.scanline.wrapping_sub(y as u16) & (height - 1);
+        if attribute & 0x80 != 0 {
+            row = height - 1 - row;
+        }
+
+        let addr = if height == 16 {
+            let table = (tile as u16 & 0x01) << 12;
+            let tile = (tile as u16 & 0xFE) + (row >> 3);
+            table | tile << 4 | (row & 0x07)
+        } else {
+            let table = if self.ctrl.contains(PPUCtrl::SpritePatternTable) { 0x1000 } else { 0 };
+            table | (tile as u16) << 4 | row
+        };
+
+        let mut pattern_low = self.bus_read(addr, cartridge.as_deref_mut());
+        let mut pattern_high = self.bus_read(addr + 8, cartridge);
+        if attribute & 0x40 != 0 {
+            pattern_low = pattern_low.reverse_bits();
+            pattern_high = pattern_high.reverse_bits();
+        }
+
+        if slot < self.sprites.found {
+            self.sprites.slots[slot] = SpriteSlot { x, attribute, pattern_low, pattern_high };
+        }
+        if slot == MAX_SPRITES_PER_LINE - 1 {
+            self.sprites.slot_count = self.sprites.found;
+            self.sprites.sprite0_in_slots = self.sprites.sprite0_found;
+        }
+    }
+
+    /// The frontmost opaque sprite pixel at the given X coordinate
+    pub fn sprite_pixel(&self, x: u16) -> Option<SpritePixel> {
+        if !self.mask.contains(PPUMask::ShowSprites) || (x < 8 && !self.mask.contains(PPUMask::ShowSpritesLeft)) {
+            return None;
+        }
+
+        self.sprites.slots[..self.sprites.slot_count]
+            .iter()
+            .enumerate()
+            .find_map(|(i, sprite)| {
+                let offset = x.wrapping_sub(sprite.x as u16);
+                if offset >= 8 {
+                    return None;
+                }
+
+                let bit = 0x80 >> offset;
+                let pixel = ((sprite.pattern_high & bit != 0) as u8) << 1 | (sprite.pattern_low & bit != 0) as u8;
+                (pixel != 0).then_some(SpritePixel {
+                    pixel,
+                    palette: (sprite.attribute & 0x03) + 4,
+                    behind_background: sprite.attribute & 0x20 != 0,
+                    sprite0: i == 0 && self.sprites.sprite0_in_slots,
+                })
+            })
+    }
+}
+
+impl Savestate for SpriteSlot {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(self.x);
+        state.write(self.attribute);
+        state.write(self.pattern_low);
+        state.write(self.pattern_high);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        self.x = state.read()?;
+        self.attribute = state.read()?;
+        self.pattern_low = state.read()?;
+        self.pattern_high = state.read()?;
+        Ok(())
+    }
+}
+
+impl Savestate for Sprites {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.secondary_oam);
+        state.write(self.found);
+        state.write(self.sprite0_found);
+        for slot in &self.slots {
+            slot.save_state(state);
+        }
+        state.write(self.slot_count);
+        state.write(self.sprite0_in_slots);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()> {
+        state.read_bytes_into(&mut self.secondary_oam)?;
+        self.found = state.read()?;
+        self.sprite0_found = state.read()?;
+        for slot in &mut self.slots {
+            slot.load_state(state)?;
+        }
+        self.slot_count = state.read()?;
+        self.sprite0_in_slots = state.read()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::system::nes::file::Region;
+
+/// The region to emulate, `Auto` follows the NES 2.0 header or the game database entry of the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegionSetting {
+    #[default]
+    Auto,
+    NTSC,
+    PAL,
+    Dendy,
+}
+
+impl RegionSetting {
+    /// The region to run a ROM of the given region in, multi-region ROMs run as NTSC
+    pub fn resolve(self, rom_region: Option<Region>) -> Region {
+        match self {
+            RegionSetting::Auto => match rom_region {
+                Some(Region::PAL) => Region::PAL,
+                Some(Region::Dendy) => Region::Dendy,
+                _ => Region::NTSC,
+            },
+            RegionSetting::NTSC => Region::NTSC,
+            RegionSetting::PAL => Region::PAL,
+            RegionSetting::Dendy => Region::Dendy,
+        }
+    }
+}
+
+impl Region {
+    /// Master clock in Hz
+    pub fn master_clock(self) -> u32 {
+        match self {
+            Region::PAL | Region::Dendy => 26_601_712,
+            _ => 21_477_272,
+        }
+    }
+
+    /// Master clock cycles per CPU cycle
+    pub fn cpu_divider(self) -> u32 {
+        match self {
+            Region::PAL => 16,
+            Region::Dendy => 15,
+            _ => 12,
+        }
+    }
+
+    /// Master clock cycles per PPU dot
+    pub fn ppu_divider(self) -> u32 {
+        match self {
+            Region::PAL | Region::Dendy => 5,
+            _ => 4,
+        }
+    }
+
+    /// CPU clock in Hz
+    pub fn cpu_clock(self) -> u32 {
+        (self.master_clock() as f64 / self.cpu_divider() as f64).round() as u32
+    }
+
+    pub fn scanlines_per_frame(self) -> u16 {
+        match self {
+            Region::PAL | Region::Dendy => 312,
+            _ => 262,
+        }
+    }
+
+    /// The scanline on which vblank starts. Dendy keeps the 20 vblank lines of NTSC and adds
+    /// its extra 50 lines after the picture instead.
+    pub fn vblank_scanline(self) -> u16 {
+        match self {
+            Region::Dendy => 291,
+            _ => 241,
+        }
+    }
+
+    /// Only the 2C02 shortens odd frames by a dot while rendering
+    pub fn skips_odd_frame_dot(self) -> bool {
+        !matches!(self, Region::PAL | Region::Dendy)
+    }
+
+    /// The 2C07 and Dendy PPUs swap the red and green emphasis bits
+    pub fn swaps_emphasis(self) -> bool {
+        matches!(self, Region::PAL | Region::Dendy)
+    }
+}
@@ -0,0 +1,205 @@
+use std::mem::size_of;
+use anyhow::bail;
+use log::debug;
+
+const MAGIC: &[u8; 8] = b"NESSESTA";
+/// Bumped whenever the fields of a section change. States of newer versions are refused, older ones
+/// are read by checking `StateReader::version` for the fields they have.
+pub const STATE_VERSION: u16 = 1;
+
+/// A piece of the machine that can be written to and restored from a save state
+pub trait Savestate {
+    fn save_state(&self, state: &mut StateWriter);
+
+    fn load_state(&mut self, state: &mut StateReader) -> anyhow::Result<()>;
+}
+
+/// A value stored little endian in a save state
+pub trait StateValue: Sized {
+    fn write(self, data: &mut Vec<u8>);
+
+    fn read(state: &mut StateReader) -> anyhow::Result<Self>;
+}
+
+macro_rules! state_value {
+    ($($ty:ty),*) => {
+        $(impl StateValue for $ty {
+            fn write(self, data: &mut Vec<u8>) {
+                data.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read(state: &mut StateReader) -> anyhow::Result<Self> {
+                Ok(<$ty>::from_le_bytes(state.take(size_of::<$ty>())?.try_into()?))
+            }
+        })*
+    };
+}
+
+state_value!(u8, u16, u32, u64, i8, i16, i32, f32, f64);
+
+impl StateValue for bool {
+    fn write(self, data: &mut Vec<u8>) {
+        data.push(self as u8);
+    }
+
+    fn read(state: &mut StateReader) -> anyhow::Result<Self> {
+        Ok(state.read::<u8>()? != 0)
+    }
+}
+
+/// Stored as 64 bits, so states don't depend on the host
+impl StateValue for usize {
+    fn write(self, data: &mut Vec<u8>) {
+        (self as u64).write(data);
+    }
+
+    fn read(state: &mut StateReader) -> anyhow::Result<Self> {
+        Ok(usize::try_from(state.read::<u64>()?)?)
+    }
+}
+
+impl<T: StateValue> StateValue for Option<T> {
+    fn write(self, data: &mut Vec<u8>) {
+        self.is_some().write(data);
+        if let Some(value) = self {
+            value.write(data);
+        }
+    }
+
+    fn read(state: &mut StateReader) -> anyhow::Result<Self> {
+        if state.read::<bool>()? { Ok(Some(state.read()?)) } else { Ok(None) }
+    }
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { data: vec![] }
+    }
+
+    pub fn write<T: StateValue>(&mut self, value: T) {
+        value.write(&mut self.data);
+    }
+
+    /// Writes a length prefixed block of bytes
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// The version the state was saved with
+    pub version: u16,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8], version: u16) -> Self {
+        Self { data, position: 0, version }
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let Some(bytes) = self.data.get(self.position..self.position + len) else {
+            bail!("Save state is truncated");
+        };
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub fn read<T: StateValue>(&mut self) -> anyhow::Result<T> {
+        T::read(self)
+    }
+
+    /// Reads a block written by `write_bytes`
+    pub fn read_bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.read::<u32>()? as usize;
+        self.take(len)
+    }
+
+    /// Reads a block written by `write_bytes` into a buffer of the same size
+    pub fn read_bytes_into(&mut self, target: &mut [u8]) -> anyhow::Result<()> {
+        let bytes = self.read_bytes()?;
+        if bytes.len() != target.len() {
+            bail!("Save state holds {} bytes where {} were expected", bytes.len(), target.len());
+        }
+        target.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Layout: magic, version, CRC32 of the ROM, then sections of a 4 byte tag, a 32-bit length and
+/// their data. Sections of unknown tags are skipped, so new hardware can add its own.
+pub struct StateFile<'a> {
+    pub version: u16,
+    pub rom_checksum: u32,
+    sections: Vec<(&'a [u8], &'a [u8])>,
+}
+
+impl<'a> StateFile<'a> {
+    pub fn write(rom_checksum: u32, sections: &[(&[u8; 4], &dyn Savestate)]) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.data.extend_from_slice(MAGIC);
+        state.write(STATE_VERSION);
+        state.write(rom_checksum);
+
+        for (tag, section) in sections {
+            let mut section_state = StateWriter::new();
+            section.save_state(&mut section_state);
+            state.data.extend_from_slice(*tag);
+            state.write_bytes(&section_state.data);
+        }
+        state.into_bytes()
+    }
+
+    pub fn parse(data: &'a [u8]) -> anyhow::Result<Self> {
+        if !data.starts_with(MAGIC) {
+            bail!("Not a save state");
+        }
+
+        let mut reader = StateReader::new(&data[MAGIC.len()..], 0);
+        let version = reader.read::<u16>()?;
+        if version > STATE_VERSION {
+            bail!("Save state version {version} is newer than the supported version {STATE_VERSION}");
+        }
+        let rom_checksum = reader.read::<u32>()?;
+
+        let mut sections = vec![];
+        while reader.position < reader.data.len() {
+            let tag = reader.take(4)?;
+            let section = reader.read_bytes()?;
+            sections.push((tag, section));
+        }
+
+        Ok(Self { version, rom_checksum, sections })
+    }
+
+    /// Restores a section. Data past the fields the target reads is ignored.
+    pub fn load_section(&self, tag: &[u8; 4], target: &mut dyn Savestate) -> anyhow::Result<()> {
+        let Some((_, data)) = self.sections.iter().find(|(section_tag, _)| *section_tag == tag) else {
+            bail!("Save state has no {} section", String::from_utf8_lossy(tag).trim_end());
+        };
+        target.load_state(&mut StateReader::new(data, self.version))
+    }
+
+    pub fn log_unknown_sections(&self, known: &[&[u8; 4]]) {
+        let tags = self.sections.iter().map(|(tag, _)| *tag);
+        for tag in tags.filter(|tag| !known.iter().any(|known| known[..] == **tag)) {
+            debug!("Skipping unknown save state section {}", String::from_utf8_lossy(tag));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, format_err};
+use log::{debug, warn};
+use crate::system::nes::file::{Mirroring, NESFile, NESFileData, NESFileHeader, Region};
+
+const UNIF_MAGIC: &[u8] = b"UNIF";
+const UNIF_HEADER_SIZE: usize = 32;
+
+/// Board names without their `NES-`/`HVC-`/`UNL-`/... prefix, mapped to the iNES mappers implementing them
+const BOARDS: &[(&str, u16)] = &[
+    ("NROM", 0),
+    ("NROM-128", 0),
+    ("NROM-256", 0),
+    ("RROM", 0),
+    ("RROM-128", 0),
+    ("SAROM", 1),
+    ("SBROM", 1),
+    ("SCROM", 1),
+    ("SC1ROM", 1),
+    ("SEROM", 1),
+    ("SFROM", 1),
+    ("SGROM", 1),
+    ("SHROM", 1),
+    ("SH1ROM", 1),
+    ("SJROM", 1),
+    ("SKROM", 1),
+    ("SLROM", 1),
+    ("SL1ROM", 1),
+    ("SL2ROM", 1),
+    ("SL3ROM", 1),
+    ("SLRROM", 1),
+    ("SNROM", 1),
+    ("SOROM", 1),
+    ("SUROM", 1),
+    ("SXROM", 1),
+    ("UNROM", 2),
+    ("UOROM", 2),
+    ("CNROM", 3),
+    ("AMROM", 7),
+    ("ANROM", 7),
+    ("AN1ROM", 7),
+    ("AOROM", 7),
+    ("GNROM", 66),
+    ("MHROM", 66),
+    ("SA-0037", 148),
+    ("NovelDiamond9999999in1", 201),
+];
+
+/// A ROM in the chunked UNIF format, which identifies its board by name instead of a mapper number.
+#[derive(Debug)]
+pub struct UNIFFile {
+    pub revision: u32,
+    pub board: String,
+    pub name: Option<String>,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub battery: bool,
+    pub region: Region,
+}
+
+impl UNIFFile {
+    pub fn new<P: AsRef<Path>>(rom_path: &P) -> anyhow::Result<Self> {
+        Self::from_bytes(&fs::read(rom_path)?)
+    }
+
+    pub fn is_unif(data: &[u8]) -> bool {
+        data.starts_with(UNIF_MAGIC)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if !Self::is_unif(data) || data.len() < UNIF_HEADER_SIZE {
+            bail!("Invalid UNIF ROM");
+        }
+
+        let revision = u32::from_le_bytes(data[4..8].try_into()?);
+        let mut prg_chunks: [Option<&[u8]>; 16] = [None; 16];
+        let mut chr_chunks: [Option<&[u8]>; 16] = [None; 16];
+        let mut prg_checksums = [None; 16];
+        let mut chr_checksums = [None; 16];
+        let mut unif = Self {
+            revision,
+            board: String::new(),
+            name: None,
+            prg_rom: vec![],
+            chr_rom: vec![],
+            mirroring: None,
+            battery: false,
+            region: Region::NTSC,
+        };
+
+        let mut pos = UNIF_HEADER_SIZE;
+        while pos + 8 <= data.len() {
+            let id = &data[pos..pos + 4];
+            let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into()?) as usize;
+            let chunk = data
+                .get(pos + 8..pos + 8 + len)
+                .ok_or_else(|| format_err!("Truncated UNIF chunk {}", String::from_utf8_lossy(id)))?;
+            pos += 8 + len;
+
+            let index = hex_digit(id[3]);
+            match (&id[..3], index) {
+                (b"PRG", Some(i)) => prg_chunks[i] = Some(chunk),
+                (b"CHR", Some(i)) => chr_chunks[i] = Some(chunk),
+                (b"PCK", Some(i)) => prg_checksums[i] = chunk.get(..4).map(|crc| u32::from_le_bytes(crc.try_into().unwrap())),
+                (b"CCK", Some(i)) => chr_checksums[i] = chunk.get(..4).map(|crc| u32::from_le_bytes(crc.try_into().unwrap())),
+                _ => match id {
+                    b"MAPR" => unif.board = read_string(chunk),
+                    b"NAME" => unif.name = Some(read_string(chunk)),
+                    b"BATR" => unif.battery = chunk.first().is_none_or(|&b| b != 0),
+                    b"MIRR" => {
+                        unif.mirroring = match chunk.first() {
+                            Some(0) => Some(Mirroring::Horizontal),
+                            Some(1) => Some(Mirroring::Vertical),
+                            Some(2) => Some(Mirroring::SingleScreenLower),
+                            Some(3) => Some(Mirroring::SingleScreenUpper),
+                            Some(4) => Some(Mirroring::FourScreen),
+                            // Controlled by the mapper
+                            _ => None,
+                        }
+                    }
+                    b"TVCI" => {
+                        unif.region = match chunk.first() {
+                            Some(1) => Region::PAL,
+                            Some(2) => Region::MultiRegion,
+                            _ => Region::NTSC,
+                        }
+                    }
+                    _ => debug!("Ignoring UNIF chunk {}", String::from_utf8_lossy(id)),
+                },
+            }
+        }
+
+        for (i, chunk) in prg_chunks.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                verify_chunk("PRG", i, chunk, prg_checksums[i]);
+                unif.prg_rom.extend_from_slice(chunk);
+            }
+        }
+        for (i, chunk) in chr_chunks.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                verify_chunk("CHR", i, chunk, chr_checksums[i]);
+                unif.chr_rom.extend_from_slice(chunk);
+            }
+        }
+
+        if unif.board.is_empty() {
+            bail!("UNIF ROM is missing its MAPR chunk");
+        }
+        if unif.prg_rom.is_empty() {
+            bail!("UNIF ROM has no PRG data");
+        }
+
+        Ok(unif)
+    }
+
+    /// Looks up the iNES mapper implementing this board
+    pub fn mapper(&self) -> Option<u16> {
+        board_to_mapper(&self.board)
+    }
+
+    /// Converts the ROM into an `NESFile` with an NES 2.0 header describing the board
+    pub fn into_nes_file(self) -> anyhow::Result<NESFile> {
+        let mapper = self.mapper().ok_or_else(|| format_err!("Unsupported UNIF board {}", self.board))?;
+        let prg_rom = pad_to_multiple(self.prg_rom, 0x4000);
+        let chr_rom = pad_to_multiple(self.chr_rom, 0x2000);
+        let prg_units = prg_rom.len() / 0x4000;
+        let chr_units = chr_rom.len() / 0x2000;
+
+        let mut flags6 = ((mapper & 0x0F) as u8) << 4;
+        match self.mirroring {
+            Some(Mirroring::Vertical) => flags6 |= 0x01,
+            Some(Mirroring::FourScreen) => flags6 |= 0x08,
+            _ => {}
+        }
+        if self.battery {
+            flags6 |= 0x02;
+        }
+
+        let header = NESFileHeader {
+            magic_number: 0x1A53454E,
+            prg_size: prg_units as u8,
+            chr_size: chr_units as u8,
+            flags6,
+            flags7: (mapper & 0xF0) as u8 | 0x08,
+            flags8: ((mapper >> 8) & 0x0F) as u8,
+            flags9: ((chr_units >> 8) as u8 & 0x0F) << 4 | ((prg_units >> 8) as u8 & 0x0F),
+            // 8 KiB of PRG (NV)RAM and, without CHR ROM, 8 KiB of CHR RAM
+            flags10: if self.battery { 0x70 } else { 0x07 },
+            flags11: if chr_rom.is_empty() { 0x07 } else { 0x00 },
+            flags12: match self.region {
+                Region::PAL => 1,
+                Region::MultiRegion => 2,
+                _ => 0,
+            },
+            flags13: 0,
+            flags14: 0,
+            flags15: 0,
+        };
+
+        let mut rom = NESFile::from_parts(header, NESFileData { trainer: None, prg_rom, chr_rom });
+        if let Some(Mirroring::SingleScreenLower | Mirroring::SingleScreenUpper) = self.mirroring {
+            rom.mirroring_override = self.mirroring;
+        }
+        Ok(rom)
+    }
+}
+
+pub fn board_to_mapper(board: &str) -> Option<u16> {
+    let name = ["NES-", "HVC-", "UNL-", "BTL-", "BMC-", "IREM-", "KONAMI-", "TAITO-"]
+        .iter()
+        .find_map(|prefix| board.strip_prefix(prefix))
+        .unwrap_or(board);
+
+    BOARDS
+        .iter()
+        .find(|(board_name, _)| board_name.eq_ignore_ascii_case(name))
+        .map(|(_, mapper)| *mapper)
+}
+
+fn hex_digit(c: u8) -> Option<usize> {
+    (c as char).to_digit(16).map(|d| d as usize)
+}
+
+fn read_string(chunk: &[u8]) -> String {
+    let end = chunk.iter().position(|&b| b == 0).unwrap_or(chunk.len());
+    String::from_utf8_lossy(&chunk[..end]).trim().to_owned()
+}
+
+fn verify_chunk(kind: &str, index: usize, chunk: &[u8], checksum: Option<u32>) {
+    if let Some(expected) = checksum {
+        let actual = crc32fast::hash(chunk);
+        if actual != expected {
+            warn!("UNIF {kind}{index:X} CRC32 0x{actual:08X} does not match 0x{expected:08X}");
+        }
+    }
+}
+
+/// Repeats the data until it fills a whole number of iNES size units
+fn pad_to_multiple(mut data: Vec<u8>, unit: usize) -> Vec<u8> {
+    if data.is_empty() || data.len().is_multiple_of(unit) {
+        return data;
+    }
+
+    let original_len = data.len();
+    while !data.len().is_multiple_of(unit) {
+        let len = (unit - data.len() % unit).min(original_len);
+        data.extend_from_within(..len);
+    }
+    data
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use anyhow::bail;
+
+const HEADER_SIZE: u32 = 44;
+
+/// Writes mono 16-bit PCM WAV files. The header is kept up to date on every flush, so the file
+/// stays playable if emulation stops without finishing it.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    data_size: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: &P, sample_rate: u32) -> anyhow::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(writer: W, sample_rate: u32) -> anyhow::Result<Self> {
+        let mut wav = Self { writer, data_size: 0 };
+        wav.write_header(sample_rate)?;
+        Ok(wav)
+    }
+
+    fn write_header(&mut self, sample_rate: u32) -> anyhow::Result<()> {
+        let writer = &mut self.writer;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // PCM, 1 channel
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Appends samples in the range -1.0..=1.0
+    pub fn write_samples(&mut self, samples: &[f32]) -> anyhow::Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample_to_i16(*sample).to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    /// Patches the sizes in the header and flushes the samples written so far
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// A decoded WAV file, mixed down to mono
+pub struct WavFile {
+    pub sample_rate: u32,
+    /// Samples in the range -1.0..=1.0
+    pub samples: Vec<f32>,
+}
+
+impl WavFile {
+    pub fn new<P: AsRef<Path>>(path: &P) -> anyhow::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Decodes 8, 16, 24 or 32-bit integer PCM and 32-bit float WAV data
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            bail!("Invalid WAV file");
+        }
+
+        let mut format = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let id = &data[offset..offset + 4];
+            let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into()?) as usize;
+            let body = &data[offset + 8..(offset + 8 + size).min(data.len())];
+
+            match id {
+                b"fmt " if body.len() >= 16 => {
+                    let tag = u16::from_le_bytes([body[0], body[1]]);
+                    let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+                    let sample_rate = u32::from_le_bytes(body[4..8].try_into()?);
+                    let bits = u16::from_le_bytes([body[14], body[15]]) as usize;
+                    format = Some((tag, channels, sample_rate, bits));
+                }
+                b"data" => {
+                    let Some((tag, channels, sample_rate, bits)) = format else {
+                        bail!("WAV data chunk before the format chunk");
+                    };
+                    if channels == 0 {
+                        bail!("WAV file without channels");
+                    }
+                    let samples = decode_samples(body, tag, bits)?
+                        .chunks_exact(channels)
+                        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                        .collect();
+                    return Ok(Self { sample_rate, samples });
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            offset += 8 + size + (size & 0x01);
+        }
+
+        bail!("WAV file without a data chunk")
+    }
+}
+
+fn decode_samples(data: &[u8], tag: u16, bits: usize) -> anyhow::Result<Vec<f32>> {
+    // 0xFFFE is WAVE_FORMAT_EXTENSIBLE, whose subformat is assumed to match the bit depth
+    let samples = match (tag, bits) {
+        (1 | 0xFFFE, 8) => data.iter().map(|sample| (*sample as f32 - 128.0) / 128.0).collect(),
+        (1 | 0xFFFE, 16) => data.chunks_exact(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0).collect(),
+        (1 | 0xFFFE, 24) => data.chunks_exact(3).map(|sample| i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) as f32 / 2_147_483_648.0).collect(),
+        (1 | 0xFFFE, 32) => data.chunks_exact(4).map(|sample| i32::from_le_bytes(sample.try_into().unwrap()) as f32 / 2_147_483_648.0).collect(),
+        (3, 32) => data.chunks_exact(4).map(|sample| f32::from_le_bytes(sample.try_into().unwrap())).collect(),
+        _ => bail!("Unsupported WAV format {tag} with {bits} bits per sample"),
+    };
+    Ok(samples)
+}
+
+pub fn sample_to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
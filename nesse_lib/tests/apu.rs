@@ -0,0 +1,296 @@
+use nesse_lib::system::nes::apu::frame_counter::FrameCounter;
+use nesse_lib::system::nes::cpu::IRQSource;
+use nesse_lib::system::nes::file::Region;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// Runs the APU for the given CPU cycles, returning how often pulse 1's output changed
+fn pulse1_transitions(nes: &mut NES, cycles: usize) -> usize {
+    let mut transitions = 0;
+    let mut last = nes.bus.apu.pulse1.output();
+    for _ in 0..cycles {
+        nes.bus.apu.tick();
+        let output = nes.bus.apu.pulse1.output();
+        if output != last {
+            transitions += 1;
+            last = output;
+        }
+    }
+    transitions
+}
+
+#[test]
+fn test_pulse_duty_and_timer() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4015, 0x03u8).unwrap();
+    // 50% duty, constant volume 9, period 99
+    nes.bus.write(0x4000, 0xB9u8).unwrap();
+    nes.bus.write(0x4002, 99u8).unwrap();
+    nes.bus.write(0x4003, 0x08u8).unwrap();
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 254);
+
+    // A full period is 8 steps of 100 APU cycles, or 1600 CPU cycles with two transitions
+    assert_eq!(pulse1_transitions(&mut nes, 1600 * 10), 20);
+    assert!([0, 9].contains(&nes.bus.apu.pulse1.output()));
+
+    // Periods below 8 mute the channel
+    nes.bus.write(0x4002, 7u8).unwrap();
+    nes.bus.write(0x4003, 0x08u8).unwrap();
+    assert_eq!(pulse1_transitions(&mut nes, 1000), 0);
+    assert_eq!(nes.bus.apu.pulse1.output(), 0);
+}
+
+#[test]
+fn test_length_counter_and_envelope() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4015, 0x01u8).unwrap();
+    // 75% duty so the first step is high, decaying envelope with divider period 1
+    nes.bus.write(0x4000, 0xC1u8).unwrap();
+    nes.bus.write(0x4002, 0xFFu8).unwrap();
+    nes.bus.write(0x4003, 0x18u8).unwrap();
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 2);
+
+    nes.bus.apu.clock_quarter_frame();
+    assert_eq!(nes.bus.apu.pulse1.envelope.output(), 15);
+    nes.bus.apu.clock_quarter_frame();
+    nes.bus.apu.clock_quarter_frame();
+    assert_eq!(nes.bus.apu.pulse1.envelope.output(), 14);
+
+    nes.bus.apu.clock_half_frame();
+    assert!(nes.bus.apu.pulse1.output() > 0);
+    nes.bus.apu.clock_half_frame();
+    assert_eq!(nes.bus.apu.pulse1.output(), 0);
+
+    // Disabling the channel clears its length counter, loads are ignored while disabled
+    nes.bus.write(0x4003, 0x08u8).unwrap();
+    nes.bus.write(0x4015, 0x00u8).unwrap();
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 0);
+    nes.bus.write(0x4003, 0x08u8).unwrap();
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 0);
+}
+
+#[test]
+fn test_sweep_negate_differs_between_channels() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4015, 0x03u8).unwrap();
+    for base in [0x4000u16, 0x4004] {
+        nes.bus.write(base, 0x30u8).unwrap();
+        // Enabled, divider period 0, negate, shift 1
+        nes.bus.write(base + 1, 0x89u8).unwrap();
+        nes.bus.write(base + 2, 0x00u8).unwrap();
+        nes.bus.write(base + 3, 0x01u8).unwrap();
+    }
+    nes.bus.apu.clock_half_frame();
+    assert_eq!(nes.bus.apu.pulse1.timer_period, 0x100 - 0x80 - 1);
+    assert_eq!(nes.bus.apu.pulse2.timer_period, 0x100 - 0x80);
+
+    // A target period past $7FF mutes the channel even with the sweep disabled
+    nes.bus.write(0x4001, 0x01u8).unwrap();
+    nes.bus.write(0x4002, 0x00u8).unwrap();
+    nes.bus.write(0x4003, 0x06u8).unwrap();
+    nes.bus.apu.clock_half_frame();
+    assert_eq!(nes.bus.apu.pulse1.timer_period, 0x600);
+    for _ in 0..100 {
+        nes.bus.apu.tick();
+        assert_eq!(nes.bus.apu.pulse1.output(), 0);
+    }
+}
+
+#[test]
+fn test_triangle_linear_counter_and_ultrasonic_silencing() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4015, 0x04u8).unwrap();
+    // Linear counter 2, period 9
+    nes.bus.write(0x4008, 0x02u8).unwrap();
+    nes.bus.write(0x400A, 9u8).unwrap();
+    nes.bus.write(0x400B, 0x08u8).unwrap();
+
+    // The sequencer doesn't move until the linear counter is reloaded
+    for _ in 0..100 {
+        nes.bus.apu.tick();
+    }
+    assert_eq!(nes.bus.apu.triangle.output(), 15);
+    nes.bus.apu.clock_quarter_frame();
+    for _ in 0..10 * 16 {
+        nes.bus.apu.tick();
+    }
+    assert_eq!(nes.bus.apu.triangle.output(), 0);
+
+    // Once the linear counter runs out the level is held
+    nes.bus.apu.clock_quarter_frame();
+    nes.bus.apu.clock_quarter_frame();
+    let level = nes.bus.apu.triangle.output();
+    for _ in 0..100 {
+        nes.bus.apu.tick();
+    }
+    assert_eq!(nes.bus.apu.triangle.output(), level);
+
+    nes.bus.apu.triangle.silence_ultrasonic = true;
+    nes.bus.write(0x400A, 0x01u8).unwrap();
+    nes.bus.write(0x400B, 0x08u8).unwrap();
+    nes.bus.apu.clock_quarter_frame();
+    let level = nes.bus.apu.triangle.output();
+    for _ in 0..100 {
+        nes.bus.apu.tick();
+    }
+    assert_eq!(nes.bus.apu.triangle.output(), level);
+}
+
+#[test]
+fn test_noise_modes_repeat_after_their_sequence_length() {
+    let sequence = |short_mode: bool| {
+        let mut nes = NES::new();
+        nes.bus.write(0x4015, 0x08u8).unwrap();
+        nes.bus.write(0x400C, 0x3Fu8).unwrap();
+        nes.bus.write(0x400E, if short_mode { 0x80u8 } else { 0x00 }).unwrap();
+        nes.bus.write(0x400F, 0x08u8).unwrap();
+        // Period 4, one output per shift
+        (0..40000)
+            .map(|_| {
+                for _ in 0..4 {
+                    nes.bus.apu.tick();
+                }
+                nes.bus.apu.noise.output()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let short = sequence(true);
+    assert!(short.contains(&15) && short.contains(&0));
+    assert_eq!(short[..93], short[93..186]);
+    assert_ne!(short[..93], short[1..94]);
+    let long = sequence(false);
+    assert_eq!(long[..1000], long[32767..33767]);
+}
+
+#[test]
+fn test_dmc_plays_samples_by_dma() {
+    let mut nes = NES::new();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 1, 0x00, 0x00, 0xFF), &mut nes).unwrap();
+    nes.bus.write(0x4011, 0x40u8).unwrap();
+    assert_eq!(nes.bus.apu.dmc.output(), 0x40);
+
+    // Fastest rate with IRQ, sample of 17 bytes at $C000
+    nes.bus.write(0x4010, 0x8Fu8).unwrap();
+    nes.bus.write(0x4012, 0x00u8).unwrap();
+    nes.bus.write(0x4013, 0x01u8).unwrap();
+    nes.bus.write(0x4015, 0x10u8).unwrap();
+
+    // The first fetch halts the CPU for 4 cycles
+    let start = nes.bus.cycles;
+    nes.bus.tick(1);
+    assert_eq!(nes.bus.cycles - start, 5);
+    assert_eq!(nes.bus.apu.dmc.bytes_remaining, 16);
+
+    for _ in 0..17 * 8 * 54 {
+        nes.bus.tick(1);
+    }
+    // All bits are set, so the level only rises until it saturates
+    assert_eq!(nes.bus.apu.dmc.output(), 0x7E);
+    assert_eq!(nes.bus.apu.dmc.bytes_remaining, 0);
+    assert!(nes.bus.apu.dmc.irq_flag);
+    assert!(nes.bus.cpu.irq.contains(IRQSource::DMC));
+
+    // Writing $4015 acknowledges the IRQ
+    nes.bus.write(0x4015, 0x00u8).unwrap();
+    nes.bus.tick(1);
+    assert!(!nes.bus.cpu.irq.contains(IRQSource::DMC));
+}
+
+/// CPU cycles until the APU's frame IRQ flag is set
+fn cycles_until_frame_irq(nes: &mut NES, limit: u64) -> Option<u64> {
+    let start = nes.bus.cycles;
+    while nes.bus.cycles - start < limit {
+        nes.bus.tick(1);
+        if nes.bus.apu.peek_status() & 0x40 != 0 {
+            return Some(nes.bus.cycles - start);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_frame_counter_four_step_irq() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4017, 0x00u8).unwrap();
+    let first = cycles_until_frame_irq(&mut nes, 40000).unwrap();
+    assert!((29831..=29833).contains(&first));
+    assert!(nes.bus.cpu.irq.contains(IRQSource::FrameCounter));
+
+    // Reading $4015 after the flag stops being set acknowledges it, the next one comes a full sequence later
+    assert_eq!(nes.bus.read::<u8>(0x4015).unwrap() & 0x40, 0x40);
+    nes.bus.tick(3);
+    nes.bus.read::<u8>(0x4015).unwrap();
+    assert_eq!(cycles_until_frame_irq(&mut nes, 40000), Some(29830 - 3));
+
+    // The inhibit flag clears and suppresses it
+    nes.bus.write(0x4017, 0x40u8).unwrap();
+    assert_eq!(nes.bus.apu.peek_status() & 0x40, 0);
+    assert_eq!(cycles_until_frame_irq(&mut nes, 100000), None);
+    nes.bus.tick(1);
+    assert!(!nes.bus.cpu.irq.contains(IRQSource::FrameCounter));
+
+    // The 5-step sequence never raises it
+    nes.bus.write(0x4017, 0x80u8).unwrap();
+    assert_eq!(cycles_until_frame_irq(&mut nes, 100000), None);
+}
+
+/// Cycles after the sequencer reset on which `FrameCounter::tick` clocks the half frame units
+fn half_frame_cycles(region: Region, mode: u8, cycles: u32) -> Vec<u32> {
+    let mut frame_counter = FrameCounter::new();
+    frame_counter.write(mode, true);
+    for _ in 0..3 {
+        frame_counter.tick(region);
+    }
+    (1..=cycles).filter(|_| frame_counter.tick(region).half).collect()
+}
+
+#[test]
+fn test_five_step_sequence_wraps_after_its_last_step() {
+    assert_eq!(half_frame_cycles(Region::NTSC, 0x80, 80000), [14913, 37281, 37282 + 14913, 37282 + 37281]);
+    assert_eq!(half_frame_cycles(Region::PAL, 0x80, 90000), [16627, 41565, 41566 + 16627, 41566 + 41565]);
+    assert_eq!(half_frame_cycles(Region::NTSC, 0x00, 60000), [14913, 29829, 29830 + 14913, 29830 + 29829]);
+}
+
+#[test]
+fn test_reading_status_on_the_cycle_the_frame_irq_is_set_keeps_it() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4017, 0x00u8).unwrap();
+    cycles_until_frame_irq(&mut nes, 40000).unwrap();
+
+    // The flag is set on three cycles in a row
+    for _ in 0..3 {
+        assert_eq!(nes.bus.read::<u8>(0x4015).unwrap() & 0x40, 0x40);
+        assert_eq!(nes.bus.apu.peek_status() & 0x40, 0x40);
+        nes.bus.tick(1);
+    }
+    assert_eq!(nes.bus.read::<u8>(0x4015).unwrap() & 0x40, 0x40);
+    assert_eq!(nes.bus.apu.peek_status() & 0x40, 0);
+}
+
+#[test]
+fn test_frame_counter_clocks_length_counters() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4015, 0x0Fu8).unwrap();
+    nes.bus.write(0x4000, 0x10u8).unwrap();
+    // Length index 3 loads 2
+    nes.bus.write(0x4003, 0x18u8).unwrap();
+    nes.bus.write(0x400F, 0x18u8).unwrap();
+    assert_eq!(nes.bus.read::<u8>(0x4015).unwrap() & 0x0F, 0x09);
+
+    // Switching to 5-step mode immediately clocks a half frame
+    nes.bus.write(0x4017, 0x80u8).unwrap();
+    nes.bus.tick(4);
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 1);
+
+    // The half frames are at steps 2 and 5
+    for _ in 0..14913 - 1 {
+        nes.bus.tick(1);
+    }
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 1);
+    nes.bus.tick(1);
+    assert_eq!(nes.bus.apu.pulse1.length_counter.counter, 0);
+    assert_eq!(nes.bus.read::<u8>(0x4015).unwrap() & 0x0F, 0x00);
+}
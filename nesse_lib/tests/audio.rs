@@ -0,0 +1,179 @@
+use nesse_lib::system::nes::apu::Channel;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// Runs the console for the given amount of CPU cycles
+fn run_cycles(nes: &mut NES, cycles: u32) {
+    for _ in 0..cycles / 200 {
+        nes.bus.tick(200);
+    }
+}
+
+#[test]
+fn test_dacs_mix_non_linearly() {
+    let mut nes = NES::new();
+    // The triangle's sequencer powers up at 15
+    let base = nes.bus.apu.output(&[]);
+    nes.bus.write(0x4011, 64u8).unwrap();
+    let half = nes.bus.apu.output(&[]) - base;
+    nes.bus.write(0x4011, 127u8).unwrap();
+    let full = nes.bus.apu.output(&[]) - base;
+
+    assert!((full + base - 163.67 / (24329.0 / (3.0 * 15.0 + 127.0) + 100.0)).abs() < 0.0001);
+    // Higher levels add less
+    assert!(full < half * 2.0);
+    assert!(full > half);
+}
+
+#[test]
+fn test_channels_can_be_muted_soloed_and_rescaled() {
+    let mut nes = NES::new();
+    nes.bus.write(0x4011, 100u8).unwrap();
+    let mixed = nes.bus.apu.output(&[0.5]);
+    let triangle = nes.bus.apu.channel_output(Channel::Triangle);
+    let dmc = nes.bus.apu.channel_output(Channel::DMC);
+
+    nes.bus.apu.channel_control_mut(Channel::Triangle).muted = true;
+    nes.bus.apu.expansion_control_mut(0).muted = true;
+    assert_eq!(nes.bus.apu.output(&[0.5]), dmc);
+
+    // Solo wins over the other channels, mute wins over solo
+    nes.bus.apu.channel_control_mut(Channel::Triangle).solo = true;
+    assert_eq!(nes.bus.apu.output(&[0.5]), 0.0);
+    nes.bus.apu.channel_control_mut(Channel::Triangle).muted = false;
+    assert_eq!(nes.bus.apu.output(&[0.5]), triangle);
+
+    nes.bus.apu.reset_channel_controls();
+    nes.bus.apu.channel_control_mut(Channel::DMC).volume = 0.5;
+    let dmc_half = nes.bus.apu.output(&[]) - triangle;
+    nes.bus.write(0x4011, 50u8).unwrap();
+    nes.bus.apu.reset_channel_controls();
+    assert!((nes.bus.apu.output(&[]) - triangle - dmc_half).abs() < 0.0001);
+
+    // The channels themselves keep running unchanged
+    nes.bus.write(0x4011, 100u8).unwrap();
+    nes.bus.apu.channel_control_mut(Channel::DMC).muted = true;
+    assert_eq!(nes.bus.apu.channel_output(Channel::DMC), dmc);
+    nes.bus.apu.reset_channel_controls();
+    assert_eq!(nes.bus.apu.output(&[0.5]), mixed);
+}
+
+#[test]
+fn test_expansion_channels_are_controlled_separately() {
+    let mut nes = NES::new();
+    let base = nes.bus.apu.output(&[]);
+    let mixed = nes.bus.apu.output(&[0.25, 0.125]);
+    assert!((mixed - base - 0.375).abs() < 0.0001);
+
+    nes.bus.apu.expansion_control_mut(1).muted = true;
+    assert!((nes.bus.apu.output(&[0.25, 0.125]) - base - 0.25).abs() < 0.0001);
+    nes.bus.apu.expansion_control_mut(0).volume = 0.5;
+    assert!((nes.bus.apu.output(&[0.25, 0.125]) - base - 0.125).abs() < 0.0001);
+
+    // Soloing one expansion channel silences the APU and the other channels
+    nes.bus.apu.reset_channel_controls();
+    nes.bus.apu.expansion_control_mut(1).solo = true;
+    assert_eq!(nes.bus.apu.output(&[0.25, 0.125]), 0.125);
+
+    nes.bus.apu.reset_channel_controls();
+    assert!(nes.bus.apu.expansion_controls_unchanged());
+    assert_eq!(nes.bus.apu.output(&[0.25, 0.125]), mixed);
+}
+
+#[test]
+fn test_samples_follow_the_sample_rate() {
+    let mut nes = common::powered_up_nes();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 1, 0x00, 0x00, 0xEA), &mut nes).unwrap();
+    nes.reset().unwrap();
+    nes.set_sample_rate(48000);
+    nes.next_frame().unwrap();
+    nes.audio_samples();
+
+    nes.next_frame().unwrap();
+    let samples = nes.audio_samples();
+    // 48000 Hz over 60.1 frames per second
+    assert!((797..=800).contains(&samples.len()), "{} samples", samples.len());
+    assert!(nes.audio_samples().is_empty());
+
+    nes.set_sample_rate(44100);
+    run_cycles(&mut nes, 100_000);
+    // 100000 cycles are 2464.1 samples
+    assert!((2463..=2465).contains(&nes.audio_samples_i16().len()));
+}
+
+#[test]
+fn test_pulse_wave_is_resampled_at_its_pitch() {
+    let mut nes = NES::new();
+    nes.set_sample_rate(44100);
+    nes.bus.write(0x4015, 0x01u8).unwrap();
+    nes.bus.write(0x4000, 0xBFu8).unwrap();
+    // Timer 253 is 440 Hz
+    nes.bus.write(0x4002, 0xFDu8).unwrap();
+    nes.bus.write(0x4003, 0xF8u8).unwrap();
+    // Lets the high-pass filters settle
+    run_cycles(&mut nes, 180_000);
+    nes.audio_samples();
+
+    run_cycles(&mut nes, 1_789_773 / 4);
+    let samples = nes.audio_samples();
+    // The high-pass filters let the wave droop back to 0 after each edge, so only count clear swings
+    let mut high = false;
+    let mut rising_edges = 0;
+    for sample in &samples {
+        if !high && *sample > 0.05 {
+            high = true;
+            rising_edges += 1;
+        } else if high && *sample < -0.05 {
+            high = false;
+        }
+    }
+    assert!((109..=111).contains(&rising_edges), "{rising_edges} periods");
+
+    // The high-pass filters center the wave, and the band-limited steps stay in range
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    assert!(mean.abs() < 0.01);
+    assert!(samples.iter().all(|sample| sample.abs() < 0.5));
+}
+
+#[test]
+fn test_recording_writes_the_mix_and_stems() {
+    let dir = std::env::temp_dir().join(format!("nesse_recording_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("music.wav");
+
+    let mut nes = NES::new();
+    nes.set_sample_rate(48000);
+    nes.start_recording(&path, true).unwrap();
+    nes.bus.write(0x4015, 0x01u8).unwrap();
+    nes.bus.write(0x4000, 0xBFu8).unwrap();
+    nes.bus.write(0x4002, 0xFDu8).unwrap();
+    nes.bus.write(0x4003, 0xF8u8).unwrap();
+    run_cycles(&mut nes, 180_000);
+    nes.stop_recording().unwrap();
+
+    let read_samples = |name: &str| {
+        let wav = std::fs::read(dir.join(name)).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize, wav.len() - 8);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 48000);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize, wav.len() - 44);
+        wav[44..].chunks_exact(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect::<Vec<_>>()
+    };
+
+    let mix = read_samples("music.wav");
+    // 180000 cycles are 4827.2 samples
+    assert!((4826..=4828).contains(&mix.len()));
+    let pulse = read_samples("music_pulse1.wav");
+    assert_eq!(pulse.len(), mix.len());
+    assert!(pulse.iter().any(|sample| sample.abs() > 1000));
+    for silent in ["music_pulse2.wav", "music_noise.wav", "music_dmc.wav"] {
+        assert!(read_samples(silent).iter().all(|sample| *sample == 0), "{silent}");
+    }
+    // Without a cartridge there are no expansion channels
+    assert!(read_samples("music_triangle.wav").len() == mix.len());
+    assert!(!dir.join("music_fds.wav").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,26 @@
+#![allow(dead_code)]
+//! ROM images and machines shared by the integration tests.
+
+/// Builds an iNES image whose PRG ROM is filled with `prg_fill` and whose reset vector points to 0x8000.
+/// CHR ROM counts up from 0, so tests can tell which bank is mapped.
+pub fn ines_rom(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8, prg_fill: u8) -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, flags6, flags7, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    let mut prg = vec![prg_fill; prg_banks as usize * 16384];
+    let len = prg.len();
+    prg[len - 4..len - 2].copy_from_slice(&0x8000u16.to_le_bytes());
+    rom.extend_from_slice(&prg);
+    rom.extend((0..chr_banks as usize * 8192).map(|i| i as u8));
+
+    rom
+}
+
+/// A console that ran past the first vblank, after which the PPU accepts writes to all registers
+pub fn powered_up_nes() -> nesse_lib::system::nes::NES {
+    let mut nes = nesse_lib::system::nes::NES::new();
+    while !nes.bus.ppu.is_warmed_up() {
+        nes.bus.tick(1);
+    }
+    nes.bus.cpu.nmi = false;
+    nes
+}
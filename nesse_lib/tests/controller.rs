@@ -0,0 +1,89 @@
+use nesse_lib::system::nes::input::controller::Buttons;
+use nesse_lib::system::nes::NES;
+
+fn strobe(nes: &mut NES) {
+    nes.bus.write(0x4016, 0x01u8).unwrap();
+    nes.bus.write(0x4016, 0x00u8).unwrap();
+}
+
+fn read_bits(nes: &mut NES, port: u16, count: usize) -> Vec<u8> {
+    (0..count).map(|_| nes.bus.read::<u8>(port).unwrap() & 0x01).collect()
+}
+
+#[test]
+fn test_buttons_shift_out_after_strobe() {
+    let mut nes = NES::new();
+    nes.set_buttons(0, Buttons::A | Buttons::Start | Buttons::Right).unwrap();
+    nes.set_buttons(1, Buttons::B).unwrap();
+    assert!(nes.set_buttons(2, Buttons::A).is_err());
+
+    strobe(&mut nes);
+    // A, B, Select, Start, Up, Down, Left, Right, then 1s
+    assert_eq!(read_bits(&mut nes, 0x4016, 10), [1, 0, 0, 1, 0, 0, 0, 1, 1, 1]);
+    assert_eq!(read_bits(&mut nes, 0x4017, 3), [0, 1, 0]);
+
+    // The upper bits are open bus
+    assert_eq!(nes.bus.read::<u8>(0x4016).unwrap() & 0xE0, 0x40);
+    assert_eq!(nes.bus.peek::<u8>(0x4017).unwrap(), 0x40);
+
+    // Buttons pressed after the strobe are seen on the next one
+    nes.set_buttons(0, Buttons::Down).unwrap();
+    assert_eq!(read_bits(&mut nes, 0x4016, 1), [1]);
+    strobe(&mut nes);
+    assert_eq!(read_bits(&mut nes, 0x4016, 8), [0, 0, 0, 0, 0, 1, 0, 0]);
+}
+
+#[test]
+fn test_strobe_held_high_keeps_reporting_a() {
+    let mut nes = NES::new();
+    nes.set_buttons(0, Buttons::A | Buttons::B).unwrap();
+    nes.bus.write(0x4016, 0x01u8).unwrap();
+    assert_eq!(read_bits(&mut nes, 0x4016, 4), [1, 1, 1, 1]);
+
+    nes.set_buttons(0, Buttons::B).unwrap();
+    assert_eq!(read_bits(&mut nes, 0x4016, 2), [0, 0]);
+    nes.bus.write(0x4016, 0x00u8).unwrap();
+    assert_eq!(read_bits(&mut nes, 0x4016, 2), [0, 1]);
+}
+
+#[test]
+fn test_dmc_fetch_during_a_read_skips_a_bit() {
+    let mut nes = NES::new();
+    // A, Select, Up and Left
+    nes.set_buttons(0, Buttons::from_bits_truncate(0x55)).unwrap();
+    let expected = [1, 0, 1, 0, 1, 0, 1, 0];
+
+    // A looping sample at the fastest rate fetches every 432 cycles
+    nes.bus.write(0x4010, 0x4Fu8).unwrap();
+    nes.bus.write(0x4013, 0x01u8).unwrap();
+    nes.bus.write(0x4015, 0x10u8).unwrap();
+
+    let mut glitches = 0;
+    for _ in 0..200 {
+        strobe(&mut nes);
+        let mut bits = vec![];
+        let mut fetch_at = None;
+        for index in 0..8 {
+            bits.push(nes.bus.read::<u8>(0x4016).unwrap() & 0x01);
+            // Single cycle steps, so every read is on the last cycle
+            let start = nes.bus.cycles;
+            nes.bus.tick(1);
+            if nes.bus.cycles - start > 1 {
+                fetch_at = Some(index);
+            }
+        }
+
+        match fetch_at {
+            Some(index) if index < 7 => {
+                // The repeated read drops the next button
+                let mut glitched = expected[..=index].to_vec();
+                glitched.extend_from_slice(&expected[index + 2..]);
+                glitched.push(1);
+                assert_eq!(bits, glitched);
+                glitches += 1;
+            }
+            _ => assert_eq!(bits, expected),
+        }
+    }
+    assert!(glitches > 0);
+}
@@ -0,0 +1,167 @@
+use nesse_lib::system::nes::cpu::CPUFlagStruct;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// A console running `program` from RAM at $0200
+fn nes_with_program(program: &[u8]) -> NES {
+    let mut nes = NES::new();
+    for (offset, byte) in program.iter().enumerate() {
+        nes.bus.memory.write(0x0200 + offset as u16, *byte).unwrap();
+    }
+    nes.bus.cpu.pc = 0x0200;
+    nes
+}
+
+fn stack_byte(nes: &mut NES, sp: u8) -> u8 {
+    nes.bus.memory.read::<u8>(0x0100 | sp as u16).unwrap()
+}
+
+#[test]
+fn test_jsr_pushes_its_last_byte_and_rts_returns_after_it() {
+    // JSR $0210, then RTS at $0210
+    let mut program = vec![0x20, 0x10, 0x02];
+    program.resize(0x10, 0xEA);
+    program.push(0x60);
+    let mut nes = nes_with_program(&program);
+
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x0210);
+    assert_eq!(nes.bus.cpu.sp, 0xFD);
+    assert_eq!((stack_byte(&mut nes, 0xFF), stack_byte(&mut nes, 0xFE)), (0x02, 0x02));
+
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x0203);
+    assert_eq!(nes.bus.cpu.sp, 0xFF);
+}
+
+#[test]
+fn test_brk_pushes_the_address_after_its_padding_byte() {
+    let mut nes = nes_with_program(&[0x00, 0xFF]);
+    nes.execute().unwrap();
+    assert_eq!((stack_byte(&mut nes, 0xFF), stack_byte(&mut nes, 0xFE)), (0x02, 0x02));
+    assert_eq!(nes.bus.cpu.sp, 0xFC);
+}
+
+#[test]
+fn test_stack_lives_in_page_one() {
+    // LDA #$42, PHA
+    let mut nes = nes_with_program(&[0xA9, 0x42, 0x48]);
+    nes.execute().unwrap();
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0x01FF).unwrap(), 0x42);
+    assert_eq!(nes.bus.memory.read::<u8>(0x00FF).unwrap(), 0x00);
+}
+
+#[test]
+fn test_branches_are_relative_to_the_next_instruction() {
+    // BEQ +2 skips the NOPs, BEQ -6 jumps back to the start
+    let mut nes = nes_with_program(&[0xF0, 0x02, 0xEA, 0xEA, 0xF0, 0xFA]);
+    nes.bus.cpu.flags.insert(CPUFlagStruct::Zero);
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x0204);
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x0200);
+
+    // Not taken, PC moves past the operand
+    nes.bus.cpu.flags.remove(CPUFlagStruct::Zero);
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x0202);
+}
+
+#[test]
+fn test_php_pushes_break_and_plp_restores_the_flags() {
+    // SEC, PHP, CLC, PLP
+    let mut nes = nes_with_program(&[0x38, 0x08, 0x18, 0x28]);
+    nes.bus.cpu.flags = CPUFlagStruct::InterruptDisable;
+    nes.execute().unwrap();
+    nes.execute().unwrap();
+    assert_eq!(stack_byte(&mut nes, 0xFF), 0x35);
+
+    nes.bus.cpu.flags = CPUFlagStruct::empty();
+    nes.execute().unwrap();
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.flags, CPUFlagStruct::InterruptDisable | CPUFlagStruct::Carry);
+}
+
+#[test]
+fn test_irq_returns_with_rti_and_is_taken_again() {
+    // CLI and NOPs at $8000, RTI at $9000 which the IRQ vector points to
+    let mut rom = common::ines_rom(2, 1, 0x00, 0x00, 0xEA);
+    rom[16] = 0x58;
+    rom[16 + 0x1000] = 0x40;
+    let len = rom.len() - 8192;
+    rom[len - 2..len].copy_from_slice(&0x9000u16.to_le_bytes());
+
+    let mut nes = NES::new();
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    nes.reset().unwrap();
+    // The frame counter holds the line until $4015 is read
+    nes.bus.apu.frame_counter.irq_flag = true;
+
+    // Masked until CLI
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x8001);
+
+    for _ in 0..2 {
+        nes.execute().unwrap();
+        assert_eq!(nes.bus.cpu.pc, 0x9000);
+        assert!(nes.bus.cpu.interrupt_disable());
+        // B clear, bit 5 set, I clear as it was before the IRQ
+        assert_eq!(stack_byte(&mut nes, 0xFD), 0x20);
+        assert_eq!((stack_byte(&mut nes, 0xFF), stack_byte(&mut nes, 0xFE)), (0x80, 0x01));
+
+        // The line is still asserted, RTI runs first and unmasks it
+        nes.execute().unwrap();
+        assert_eq!((nes.bus.cpu.pc, nes.bus.cpu.sp), (0x8001, 0xFF));
+        assert!(!nes.bus.cpu.interrupt_disable());
+    }
+}
+
+#[test]
+fn test_adc_and_sbc_set_carry_and_overflow() {
+    // LDA #$50, CLC, ADC #$50, SEC, SBC #$B0
+    let mut nes = nes_with_program(&[0xA9, 0x50, 0x18, 0x69, 0x50, 0x38, 0xE9, 0xB0]);
+    for _ in 0..3 {
+        nes.execute().unwrap();
+    }
+    assert_eq!(nes.bus.cpu.a, 0xA0);
+    assert!(nes.bus.cpu.overflow() && nes.bus.cpu.negative() && !nes.bus.cpu.carry());
+
+    nes.execute().unwrap();
+    nes.execute().unwrap();
+    // A borrow clears carry, two negative operands can't overflow
+    assert_eq!(nes.bus.cpu.a, 0xF0);
+    assert!(!nes.bus.cpu.overflow() && nes.bus.cpu.negative() && !nes.bus.cpu.carry());
+}
+
+#[test]
+fn test_indirect_indexed_addressing() {
+    // LDA #$00, STA $10, LDA #$03, STA $11, LDY #$02, LDA #$77, STA ($10),Y, LDX $0302
+    let mut nes = nes_with_program(&[0xA9, 0x00, 0x85, 0x10, 0xA9, 0x03, 0x85, 0x11, 0xA0, 0x02, 0xA9, 0x77, 0x91, 0x10, 0xAE, 0x02, 0x03]);
+    for _ in 0..8 {
+        nes.execute().unwrap();
+    }
+    assert_eq!(nes.bus.memory.read::<u8>(0x0302).unwrap(), 0x77);
+    assert_eq!(nes.bus.cpu.x, 0x77);
+    assert!(!nes.bus.cpu.zero() && !nes.bus.cpu.negative());
+}
+
+#[test]
+fn test_jmp_indirect_reads_its_pointer_within_one_page() {
+    // JMP ($03FF) takes the high byte from $0300, not $0400
+    let mut nes = nes_with_program(&[0x6C, 0xFF, 0x03]);
+    nes.bus.memory.write(0x03FF, 0x34u8).unwrap();
+    nes.bus.memory.write(0x0300, 0x12u8).unwrap();
+    nes.bus.memory.write(0x0400, 0x56u8).unwrap();
+    nes.execute().unwrap();
+    assert_eq!(nes.bus.cpu.pc, 0x1234);
+}
+
+#[test]
+fn test_unimplemented_opcodes_are_errors() {
+    let mut nes = nes_with_program(&[0x02]);
+    let error = nes.execute().unwrap_err();
+    assert!(error.to_string().contains("0x02"));
+}
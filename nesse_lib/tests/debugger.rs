@@ -0,0 +1,90 @@
+use nesse_lib::system::nes::debugger::Debugger;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+fn write_vram(nes: &mut NES, addr: u16, value: u8) {
+    nes.bus.write(0x2006, (addr >> 8) as u8).unwrap();
+    nes.bus.write(0x2006, addr as u8).unwrap();
+    nes.bus.write(0x2007, value).unwrap();
+}
+
+/// CHR RAM with tile 1 using colour 1 in its top-left pixel and colour 3 everywhere else in its top row
+fn debug_test_nes() -> NES {
+    let mut nes = common::powered_up_nes();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 0, 0x01, 0x00, 0xEA), &mut nes).unwrap();
+    write_vram(&mut nes, 0x0010, 0xFF);
+    write_vram(&mut nes, 0x0018, 0x7F);
+    write_vram(&mut nes, 0x3F00, 0x0F);
+    write_vram(&mut nes, 0x3F01, 0x16);
+    write_vram(&mut nes, 0x3F03, 0x30);
+    write_vram(&mut nes, 0x3F15, 0x1A);
+    write_vram(&mut nes, 0x3F17, 0x12);
+    nes
+}
+
+#[test]
+fn test_pattern_tables_and_palettes() {
+    let nes = debug_test_nes();
+    let debugger = Debugger::new(&nes);
+    let rgb = |color: u16| nes.bus.ppu.rgb_palette.rgba(color);
+
+    let table = debugger.pattern_table(0, 0);
+    assert_eq!((table.width, table.height), (128, 128));
+    assert_eq!(table.pixel(8, 0), rgb(0x16));
+    assert_eq!(table.pixel(9, 0), rgb(0x30));
+    assert_eq!(table.pixel(8, 1), rgb(0x0F));
+    assert_eq!(debugger.pattern_table(0, 5).pixel(9, 0), rgb(0x12));
+
+    let palettes = debugger.palettes();
+    assert_eq!(palettes.pixel(8, 0), rgb(0x16));
+    assert_eq!(palettes.pixel(5 * 8 + 3, 8 + 3), rgb(0x1A));
+}
+
+#[test]
+fn test_nametables_with_viewport() {
+    let mut nes = debug_test_nes();
+    // Vertical mirroring, so the tile shows up in the first and third nametable
+    write_vram(&mut nes, 0x2021, 0x01);
+    nes.bus.write(0x2000, 0x01u8).unwrap();
+    nes.bus.write(0x2005, 0x10u8).unwrap();
+    nes.bus.write(0x2005, 0x08u8).unwrap();
+
+    let debugger = Debugger::new(&nes);
+    let rgb = |color: u16| nes.bus.ppu.rgb_palette.rgba(color);
+    let nametables = debugger.nametables(false);
+    assert_eq!((nametables.width, nametables.height), (512, 480));
+    assert_eq!(nametables.pixel(8, 8), rgb(0x16));
+    assert_eq!(nametables.pixel(8, 240 + 8), rgb(0x16));
+    assert_eq!(nametables.pixel(256 + 8, 8), rgb(0x0F));
+
+    // The viewport starts in the second nametable, scrolled by (16, 8)
+    let nametables = debugger.nametables(true);
+    assert_eq!(nametables.pixel(256 + 16, 8), [0xFF, 0x00, 0xFF, 0xFF]);
+    // and wraps around to the first nametable on the right
+    assert_eq!(nametables.pixel(15, 8 + 100), [0xFF, 0x00, 0xFF, 0xFF]);
+    assert_eq!(nametables.pixel(256 + 17, 9), rgb(0x0F));
+}
+
+#[test]
+fn test_sprite_list() {
+    let mut nes = debug_test_nes();
+    nes.bus.write(0x2003, 0x04u8).unwrap();
+    for byte in [0x20, 0x01, 0xE1, 0x30] {
+        nes.bus.write(0x2004, byte as u8).unwrap();
+    }
+
+    let debugger = Debugger::new(&nes);
+    let rgb = |color: u16| nes.bus.ppu.rgb_palette.rgba(color);
+    let sprites = debugger.sprites();
+    assert_eq!(sprites.len(), 64);
+
+    let sprite = &sprites[1];
+    assert_eq!((sprite.x, sprite.y, sprite.tile, sprite.palette), (0x30, 0x20, 0x01, 5));
+    assert!(sprite.behind_background && sprite.flip_horizontal && sprite.flip_vertical);
+    // Flipped both ways, the top row of the tile ends up at the bottom right
+    assert_eq!(sprite.image.pixel(7, 7), rgb(0x1A));
+    assert_eq!(sprite.image.pixel(6, 7), rgb(0x12));
+    assert_eq!(sprite.image.pixel(7, 0), rgb(0x0F));
+}
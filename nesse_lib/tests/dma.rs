@@ -0,0 +1,24 @@
+use nesse_lib::system::nes::NES;
+
+#[test]
+fn test_oam_dma_copies_a_page_and_stalls_the_cpu() {
+    let mut nes = NES::new();
+    for offset in 0..0x100u16 {
+        nes.bus.write(0x0200 + offset, offset as u8 ^ 0x5A).unwrap();
+    }
+    // DMA starts at the OAM address, wrapping around
+    nes.bus.write(0x2003, 0x04u8).unwrap();
+    nes.bus.write(0x4014, 0x02u8).unwrap();
+    assert_eq!(nes.bus.ppu.oam[0x04], 0x00);
+
+    assert_eq!(nes.execute().unwrap(), 513);
+    assert_eq!(nes.bus.ppu.oam[0x04], 0x5A);
+    assert_eq!(nes.bus.ppu.oam[0x03], 0xFF ^ 0x5A);
+    assert_eq!(nes.bus.ppu.oam_addr, 0x04);
+
+    // Starting on an odd cycle takes an extra alignment cycle
+    assert_eq!(nes.bus.cycles % 2, 1);
+    nes.bus.write(0x4014, 0x02u8).unwrap();
+    assert_eq!(nes.execute().unwrap(), 514);
+    assert_eq!(nes.bus.cycles % 2, 1);
+}
@@ -0,0 +1,101 @@
+use nesse_lib::system::nes::cpu::IRQSource;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::mapper::fds::disk::{raw_to_side, side_to_raw, FDSImage, SIDE_SIZE};
+use nesse_lib::system::nes::NES;
+
+fn disk_side(file_data: &[u8]) -> Vec<u8> {
+    let mut side = vec![0x01];
+    side.extend_from_slice(b"*NINTENDO-HVC*");
+    side.resize(56, 0);
+    side.extend_from_slice(&[0x02, 0x01]);
+
+    let mut file_header = vec![0x03, 0x00, 0x00, b'T', b'E', b'S', b'T', b'F', b'I', b'L', b'E', 0x00, 0x60];
+    file_header.extend_from_slice(&(file_data.len() as u16).to_le_bytes());
+    file_header.push(0x00);
+    side.extend_from_slice(&file_header);
+    side.push(0x04);
+    side.extend_from_slice(file_data);
+
+    side.resize(SIDE_SIZE, 0);
+    side
+}
+
+fn fds_nes() -> NES {
+    let mut image = b"FDS\x1A\x02".to_vec();
+    image.resize(16, 0);
+    image.extend(disk_side(&[0xDE, 0xAD, 0xBE, 0xEF]));
+    image.extend(disk_side(&[0x12, 0x34]));
+
+    let mut nes = NES::new();
+    nes.fds_bios = Some(vec![0; 0x2000]);
+    NESLoader::load_disk_bytes(&image, None, &mut nes).unwrap();
+    nes
+}
+
+#[test]
+fn test_fds_raw_side_roundtrip() {
+    let side = disk_side(&[1, 2, 3]);
+    assert_eq!(raw_to_side(&side_to_raw(&side)), side);
+
+    let image = FDSImage::from_bytes(&side).unwrap();
+    assert_eq!(image.sides.len(), 1);
+}
+
+#[test]
+fn test_fds_side_swap() {
+    let mut nes = fds_nes();
+    nes.bus.memory.write(0x4023, 0x01u8).unwrap();
+    assert_eq!(nes.disk_side_count(), 2);
+    assert_eq!(nes.disk_side(), Some(0));
+    assert_eq!(nes.bus.memory.read::<u8>(0x4032).unwrap() & 0x01, 0);
+
+    nes.eject_disk().unwrap();
+    assert_eq!(nes.disk_side(), None);
+    assert_eq!(nes.bus.memory.read::<u8>(0x4032).unwrap() & 0x01, 1);
+
+    nes.insert_disk_side(1).unwrap();
+    assert_eq!(nes.disk_side(), Some(1));
+    // The drive reports no disk for a moment so the BIOS notices the swap
+    assert_eq!(nes.bus.memory.read::<u8>(0x4032).unwrap() & 0x01, 1);
+    assert!(nes.insert_disk_side(2).is_err());
+}
+
+#[test]
+fn test_fds_timer_irq() {
+    let mut nes = fds_nes();
+    nes.bus.memory.write(0x4023, 0x01u8).unwrap();
+    nes.bus.memory.write(0x4020, 10u8).unwrap();
+    nes.bus.memory.write(0x4021, 0u8).unwrap();
+    nes.bus.memory.write(0x4022, 0x02u8).unwrap();
+
+    nes.bus.tick(10);
+    assert!(!nes.bus.cpu.irq.contains(IRQSource::Mapper));
+    nes.bus.tick(1);
+    assert!(nes.bus.cpu.irq.contains(IRQSource::Mapper));
+
+    assert_eq!(nes.bus.memory.read::<u8>(0x4030).unwrap() & 0x01, 1);
+    assert_eq!(nes.bus.memory.read::<u8>(0x4030).unwrap() & 0x01, 0);
+}
+
+#[test]
+fn test_fds_reads_disk_blocks() {
+    let mut nes = fds_nes();
+    nes.bus.memory.write(0x4023, 0x01u8).unwrap();
+    // Motor on, read mode, start transfer at the next block
+    nes.bus.memory.write(0x4025, 0x45u8).unwrap();
+
+    let mut bytes = vec![];
+    for _ in 0..1_000_000 {
+        nes.bus.tick(1);
+        if nes.bus.memory.peek::<u8>(0x4030).unwrap() & 0x02 != 0 {
+            bytes.push(nes.bus.memory.read::<u8>(0x4031).unwrap());
+            if bytes.len() == 16 {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(bytes[0], 0x80);
+    assert_eq!(bytes[1], 0x01);
+    assert_eq!(&bytes[2..16], b"*NINTENDO-HVC*");
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use nesse_lib::system::nes::database::GameDatabase;
+use nesse_lib::system::nes::file::{Mirroring, NESFile, Region};
+
+fn sha1_hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn test_database_corrects_header() {
+    // Mapper 0, horizontal mirroring, no battery
+    let mut rom = NESFile::from_bytes(&common::ines_rom(2, 1, 0x00, 0x00, 0xEA)).unwrap();
+    assert_eq!(rom.mapper(), 0);
+    assert_eq!(rom.region(), Region::NTSC);
+    assert!(!rom.is_nes2());
+
+    let xml = format!(
+        r#"<nes20db>
+            <game>
+                <!-- Test Cart (Europe) -->
+                <prgrom size="32768" crc32="{:08X}"/>
+                <rom size="40960" crc32="{:08X}" sha1="{}"/>
+                <pcb mapper="257" submapper="2" mirroring="V" battery="1"/>
+                <prgnvram size="8192"/>
+                <chrram size="8192"/>
+                <console type="0" region="1"/>
+            </game>
+        </nes20db>"#,
+        rom.hashes.prg_crc32,
+        rom.hashes.crc32,
+        sha1_hex(&rom.hashes.sha1),
+    );
+    let database = GameDatabase::from_xml(&xml).unwrap();
+    assert_eq!(database.len(), 1);
+
+    let game = rom.apply_database(&database).unwrap();
+    assert_eq!(game.title, "Test Cart (Europe)");
+
+    assert!(rom.is_nes2());
+    assert_eq!(rom.game_title(), Some("Test Cart (Europe)"));
+    assert_eq!(rom.mapper(), 257);
+    assert_eq!(rom.submapper(), 2);
+    assert_eq!(rom.mirroring(), Mirroring::Vertical);
+    assert!(rom.has_battery());
+    assert_eq!(rom.prg_ram_size(), 0);
+    assert_eq!(rom.prg_nvram_size(), 8192);
+    assert_eq!(rom.chr_ram_size(), 8192);
+    assert_eq!(rom.region(), Region::PAL);
+    assert_eq!(rom.data.prg_rom.len(), 32768);
+}
+
+#[test]
+fn test_database_ignores_unknown_rom() {
+    let mut rom = NESFile::from_bytes(&common::ines_rom(1, 1, 0x01, 0x00, 0x00)).unwrap();
+    let database = GameDatabase::from_xml("<nes20db></nes20db>").unwrap();
+
+    assert!(rom.apply_database(&database).is_none());
+    assert_eq!(rom.game_title(), None);
+    assert_eq!(rom.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn test_database_keeps_single_screen_mirroring() {
+    let mut rom = NESFile::from_bytes(&common::ines_rom(2, 0, 0x01, 0x00, 0xEA)).unwrap();
+    let xml = format!(
+        r#"<nes20db>
+            <game>
+                <!-- Single Screen Cart -->
+                <rom size="32768" crc32="{:08X}"/>
+                <pcb mapper="7" mirroring="1"/>
+            </game>
+        </nes20db>"#,
+        rom.hashes.crc32,
+    );
+    let database = GameDatabase::from_xml(&xml).unwrap();
+
+    rom.apply_database(&database).unwrap();
+    assert_eq!(rom.mapper(), 7);
+    assert_eq!(rom.mirroring(), Mirroring::SingleScreenLower);
+}
@@ -0,0 +1,177 @@
+use nesse_lib::system::nes::input::controller::{Buttons, Controller};
+use nesse_lib::system::nes::input::four_score::{FamicomFourPlayer, FourScore};
+use nesse_lib::system::nes::input::power_pad::PowerPad;
+use nesse_lib::system::nes::input::snes_mouse::SnesMouse;
+use nesse_lib::system::nes::input::vaus::Vaus;
+use nesse_lib::system::nes::input::zapper::Zapper;
+use nesse_lib::system::nes::input::InputPort;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+fn strobe(nes: &mut NES) {
+    nes.bus.write(0x4016, 0x01u8).unwrap();
+    nes.bus.write(0x4016, 0x00u8).unwrap();
+}
+
+/// Reads a port `count` times, keeping the given bit of every read
+fn read_bits(nes: &mut NES, port: u16, bit: u8, count: usize) -> Vec<u8> {
+    (0..count).map(|_| nes.bus.read::<u8>(port).unwrap() >> bit & 0x01).collect()
+}
+
+#[test]
+fn test_zapper_senses_recently_drawn_bright_pixels() {
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Port2, Some(Box::new(Zapper::new())));
+    let zapper = nes.input_device_mut::<Zapper>(InputPort::Port2).unwrap();
+    zapper.aim = Some((100, 50));
+    zapper.trigger = true;
+
+    // White around the aim point
+    nes.bus.ppu.framebuffer[50 * 256 + 101] = 0x30;
+    nes.bus.ppu.scanline = 55;
+    nes.bus.ppu.dot = 0;
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x18, 0x10);
+
+    // Not drawn yet this frame, or too long ago
+    nes.bus.ppu.scanline = 49;
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x18, 0x18);
+    nes.bus.ppu.scanline = 80;
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x18, 0x18);
+
+    // Black isn't seen, and neither is anything while aiming off screen
+    nes.bus.ppu.scanline = 55;
+    nes.bus.ppu.framebuffer[50 * 256 + 101] = 0x0F;
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x08, 0x08);
+    nes.bus.ppu.framebuffer[50 * 256 + 101] = 0x30;
+    let zapper = nes.input_device_mut::<Zapper>(InputPort::Port2).unwrap();
+    zapper.aim = None;
+    zapper.trigger = false;
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x18, 0x08);
+}
+
+#[test]
+fn test_four_score_reports_four_players_and_its_signature() {
+    let mut nes = NES::new();
+    assert!(nes.set_buttons(2, Buttons::A).is_err());
+    nes.connect_input(InputPort::Port1, Some(Box::new(FourScore::new(0))));
+    nes.connect_input(InputPort::Port2, Some(Box::new(FourScore::new(1))));
+    for (player, buttons) in [Buttons::A, Buttons::B, Buttons::Start, Buttons::Right].into_iter().enumerate() {
+        nes.set_buttons(player, buttons).unwrap();
+    }
+
+    strobe(&mut nes);
+    let port1 = read_bits(&mut nes, 0x4016, 0, 26);
+    let port2 = read_bits(&mut nes, 0x4017, 0, 26);
+    // Player 1 and 3, then the signature, then 1s
+    assert_eq!(port1[..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(port1[8..16], [0, 0, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(port1[16..], [0, 0, 0, 1, 0, 0, 0, 0, 1, 1]);
+    assert_eq!(port2[..8], [0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(port2[8..16], [0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(port2[16..], [0, 0, 1, 0, 0, 0, 0, 0, 1, 1]);
+}
+
+#[test]
+fn test_famicom_four_player_adapter_uses_d1() {
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Expansion, Some(Box::new(FamicomFourPlayer::new())));
+    nes.set_buttons(0, Buttons::A).unwrap();
+    nes.set_buttons(2, Buttons::B).unwrap();
+    nes.set_buttons(3, Buttons::Select).unwrap();
+
+    strobe(&mut nes);
+    assert_eq!(nes.bus.read::<u8>(0x4016).unwrap() & 0x03, 0x01);
+    assert_eq!(nes.bus.read::<u8>(0x4016).unwrap() & 0x03, 0x02);
+    assert_eq!(read_bits(&mut nes, 0x4017, 1, 3), [0, 0, 1]);
+}
+
+#[test]
+fn test_vaus_shifts_out_the_inverted_knob_position() {
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Port2, Some(Box::new(Vaus::new(false))));
+    let vaus = nes.input_device_mut::<Vaus>(InputPort::Port2).unwrap();
+    vaus.position = 0b1010_0110;
+    vaus.button = true;
+
+    strobe(&mut nes);
+    assert_eq!(read_bits(&mut nes, 0x4017, 4, 8), [0, 1, 0, 1, 1, 0, 0, 1]);
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x08, 0x08);
+
+    // The Famicom version has the button on $4016 and the position on $4017
+    nes.connect_input(InputPort::Port2, Some(Box::new(Controller::new())));
+    nes.connect_input(InputPort::Expansion, Some(Box::new(Vaus::new(true))));
+    nes.input_device_mut::<Vaus>(InputPort::Expansion).unwrap().position = 0xF0;
+    strobe(&mut nes);
+    assert_eq!(nes.bus.read::<u8>(0x4016).unwrap() & 0x02, 0);
+    assert_eq!(read_bits(&mut nes, 0x4017, 1, 5), [0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_power_pad_shifts_out_twelve_buttons() {
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Port2, Some(Box::new(PowerPad::new())));
+    let pad = nes.input_device_mut::<PowerPad>(InputPort::Port2).unwrap();
+    for button in [1, 9, 12] {
+        pad.set_button(button, true);
+    }
+
+    strobe(&mut nes);
+    let reads: Vec<u8> = (0..9).map(|_| nes.bus.read::<u8>(0x4017).unwrap() & 0x18).collect();
+    let d3: Vec<u8> = reads.iter().map(|value| value >> 3 & 0x01).collect();
+    let d4: Vec<u8> = reads.iter().map(|value| value >> 4 & 0x01).collect();
+    // D3: 2, 1, 5, 9, 6, 10, 11, 7; D4: 4, 3, 12, 8
+    assert_eq!(d3, [0, 1, 0, 1, 0, 0, 0, 0, 1]);
+    assert_eq!(d4, [0, 0, 1, 0, 1, 1, 1, 1, 1]);
+}
+
+#[test]
+fn test_snes_mouse_reports_buttons_and_motion() {
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Port1, Some(Box::new(SnesMouse::new())));
+    let mouse = nes.input_device_mut::<SnesMouse>(InputPort::Port1).unwrap();
+    mouse.left = true;
+    mouse.move_by(-5, 200);
+
+    strobe(&mut nes);
+    let bits = read_bits(&mut nes, 0x4016, 0, 33);
+    let byte = |index: usize| bits[index * 8..index * 8 + 8].iter().fold(0u8, |value, bit| value << 1 | bit);
+    assert_eq!(byte(0), 0x00);
+    // Left button, sensitivity 0, signature
+    assert_eq!(byte(1), 0x41);
+    // Down by 127, the rest follows in the next report
+    assert_eq!(byte(2), 0x7F);
+    assert_eq!(byte(3), 0x85);
+    assert_eq!(bits[32], 1);
+
+    strobe(&mut nes);
+    let bits = read_bits(&mut nes, 0x4016, 0, 32);
+    assert_eq!(bits[16..24].iter().fold(0u8, |value, bit| value << 1 | bit), 73);
+
+    // Reading with the strobe high cycles the sensitivity
+    nes.bus.write(0x4016, 0x01u8).unwrap();
+    nes.bus.read::<u8>(0x4016).unwrap();
+    assert_eq!(nes.input_device_mut::<SnesMouse>(InputPort::Port1).unwrap().sensitivity, 1);
+}
+
+#[test]
+fn test_header_selects_the_input_devices() {
+    let mut nes = NES::new();
+    let mut rom = common::ines_rom(1, 1, 0x00, 0x08, 0xEA);
+    // NES 2.0 default expansion device: Zapper on $4017
+    rom[15] = 0x08;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert!(nes.input_device_mut::<Controller>(InputPort::Port1).is_some());
+    assert!(nes.input_device_mut::<Zapper>(InputPort::Port2).is_some());
+
+    rom[15] = 0x02;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert!(nes.input_device_mut::<FourScore>(InputPort::Port1).is_some());
+    assert!(nes.input_device_mut::<FourScore>(InputPort::Port2).is_some());
+
+    rom[15] = 0x10;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert!(nes.input_device_mut::<Controller>(InputPort::Port2).is_some());
+    assert!(nes.input_device_mut::<Vaus>(InputPort::Expansion).is_some());
+}
@@ -0,0 +1,112 @@
+use nesse_lib::system::nes::input::data_recorder::{DataRecorder, TapeState};
+use nesse_lib::system::nes::input::family_keyboard::{FamilyKeyboard, Key};
+use nesse_lib::system::nes::input::InputPort;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::wav::WavFile;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// Reads the whole matrix the way Family BASIC does, returning the active low D1-D4 of each half row
+fn scan(nes: &mut NES) -> Vec<u8> {
+    let mut halves = vec![];
+    nes.bus.write(0x4016, 0x05u8).unwrap();
+    for _ in 0..10 {
+        nes.bus.write(0x4016, 0x04u8).unwrap();
+        halves.push(nes.bus.read::<u8>(0x4017).unwrap() >> 1 & 0x0F);
+        nes.bus.write(0x4016, 0x06u8).unwrap();
+        halves.push(nes.bus.read::<u8>(0x4017).unwrap() >> 1 & 0x0F);
+    }
+    halves
+}
+
+#[test]
+fn test_keyboard_matrix_is_scanned_row_by_row() {
+    let mut nes = NES::new();
+    assert!(nes.set_key(Key::A, true).is_err());
+    nes.connect_input(InputPort::Expansion, Some(Box::new(FamilyKeyboard::new())));
+    nes.set_key(Key::Return, true).unwrap();
+    nes.set_key(Key::A, true).unwrap();
+    nes.set_key(Key::Space, true).unwrap();
+
+    let halves = scan(&mut nes);
+    let mut expected = [0x0F; 20];
+    // Row 0: F8, Return, [, ]
+    expected[0] = 0x0D;
+    // Row 6: F2, W, S, A
+    expected[12] = 0x07;
+    // Row 8: Down, Space, Delete, Insert
+    expected[17] = 0x0D;
+    assert_eq!(halves, expected);
+
+    nes.set_key(Key::A, false).unwrap();
+    assert_eq!(scan(&mut nes)[12], 0x0F);
+
+    // With the matrix disabled every key reads as pressed
+    nes.bus.write(0x4016, 0x00u8).unwrap();
+    assert_eq!(nes.bus.read::<u8>(0x4017).unwrap() & 0x1E, 0);
+}
+
+#[test]
+fn test_data_recorder_records_and_plays_back_tapes() {
+    let dir = std::env::temp_dir().join(format!("nesse_tape_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tape.wav");
+
+    let mut nes = NES::new();
+    nes.connect_input(InputPort::Expansion, Some(Box::new(FamilyKeyboard::new())));
+    let recorder = nes.data_recorder_mut().unwrap();
+    recorder.record();
+
+    // A square wave of 1000 cycles per level
+    for level in 0..20 {
+        nes.bus.write(0x4016, (level % 2 * 0x04) as u8).unwrap();
+        for _ in 0..1000 {
+            nes.bus.tick(1);
+        }
+    }
+    let recorder = nes.data_recorder_mut().unwrap();
+    recorder.stop();
+    recorder.save_wav(&path).unwrap();
+
+    let wav = WavFile::new(&path).unwrap();
+    assert_eq!(wav.sample_rate, 44100);
+    // 20000 cycles at 1.79 MHz
+    assert!((wav.samples.len() as i32 - 493).abs() <= 1);
+    assert!(wav.samples[10] < 0.0 && wav.samples[35] > 0.0);
+
+    let mut recorder = DataRecorder::new();
+    recorder.load_wav(&path).unwrap();
+    nes.connect_input(InputPort::Expansion, Some(Box::new(recorder)));
+    nes.data_recorder_mut().unwrap().play();
+
+    // Each level is seen for about 1000 cycles
+    let mut levels = vec![];
+    for _ in 0..210 {
+        levels.push(nes.bus.read::<u8>(0x4016).unwrap() >> 1 & 0x01);
+        for _ in 0..100 {
+            nes.bus.tick(1);
+        }
+    }
+    let edges: Vec<usize> = levels.windows(2).enumerate().filter(|(_, pair)| pair[0] != pair[1]).map(|(index, _)| index).collect();
+    assert_eq!(edges.len(), 19);
+    edges.windows(2).for_each(|pair| assert!((9..=11).contains(&(pair[1] - pair[0]))));
+    assert_eq!(nes.data_recorder_mut().unwrap().state, TapeState::Stopped);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_header_selects_the_keyboard() {
+    let mut nes = NES::new();
+    let mut rom = common::ines_rom(1, 1, 0x00, 0x08, 0xEA);
+    rom[15] = 0x23;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert!(nes.input_device_mut::<FamilyKeyboard>(InputPort::Expansion).is_some());
+    assert!(nes.data_recorder_mut().is_some());
+
+    rom[15] = 0x20;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert!(nes.set_key(Key::A, true).is_err());
+    assert!(nes.data_recorder_mut().is_some());
+}
@@ -0,0 +1,225 @@
+use std::time::Duration;
+use nesse_lib::system::nes::nsf::player::NsfPlayer;
+use nesse_lib::system::nes::nsf::NSFFile;
+
+/// INIT stores the track number to $6000, PLAY stores 0x42 to $6001
+const PROGRAM: [u8; 9] = [
+    0x8D, 0x00, 0x60, // STA $6000
+    0x60,             // RTS
+    0xA9, 0x42,       // LDA #$42
+    0x8D, 0x01, 0x60, // STA $6001
+];
+
+fn nsf(track_count: u8, bank_init: [u8; 8], data: &[u8]) -> Vec<u8> {
+    let mut nsf = vec![0; 0x80];
+    nsf[..5].copy_from_slice(b"NESM\x1A");
+    nsf[0x05] = 1;
+    nsf[0x06] = track_count;
+    nsf[0x07] = 1;
+    nsf[0x08..0x0A].copy_from_slice(&0x8000u16.to_le_bytes());
+    nsf[0x0A..0x0C].copy_from_slice(&0x8000u16.to_le_bytes());
+    nsf[0x0C..0x0E].copy_from_slice(&0x8004u16.to_le_bytes());
+    nsf[0x0E..0x12].copy_from_slice(b"Test");
+    nsf[0x70..0x78].copy_from_slice(&bank_init);
+    nsf.extend_from_slice(data);
+    nsf
+}
+
+fn program() -> Vec<u8> {
+    let mut data = PROGRAM.to_vec();
+    data.push(0x60); // RTS
+    data
+}
+
+#[test]
+fn test_nsf_calls_init_and_play() {
+    let nsf = NSFFile::from_bytes(&nsf(3, [0; 8], &program())).unwrap();
+    assert_eq!(nsf.title, "Test");
+    assert!(!nsf.is_bankswitched());
+
+    let mut player = NsfPlayer::from_nsf(nsf).unwrap();
+    player.select_track(2).unwrap();
+
+    let mut buffer = vec![0.0; 1000];
+    player.render(&mut buffer).unwrap();
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x6000).unwrap(), 2);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x6001).unwrap(), 0x42);
+
+    assert!(player.select_track(3).is_err());
+}
+
+#[test]
+fn test_nsf_play_routine_loops_and_does_arithmetic() {
+    let data = [
+        0x60, 0xEA, 0xEA, 0xEA, // INIT: RTS
+        0xA5, 0x10,             // PLAY: LDA $10
+        0x18,                   // CLC
+        0x69, 0x01,             // ADC #$01
+        0x85, 0x10,             // STA $10
+        0x4C, 0x0E, 0x80,       // JMP $800E
+        0x8D, 0x01, 0x60,       // STA $6001
+        0x60,                   // RTS
+    ];
+    let mut player = NsfPlayer::from_nsf(NSFFile::from_bytes(&nsf(1, [0; 8], &data)).unwrap()).unwrap();
+    player.select_track(0).unwrap();
+
+    let mut buffer = vec![0.0; 4000];
+    player.render(&mut buffer).unwrap();
+    let calls = player.nes.bus.memory.peek::<u8>(0x0010).unwrap();
+    assert!(calls > 0);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x6001).unwrap(), calls);
+}
+
+#[test]
+fn test_nsf_bankswitching() {
+    let mut data = vec![0; 0x3000];
+    data[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    data[PROGRAM.len()] = 0x60;
+    data[0x1000] = 0x11;
+    data[0x2000] = 0x22;
+
+    let mut player = NsfPlayer::from_nsf(NSFFile::from_bytes(&nsf(1, [0, 1, 0, 0, 0, 0, 0, 0], &data)).unwrap()).unwrap();
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x9000).unwrap(), 0x11);
+
+    player.nes.bus.memory.write(0x5FF9, 2u8).unwrap();
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x9000).unwrap(), 0x22);
+}
+
+#[test]
+fn test_nsfe_track_metadata() {
+    let chunk = |id: &[u8], data: &[u8]| {
+        let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+        chunk.extend_from_slice(id);
+        chunk.extend_from_slice(data);
+        chunk
+    };
+
+    let mut info = vec![];
+    info.extend_from_slice(&0x8000u16.to_le_bytes());
+    info.extend_from_slice(&0x8000u16.to_le_bytes());
+    info.extend_from_slice(&0x8004u16.to_le_bytes());
+    info.extend_from_slice(&[0, 0, 2, 1]);
+
+    let mut time = 90_000i32.to_le_bytes().to_vec();
+    time.extend_from_slice(&(-1i32).to_le_bytes());
+
+    let mut nsfe = b"NSFE".to_vec();
+    nsfe.extend(chunk(b"INFO", &info));
+    nsfe.extend(chunk(b"DATA", &program()));
+    nsfe.extend(chunk(b"auth", b"Title\0Artist\0Copyright\0Ripper\0"));
+    nsfe.extend(chunk(b"tlbl", b"Intro\0Boss\0"));
+    nsfe.extend(chunk(b"time", &time));
+    nsfe.extend(chunk(b"NEND", &[]));
+
+    let player = NsfPlayer::from_nsf(NSFFile::from_bytes(&nsfe).unwrap()).unwrap();
+    assert_eq!(player.nsf.artist, "Artist");
+    assert_eq!(player.track(), 1);
+    assert_eq!(player.track_count(), 2);
+    assert_eq!(player.track_title(1), Some("Boss"));
+    assert_eq!(player.track_duration(0), Some(Duration::from_secs(90)));
+    assert_eq!(player.track_duration(1), None);
+}
+
+fn expansion_player(expansion: u8) -> NsfPlayer {
+    let mut data = nsf(1, [0; 8], &program());
+    data[0x7B] = expansion;
+    NsfPlayer::from_nsf(NSFFile::from_bytes(&data).unwrap()).unwrap()
+}
+
+fn write_registers(player: &mut NsfPlayer, writes: &[(u16, u8)]) {
+    for &(addr, value) in writes {
+        player.nes.bus.memory.write(addr, value).unwrap();
+    }
+}
+
+/// The loudest output of every expansion channel over a number of CPU cycles
+fn channel_peaks(player: &mut NsfPlayer, cycles: usize) -> Vec<f32> {
+    let channel_count = player.nes.bus.memory.cartridge().unwrap().audio_channels().len();
+    let mut peaks = vec![0.0f32; channel_count];
+    for _ in 0..cycles {
+        player.nes.bus.tick(1);
+        let cartridge = player.nes.bus.memory.cartridge().unwrap();
+        for (channel, peak) in peaks.iter_mut().enumerate() {
+            *peak = peak.max(cartridge.audio_channel_output(channel).abs());
+        }
+    }
+    peaks
+}
+
+#[test]
+fn test_vrc6_audio() {
+    let mut player = expansion_player(0x01);
+    assert_eq!(player.nes.bus.memory.cartridge().unwrap().audio_channels(), ["vrc6_pulse1", "vrc6_pulse2", "vrc6_saw"]);
+
+    write_registers(&mut player, &[(0x9000, 0x7F), (0x9001, 0x40), (0x9002, 0x80), (0xB000, 0x20), (0xB001, 0x40), (0xB002, 0x80)]);
+    let peaks = channel_peaks(&mut player, 10000);
+    assert!(peaks[0] > 0.0);
+    assert_eq!(peaks[1], 0.0);
+    assert!(peaks[2] > 0.0);
+}
+
+#[test]
+fn test_vrc7_audio() {
+    let mut player = expansion_player(0x02);
+    assert_eq!(player.nes.bus.memory.cartridge().unwrap().audio_channels().len(), 6);
+
+    // Instrument 1 at full volume, keyed on in block 4
+    write_registers(&mut player, &[(0x9010, 0x10), (0x9030, 0x80), (0x9010, 0x30), (0x9030, 0x10), (0x9010, 0x20), (0x9030, 0x18)]);
+    let peaks = channel_peaks(&mut player, 40000);
+    assert!(peaks[0] > 0.01);
+    assert!(peaks[1..].iter().all(|&peak| peak == 0.0));
+
+    // Keying off releases the note
+    write_registers(&mut player, &[(0x9010, 0x20), (0x9030, 0x08)]);
+    channel_peaks(&mut player, 400000);
+    assert!(channel_peaks(&mut player, 1000)[0] < peaks[0]);
+}
+
+#[test]
+fn test_mmc5_audio_and_multiplier() {
+    let mut player = expansion_player(0x08);
+    assert_eq!(player.nes.bus.memory.cartridge().unwrap().audio_channels(), ["mmc5_pulse1", "mmc5_pulse2", "mmc5_pcm"]);
+
+    write_registers(&mut player, &[(0x5015, 0x01), (0x5000, 0xBF), (0x5002, 0x40), (0x5003, 0x08), (0x5011, 0x80)]);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x5015).unwrap() & 0x03, 0x01);
+    let peaks = channel_peaks(&mut player, 10000);
+    assert!(peaks[0] > 0.0);
+    assert_eq!(peaks[1], 0.0);
+    assert!(peaks[2] > 0.0);
+
+    write_registers(&mut player, &[(0x5205, 12), (0x5206, 30), (0x5C00, 0x55)]);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x5205).unwrap(), 104);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x5206).unwrap(), 1);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x5C00).unwrap(), 0x55);
+}
+
+#[test]
+fn test_n163_audio() {
+    let mut player = expansion_player(0x10);
+    assert_eq!(player.nes.bus.memory.cartridge().unwrap().audio_channels().len(), 8);
+
+    // A square wave in the first 4 bytes, played by channel 8 alone
+    write_registers(&mut player, &[(0xF800, 0x80), (0x4800, 0xF0), (0x4800, 0xF0), (0x4800, 0xF0), (0x4800, 0xF0)]);
+    write_registers(&mut player, &[(0xF800, 0xF8), (0x4800, 0x00), (0x4800, 0x00), (0x4800, 0x10), (0x4800, 0x00), (0x4800, 0xF8), (0x4800, 0x00), (0x4800, 0x00), (0x4800, 0x0F)]);
+
+    write_registers(&mut player, &[(0xF800, 0x80)]);
+    assert_eq!(player.nes.bus.memory.read::<u8>(0x4800).unwrap(), 0xF0);
+    assert_eq!(player.nes.bus.memory.peek::<u8>(0x4800).unwrap(), 0xF0);
+
+    let peaks = channel_peaks(&mut player, 10000);
+    assert!(peaks[7] > 0.0);
+    assert!(peaks[..7].iter().all(|&peak| peak == 0.0));
+}
+
+#[test]
+fn test_sunsoft_5b_audio() {
+    let mut player = expansion_player(0x20);
+    assert_eq!(player.nes.bus.memory.cartridge().unwrap().audio_channels(), ["5b_a", "5b_b", "5b_c"]);
+
+    // Tone A only, at full volume
+    write_registers(&mut player, &[(0xC000, 0x00), (0xE000, 0x40), (0xC000, 0x07), (0xE000, 0x3E), (0xC000, 0x08), (0xE000, 0x0F)]);
+    let peaks = channel_peaks(&mut player, 10000);
+    assert!(peaks[0] > 0.1);
+    assert_eq!(peaks[1], 0.0);
+    assert_eq!(peaks[2], 0.0);
+}
@@ -0,0 +1,42 @@
+use nesse_lib::system::nes::ppu::ntsc::{NtscFilter, NTSC_OUTPUT_WIDTH};
+
+fn pixel(output: &[u8], x: usize, y: usize) -> [u8; 4] {
+    let index = (y * NTSC_OUTPUT_WIDTH + x) * 4;
+    output[index..index + 4].try_into().unwrap()
+}
+
+#[test]
+fn test_solid_colours_decode_to_their_hue() {
+    let filter = NtscFilter::new();
+    let solid = |color: u16| filter.filter(&vec![color; 256 * 240], 0);
+
+    let output = solid(0x0F);
+    assert_eq!(output.len(), NTSC_OUTPUT_WIDTH * 240 * 4);
+    assert_eq!(pixel(&output, 140, 120), [0, 0, 0, 0xFF]);
+
+    let [r, g, b, _] = pixel(&solid(0x30), 140, 120);
+    assert!(r > 240 && g > 240 && b > 240);
+    let [r, g, b, _] = pixel(&solid(0x16), 140, 120);
+    assert!(r > 150 && g < 60 && b < 60);
+    let [r, g, b, _] = pixel(&solid(0x1A), 140, 120);
+    assert!(g > 100 && r < 60 && b < 60);
+    let [r, g, b, _] = pixel(&solid(0x12), 140, 120);
+    assert!(b > 200 && r < 100 && g < 100);
+
+    // Blue emphasis dims white towards blue
+    let [r, g, b, _] = pixel(&solid(0x30 | 0x100), 140, 120);
+    assert!(b > r && b > g);
+}
+
+#[test]
+fn test_dot_crawl_changes_artifacts_between_frames() {
+    // Alternating columns produce artifact colours that shift with the subcarrier phase
+    let framebuffer = (0..256 * 240).map(|i| if i % 2 == 0 { 0x30 } else { 0x0F }).collect::<Vec<u16>>();
+
+    let mut filter = NtscFilter::new();
+    assert_ne!(filter.filter(&framebuffer, 0), filter.filter(&framebuffer, 1));
+    assert_eq!(filter.filter(&framebuffer, 0), filter.filter(&framebuffer, 3));
+
+    filter.dot_crawl = false;
+    assert_eq!(filter.filter(&framebuffer, 0), filter.filter(&framebuffer, 1));
+}
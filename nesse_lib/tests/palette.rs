@@ -0,0 +1,41 @@
+use nesse_lib::system::nes::ppu::palette::Palette;
+
+mod common;
+
+#[test]
+fn test_pal_files_with_64_or_512_entries() {
+    let data = (0..64 * 3).map(|i| i as u8).collect::<Vec<_>>();
+    let palette = Palette::from_bytes(&data).unwrap();
+    assert_eq!(palette.rgb(0x01), [3, 4, 5]);
+    // Red emphasis dims green and blue
+    let [r, g, b] = palette.rgb(0x40 | 0x3F);
+    assert_eq!(r, 189);
+    assert!(g < 190 && b < 191);
+
+    let data = (0..512 * 3).map(|i| (i / 3) as u8).collect::<Vec<_>>();
+    let palette = Palette::from_bytes(&data).unwrap();
+    assert_eq!(palette.rgb(0x1FF), [0xFF; 3]);
+
+    assert!(Palette::from_bytes(&[0; 100]).is_err());
+}
+
+#[test]
+fn test_frame_converts_to_rgba_and_rgb565() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.ppu.framebuffer[0] = 0x30;
+    nes.bus.ppu.framebuffer[1] = 0x0F;
+    // Greyscale is applied to the palette index before it reaches the framebuffer
+    nes.bus.write(0x2001, 0x01u8).unwrap();
+    nes.bus.write(0x2006, 0x3Fu8).unwrap();
+    nes.bus.write(0x2006, 0x00u8).unwrap();
+    nes.bus.write(0x2007, 0x16u8).unwrap();
+    assert_eq!(nes.bus.ppu.read_palette(0x3F00), 0x10);
+
+    let rgba = nes.bus.ppu.frame_rgba();
+    assert_eq!(rgba.len(), 256 * 240 * 4);
+    assert_eq!(&rgba[..8], &[0xFF, 0xFE, 0xFF, 0xFF, 0, 0, 0, 0xFF]);
+
+    let rgb565 = nes.bus.ppu.frame_rgb565();
+    assert_eq!(rgb565[0], 0xFFFF);
+    assert_eq!(rgb565[1], 0x0000);
+}
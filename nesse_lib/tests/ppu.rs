@@ -0,0 +1,325 @@
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// Runs the bus until the PPU reaches the given scanline and dot
+fn run_to(nes: &mut NES, scanline: u16, dot: u16) {
+    while nes.bus.ppu.scanline != scanline || nes.bus.ppu.dot != dot {
+        nes.bus.ppu.tick(nes.bus.memory.cartridge_mut());
+    }
+}
+
+#[test]
+fn test_ppu_data_reads_are_buffered() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.write(0x2006, 0x21u8).unwrap();
+    nes.bus.write(0x2006, 0x08u8).unwrap();
+    nes.bus.write(0x2007, 0xABu8).unwrap();
+    nes.bus.write(0x2007, 0xCDu8).unwrap();
+
+    // Reading $2002 resets the write toggle, so $2006 starts with the high byte again
+    nes.bus.write(0x2006, 0x21u8).unwrap();
+    nes.bus.read::<u8>(0x2002).unwrap();
+    nes.bus.write(0x2006, 0x21u8).unwrap();
+    nes.bus.write(0x2006, 0x08u8).unwrap();
+
+    nes.bus.read::<u8>(0x2007).unwrap();
+    assert_eq!(nes.bus.read::<u8>(0x2007).unwrap(), 0xAB);
+    assert_eq!(nes.bus.read::<u8>(0x2007).unwrap(), 0xCD);
+}
+
+#[test]
+fn test_ppu_scroll_and_address_share_the_loopy_registers() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.write(0x2000, 0x03u8).unwrap();
+    nes.bus.write(0x2005, 0x7Du8).unwrap();
+    nes.bus.write(0x2005, 0x5Eu8).unwrap();
+    assert_eq!(nes.bus.ppu.t, 0x6D6F);
+    assert_eq!(nes.bus.ppu.x, 0x05);
+    assert!(!nes.bus.ppu.w);
+
+    // Increment by 32 when PPUCTRL bit 2 is set
+    nes.bus.write(0x2000, 0x04u8).unwrap();
+    nes.bus.write(0x2006, 0x20u8).unwrap();
+    nes.bus.write(0x2006, 0x00u8).unwrap();
+    nes.bus.write(0x2007, 0x00u8).unwrap();
+    assert_eq!(nes.bus.ppu.v, 0x2020);
+}
+
+#[test]
+fn test_vblank_raises_nmi_when_enabled() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+
+    run_to(&mut nes, 241, 2);
+    assert!(nes.bus.ppu.take_nmi());
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0x80);
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0);
+
+    // Enabling NMI while the vblank flag is still set raises another NMI
+    nes.bus.ppu.tick(None);
+    run_to(&mut nes, 241, 2);
+    nes.bus.write(0x2000, 0x00u8).unwrap();
+    nes.bus.ppu.take_nmi();
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+    assert!(nes.bus.ppu.take_nmi());
+}
+
+fn write_vram(nes: &mut NES, addr: u16, value: u8) {
+    nes.bus.write(0x2006, (addr >> 8) as u8).unwrap();
+    nes.bus.write(0x2006, addr as u8).unwrap();
+    nes.bus.write(0x2007, value).unwrap();
+}
+
+fn read_vram(nes: &mut NES, addr: u16) -> u8 {
+    nes.bus.write(0x2006, (addr >> 8) as u8).unwrap();
+    nes.bus.write(0x2006, addr as u8).unwrap();
+    nes.bus.read::<u8>(0x2007).unwrap();
+    nes.bus.read::<u8>(0x2007).unwrap()
+}
+
+#[test]
+fn test_nametable_mirroring_follows_the_cartridge() {
+    let mut nes = common::powered_up_nes();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 1, 0x00, 0x00, 0xEA), &mut nes).unwrap();
+    write_vram(&mut nes, 0x2005, 0x11);
+    assert_eq!(read_vram(&mut nes, 0x2405), 0x11);
+    assert_eq!(read_vram(&mut nes, 0x2805), 0x00);
+    // $3000-$3EFF mirrors $2000-$2EFF
+    assert_eq!(read_vram(&mut nes, 0x3005), 0x11);
+
+    let mut nes = common::powered_up_nes();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 1, 0x01, 0x00, 0xEA), &mut nes).unwrap();
+    write_vram(&mut nes, 0x2005, 0x22);
+    assert_eq!(read_vram(&mut nes, 0x2805), 0x22);
+    assert_eq!(read_vram(&mut nes, 0x2405), 0x00);
+
+    // Pattern tables come from CHR ROM
+    assert_eq!(read_vram(&mut nes, 0x0123), 0x23);
+}
+
+#[test]
+fn test_palette_backdrop_entries_are_aliased() {
+    let mut nes = common::powered_up_nes();
+    write_vram(&mut nes, 0x3F10, 0x2A);
+    write_vram(&mut nes, 0x3F05, 0x16);
+    assert_eq!(nes.bus.ppu.read_palette(0x3F00), 0x2A);
+    assert_eq!(nes.bus.ppu.read_palette(0x3F25), 0x16);
+    assert_eq!(nes.bus.ppu.read_palette(0x3F15), 0x00);
+}
+
+/// A CHR RAM cartridge whose tile 1 is solid colour 1, with tile 1 down the left column of the first nametable
+fn background_test_nes() -> NES {
+    let mut nes = common::powered_up_nes();
+    NESLoader::load_rom_bytes(&common::ines_rom(1, 0, 0x00, 0x00, 0xEA), &mut nes).unwrap();
+    for row in 0..8 {
+        write_vram(&mut nes, 0x0010 + row, 0xFF);
+    }
+    for tile_row in 0..30 {
+        write_vram(&mut nes, 0x2000 + tile_row * 32, 0x01);
+    }
+    write_vram(&mut nes, 0x3F00, 0x0F);
+    write_vram(&mut nes, 0x3F01, 0x30);
+
+    nes.bus.write(0x2006, 0x00u8).unwrap();
+    nes.bus.write(0x2006, 0x00u8).unwrap();
+    nes.bus.write(0x2001, 0x0Au8).unwrap();
+    nes
+}
+
+#[test]
+fn test_background_renders_tiles_with_fine_scroll() {
+    let mut nes = background_test_nes();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    run_to(&mut nes, 261, 0);
+    run_to(&mut nes, 240, 0);
+
+    let framebuffer = &nes.bus.ppu.framebuffer;
+    assert_eq!(&framebuffer[..9], &[0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x0F]);
+    assert_eq!(framebuffer[239 * 256 + 7], 0x30);
+
+    // Scrolling 3 pixels to the right
+    nes.bus.write(0x2005, 0x03u8).unwrap();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    run_to(&mut nes, 261, 0);
+    run_to(&mut nes, 240, 0);
+    assert_eq!(nes.bus.ppu.framebuffer[4], 0x30);
+    assert_eq!(nes.bus.ppu.framebuffer[5], 0x0F);
+}
+
+#[test]
+fn test_mid_frame_scroll_split() {
+    let mut nes = background_test_nes();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    run_to(&mut nes, 261, 0);
+
+    // Like a status bar split, change the horizontal scroll halfway down the screen
+    run_to(&mut nes, 120, 0);
+    nes.bus.write(0x2005, 0x04u8).unwrap();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    run_to(&mut nes, 240, 0);
+
+    let framebuffer = &nes.bus.ppu.framebuffer;
+    assert_eq!(framebuffer[50 * 256 + 5], 0x30);
+    assert_eq!(framebuffer[200 * 256 + 3], 0x30);
+    assert_eq!(framebuffer[200 * 256 + 5], 0x0F);
+}
+
+fn write_oam(nes: &mut NES, sprite: u8, bytes: [u8; 4]) {
+    nes.bus.write(0x2003, sprite * 4).unwrap();
+    for byte in bytes {
+        nes.bus.write(0x2004, byte).unwrap();
+    }
+}
+
+#[test]
+fn test_sprites_render_with_priority_and_sprite0_hit() {
+    let mut nes = background_test_nes();
+    write_vram(&mut nes, 0x3F11, 0x16);
+    write_vram(&mut nes, 0x0000, 0x00);
+    // Sprite 0 overlaps the background column, sprite 1 is behind it, sprite 2 is flipped over the backdrop
+    write_oam(&mut nes, 0, [49, 0x01, 0x00, 4]);
+    write_oam(&mut nes, 1, [99, 0x01, 0x20, 2]);
+    write_oam(&mut nes, 2, [149, 0x01, 0x40, 100]);
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    nes.bus.write(0x2005, 0x00u8).unwrap();
+    nes.bus.write(0x2001, 0x1Eu8).unwrap();
+
+    run_to(&mut nes, 261, 0);
+    run_to(&mut nes, 50, 0);
+    assert_eq!(nes.bus.ppu.peek_register(0x2002) & 0x40, 0);
+    run_to(&mut nes, 51, 0);
+    assert_eq!(nes.bus.ppu.peek_register(0x2002) & 0x40, 0x40);
+    run_to(&mut nes, 240, 0);
+
+    let framebuffer = &nes.bus.ppu.framebuffer;
+    assert_eq!(framebuffer[50 * 256 + 11], 0x16);
+    assert_eq!(framebuffer[49 * 256 + 11], 0x0F);
+    assert_eq!(framebuffer[100 * 256 + 2], 0x30);
+    assert_eq!(framebuffer[100 * 256 + 9], 0x16);
+    assert_eq!(framebuffer[150 * 256 + 100], 0x16);
+    assert_eq!(framebuffer[150 * 256 + 108], 0x0F);
+}
+
+#[test]
+fn test_more_than_eight_sprites_set_overflow() {
+    let mut nes = background_test_nes();
+    write_vram(&mut nes, 0x3F11, 0x16);
+    write_vram(&mut nes, 0x0000, 0x00);
+    for sprite in 0..9 {
+        write_oam(&mut nes, sprite, [80, 0x01, 0x00, sprite * 16]);
+    }
+    for sprite in 9..64 {
+        write_oam(&mut nes, sprite, [0xF0, 0x01, 0x00, 0]);
+    }
+    nes.bus.write(0x2001, 0x1Eu8).unwrap();
+
+    run_to(&mut nes, 261, 0);
+    run_to(&mut nes, 80, 0);
+    assert_eq!(nes.bus.ppu.peek_register(0x2002) & 0x20, 0);
+    run_to(&mut nes, 81, 0);
+    assert_eq!(nes.bus.ppu.peek_register(0x2002) & 0x20, 0x20);
+
+    // Only the first 8 sprites are drawn
+    run_to(&mut nes, 82, 0);
+    let line = &nes.bus.ppu.framebuffer[81 * 256..82 * 256];
+    assert_eq!(line[7 * 16], 0x16);
+    assert_eq!(line[8 * 16], 0x0F);
+}
+
+#[test]
+fn test_writes_are_ignored_until_the_first_vblank_ends() {
+    let mut nes = NES::new();
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+    nes.bus.write(0x2005, 0x7Du8).unwrap();
+    nes.bus.write(0x2003, 0x10u8).unwrap();
+    assert_eq!(nes.bus.ppu.ctrl.bits(), 0);
+    assert_eq!(nes.bus.ppu.t, 0);
+    assert_eq!(nes.bus.ppu.oam_addr, 0x10);
+
+    run_to(&mut nes, 261, 2);
+    assert!(nes.bus.ppu.is_warmed_up());
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+    assert_eq!(nes.bus.ppu.ctrl.bits(), 0x80);
+
+    nes.bus.ppu.reset();
+    assert_eq!(nes.bus.ppu.ctrl.bits(), 0);
+    nes.bus.write(0x2001, 0x1Eu8).unwrap();
+    assert_eq!(nes.bus.ppu.mask.bits(), 0);
+}
+
+#[test]
+fn test_odd_frames_skip_a_dot_while_rendering() {
+    let mut nes = background_test_nes();
+    let frame_length = |nes: &mut NES| {
+        run_to(nes, 0, 0);
+        let mut dots = 0;
+        loop {
+            nes.bus.ppu.tick(nes.bus.memory.cartridge_mut());
+            dots += 1;
+            if nes.bus.ppu.scanline == 0 && nes.bus.ppu.dot == 0 {
+                return (nes.bus.ppu.frame, dots);
+            }
+        }
+    };
+
+    let (frame, dots) = frame_length(&mut nes);
+    let (_, next_dots) = frame_length(&mut nes);
+    let (odd, even) = if frame % 2 == 0 { (dots, next_dots) } else { (next_dots, dots) };
+    assert_eq!(odd, 341 * 262 - 1);
+    assert_eq!(even, 341 * 262);
+
+    // Without rendering every frame is full length
+    nes.bus.write(0x2001, 0x00u8).unwrap();
+    assert_eq!(frame_length(&mut nes).1, 341 * 262);
+    assert_eq!(frame_length(&mut nes).1, 341 * 262);
+}
+
+#[test]
+fn test_reading_status_at_vblank_start_suppresses_the_nmi() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+
+    // One dot before the flag is set, it reads as clear and is never set this frame
+    run_to(&mut nes, 241, 1);
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0);
+    run_to(&mut nes, 241, 10);
+    assert!(!nes.bus.ppu.take_nmi());
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0);
+
+    // On the dot after, it reads as set but the NMI is cancelled even if the CPU already saw it
+    run_to(&mut nes, 241, 2);
+    nes.bus.cpu.nmi = true;
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0x80);
+    assert!(!nes.bus.cpu.nmi);
+    assert!(!nes.bus.ppu.take_nmi());
+
+    // Later reads leave the NMI alone
+    run_to(&mut nes, 241, 0);
+    run_to(&mut nes, 241, 5);
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x80, 0x80);
+    assert!(nes.bus.ppu.take_nmi());
+}
+
+#[test]
+fn test_io_latch_decays() {
+    let mut nes = common::powered_up_nes();
+    nes.bus.write(0x2003, 0xA5u8).unwrap();
+    assert_eq!(nes.bus.read::<u8>(0x2000).unwrap(), 0xA5);
+    assert_eq!(nes.bus.read::<u8>(0x2002).unwrap() & 0x1F, 0x05);
+
+    for _ in 0..20 {
+        run_to(&mut nes, 0, 0);
+        nes.bus.ppu.tick(None);
+    }
+    // Reading PPUSTATUS drives bits 7-5, which keeps them from decaying
+    nes.bus.read::<u8>(0x2002).unwrap();
+    for _ in 0..20 {
+        run_to(&mut nes, 0, 0);
+        nes.bus.ppu.tick(None);
+    }
+    assert_eq!(nes.bus.read::<u8>(0x2000).unwrap() & 0x1F, 0x00);
+}
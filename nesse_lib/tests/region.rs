@@ -0,0 +1,92 @@
+use nesse_lib::system::nes::file::Region;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::region::RegionSetting;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// CPU cycles from the start of one vblank to the next
+fn cycles_per_frame(nes: &mut NES) -> u64 {
+    nes.bus.write(0x2000, 0x80u8).unwrap();
+    while !nes.bus.cpu.nmi {
+        nes.bus.tick(1);
+    }
+    nes.bus.cpu.nmi = false;
+    let start = nes.bus.cycles;
+    while !nes.bus.cpu.nmi {
+        nes.bus.tick(1);
+    }
+    nes.bus.cpu.nmi = false;
+    nes.bus.cycles - start
+}
+
+#[test]
+fn test_region_timing() {
+    let mut nes = common::powered_up_nes();
+    assert_eq!(nes.bus.region, Region::NTSC);
+    assert!((cycles_per_frame(&mut nes) as f64 - 29780.5).abs() < 1.0);
+
+    nes.set_region(RegionSetting::PAL);
+    assert_eq!(nes.bus.ppu.region, Region::PAL);
+    assert_eq!(Region::PAL.cpu_clock(), 1_662_607);
+    // 312 scanlines of 341 dots at 3.2 dots per cycle
+    assert!((cycles_per_frame(&mut nes) as f64 - 33247.5).abs() < 1.0);
+
+    nes.set_region(RegionSetting::Dendy);
+    assert_eq!(Region::Dendy.vblank_scanline(), 291);
+    assert!((cycles_per_frame(&mut nes) as f64 - 35464.0).abs() < 1.0);
+}
+
+#[test]
+fn test_auto_region_follows_the_header() {
+    let mut nes = common::powered_up_nes();
+    // NES 2.0 header with the PAL timing bits in byte 12
+    let mut rom = common::ines_rom(1, 1, 0x00, 0x08, 0xEA);
+    rom[12] = 0x01;
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+    assert_eq!(nes.bus.region, Region::PAL);
+
+    nes.set_region(RegionSetting::NTSC);
+    assert_eq!(nes.bus.region, Region::NTSC);
+    nes.set_region(RegionSetting::Auto);
+    assert_eq!(nes.bus.region, Region::PAL);
+}
+
+#[test]
+fn test_pal_swaps_red_and_green_emphasis() {
+    let mut nes = common::powered_up_nes();
+    nes.set_region(RegionSetting::PAL);
+    // Red emphasis on a 2C07 is green emphasis on a 2C02
+    nes.bus.write(0x2001, 0x20u8).unwrap();
+    while nes.bus.ppu.scanline != 1 {
+        nes.bus.tick(1);
+    }
+    assert_eq!(nes.bus.ppu.framebuffer[0] >> 6, 0x02);
+}
+
+/// CPU cycles from a write to $4017 until the 4-step sequence raises the frame IRQ
+fn cycles_until_frame_irq(nes: &mut NES) -> u64 {
+    // Inhibiting clears the flag of the previous sequence
+    nes.bus.write(0x4017, 0x40u8).unwrap();
+    nes.bus.tick(4);
+    nes.bus.write(0x4017, 0x00u8).unwrap();
+    let start = nes.bus.cycles;
+    while nes.bus.apu.peek_status() & 0x40 == 0 {
+        nes.bus.tick(1);
+    }
+    nes.bus.cycles - start
+}
+
+#[test]
+fn test_dendy_apu_uses_the_pal_tables() {
+    let mut nes = NES::new();
+    let ntsc = cycles_until_frame_irq(&mut nes);
+
+    nes.set_region(RegionSetting::PAL);
+    let pal = cycles_until_frame_irq(&mut nes);
+    assert!(pal > ntsc + 3000);
+
+    nes.set_region(RegionSetting::Dendy);
+    assert_eq!(nes.bus.apu.region, Region::Dendy);
+    assert_eq!(cycles_until_frame_irq(&mut nes), pal);
+}
@@ -0,0 +1,168 @@
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::patch::{Patch, PatchFormat};
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+fn encode_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let x = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        value -= 1;
+    }
+}
+
+fn append_checksums(patch: &mut Vec<u8>, source: &[u8], target: &[u8]) {
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let patch_crc = crc32fast::hash(patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+}
+
+fn source_rom() -> Vec<u8> {
+    (0..64u8).collect()
+}
+
+#[test]
+fn test_ips_records_and_rle() {
+    let mut ips = b"PATCH".to_vec();
+    ips.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);
+    ips.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+    ips.extend_from_slice(b"EOF");
+
+    let patch = Patch::from_bytes(ips).unwrap();
+    assert_eq!(patch.format, PatchFormat::IPS);
+
+    let target = patch.apply(&source_rom()).unwrap();
+    assert_eq!(target.len(), 64);
+    assert_eq!(&target[0..4], &[0, 1, 0xAA, 0xBB]);
+    assert_eq!(&target[0x10..0x15], &[0xCC, 0xCC, 0xCC, 0xCC, 0x14]);
+}
+
+#[test]
+fn test_ups_xor_hunk() {
+    let source = source_rom();
+    let mut target = source.clone();
+    target[5] = 0xFF;
+    target[6] = 0xFE;
+
+    let mut ups = b"UPS1".to_vec();
+    encode_varint(&mut ups, source.len());
+    encode_varint(&mut ups, target.len());
+    encode_varint(&mut ups, 5);
+    ups.extend_from_slice(&[source[5] ^ 0xFF, source[6] ^ 0xFE, 0x00]);
+    append_checksums(&mut ups, &source, &target);
+
+    let patch = Patch::from_bytes(ups).unwrap();
+    assert_eq!(patch.apply(&source).unwrap(), target);
+}
+
+#[test]
+fn test_bps_actions_and_crc_validation() {
+    let source = source_rom();
+    let mut target = source[..32].to_vec();
+    target.extend_from_slice(&[0x11, 0x22]);
+    target.extend_from_slice(&source[8..16]);
+
+    let mut bps = b"BPS1".to_vec();
+    encode_varint(&mut bps, source.len());
+    encode_varint(&mut bps, target.len());
+    encode_varint(&mut bps, 0);
+    // SourceRead 32 bytes
+    encode_varint(&mut bps, 31 << 2);
+    // TargetRead 2 bytes
+    encode_varint(&mut bps, (1 << 2) | 1);
+    bps.extend_from_slice(&[0x11, 0x22]);
+    // SourceCopy 8 bytes from offset 8
+    encode_varint(&mut bps, (7 << 2) | 2);
+    encode_varint(&mut bps, 8 << 1);
+    append_checksums(&mut bps, &source, &target);
+
+    let patch = Patch::from_bytes(bps).unwrap();
+    assert_eq!(patch.format, PatchFormat::BPS);
+    assert_eq!(patch.apply(&source).unwrap(), target);
+
+    let mut wrong_source = source.clone();
+    wrong_source[0] = 0xFF;
+    assert!(patch.apply(&wrong_source).is_err());
+}
+
+#[test]
+fn test_hostile_patches_are_refused() {
+    let source = source_rom();
+    let hostile = |body: &[u8]| {
+        let mut patch = b"BPS1".to_vec();
+        encode_varint(&mut patch, source.len());
+        patch.extend_from_slice(body);
+        append_checksums(&mut patch, &source, &source);
+        Patch::from_bytes(patch).unwrap().apply(&source)
+    };
+
+    // A number that doesn't fit in 64 bits
+    assert!(hostile(&[0x00; 12]).is_err());
+
+    // A target size too large to allocate
+    let mut body = vec![];
+    encode_varint(&mut body, usize::MAX >> 1);
+    encode_varint(&mut body, 0);
+    assert!(hostile(&body).is_err());
+
+    // A SourceCopy whose end doesn't fit in an address
+    let mut body = vec![];
+    encode_varint(&mut body, source.len());
+    encode_varint(&mut body, 0);
+    encode_varint(&mut body, usize::MAX - 1);
+    encode_varint(&mut body, usize::MAX - 1);
+    assert!(hostile(&body).is_err());
+
+    // A TargetCopy repeating its own output far past the declared size
+    let mut body = vec![];
+    encode_varint(&mut body, 1);
+    encode_varint(&mut body, 0);
+    encode_varint(&mut body, 1);
+    body.push(0xAA);
+    encode_varint(&mut body, ((1 << 40) << 2) | 3);
+    encode_varint(&mut body, 0);
+    assert!(hostile(&body).is_err());
+
+    // A UPS hunk offset that overflows
+    let mut ups = b"UPS1".to_vec();
+    encode_varint(&mut ups, source.len());
+    encode_varint(&mut ups, source.len());
+    encode_varint(&mut ups, usize::MAX);
+    ups.push(0x00);
+    encode_varint(&mut ups, usize::MAX);
+    ups.push(0x00);
+    append_checksums(&mut ups, &source, &source);
+    assert!(Patch::from_bytes(ups).unwrap().apply(&source).is_err());
+}
+
+#[test]
+fn test_sibling_patch_is_applied_when_loading() {
+    let dir = std::env::temp_dir().join(format!("nesse_patch_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let rom_path = dir.join("Game.nes");
+    let rom = common::ines_rom(1, 1, 0x00, 0x00, 0xEA);
+    std::fs::write(&rom_path, &rom).unwrap();
+
+    let mut nes = NES::new();
+    assert_eq!(Patch::find_sibling(&rom_path), None);
+    NESLoader::load_rom(&rom_path, &mut nes).unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0x8000).unwrap(), 0xEA);
+
+    let mut patched = rom.clone();
+    patched[16] = 0x42;
+    std::fs::write(dir.join("Game.ips"), Patch::create_ips(&rom, &patched)).unwrap();
+    assert_eq!(Patch::find_sibling(&rom_path), Some(dir.join("Game.ips")));
+    NESLoader::load_rom(&rom_path, &mut nes).unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0x8000).unwrap(), 0x42);
+    // The ROM on disk is left alone
+    assert_eq!(std::fs::read(&rom_path).unwrap(), rom);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,155 @@
+use nesse_lib::system::nes::input::controller::Buttons;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::NES;
+
+mod common;
+
+/// MMC1 with CHR RAM, running a loop that touches RAM, PRG RAM, the APU and the PPU
+fn test_rom() -> Vec<u8> {
+    let mut rom = common::ines_rom(2, 0, 0x10, 0x00, 0xEA);
+    let program = [
+        0xE8, // INX
+        0x8A, // TXA
+        0x8D, 0x10, 0x00, // STA $0010
+        0x8D, 0x00, 0x60, // STA $6000
+        0x8D, 0x02, 0x40, // STA $4002
+        0x8D, 0x06, 0x20, // STA $2006
+        0xD0, 0xF0, // BNE $8000
+        0xA9, 0x01, // LDA #$01
+        0xD0, 0xEC, // BNE $8000
+    ];
+    rom[16..16 + program.len()].copy_from_slice(&program);
+    rom
+}
+
+fn booted_nes(rom: &[u8]) -> NES {
+    let mut nes = NES::new();
+    NESLoader::load_rom_bytes(rom, &mut nes).unwrap();
+    nes.reset().unwrap();
+    nes
+}
+
+/// What the machine looks like after running a few frames
+fn run_and_snapshot(nes: &mut NES) -> (u16, u8, u8, u8, u64, u16, Vec<u16>, u8) {
+    for _ in 0..2 {
+        nes.next_frame().unwrap();
+    }
+    let cpu = &nes.bus.cpu;
+    (
+        cpu.pc,
+        cpu.a,
+        nes.bus.peek::<u8>(0x0010).unwrap(),
+        nes.bus.peek::<u8>(0x6000).unwrap(),
+        nes.bus.cycles,
+        nes.bus.ppu.v,
+        nes.bus.ppu.framebuffer.clone(),
+        nes.bus.apu.peek_status(),
+    )
+}
+
+#[test]
+fn test_loading_a_state_replays_the_same_frames() {
+    let mut nes = booted_nes(&test_rom());
+    for _ in 0..3 {
+        nes.next_frame().unwrap();
+    }
+    nes.bus.write(0x4015, 0x01u8).unwrap();
+    nes.bus.write(0x4003, 0xF8u8).unwrap();
+
+    let state = nes.save_state().unwrap();
+    // RAM, CIRAM, OAM, CHR RAM and PRG RAM make up most of it
+    assert!(state.len() < 0x6000, "state is {} bytes", state.len());
+
+    let expected = run_and_snapshot(&mut nes);
+    nes.load_state(&state).unwrap();
+    assert_eq!(run_and_snapshot(&mut nes), expected);
+
+    // A fresh console with the same game continues the same way
+    let mut other = booted_nes(&test_rom());
+    other.load_state(&state).unwrap();
+    assert_eq!(run_and_snapshot(&mut other), expected);
+}
+
+#[test]
+fn test_states_of_other_games_are_refused() {
+    let mut nes = booted_nes(&test_rom());
+    nes.next_frame().unwrap();
+    let state = nes.save_state().unwrap();
+
+    let mut other_rom = test_rom();
+    other_rom[16 + 0x100] = 0x00;
+    let mut other = booted_nes(&other_rom);
+    let error = other.load_state(&state).unwrap_err();
+    assert!(error.to_string().contains("another game"), "{error}");
+
+    assert!(NES::new().save_state().is_err());
+}
+
+#[test]
+fn test_broken_states_leave_the_machine_untouched() {
+    let mut nes = booted_nes(&test_rom());
+    nes.next_frame().unwrap();
+    let state = nes.save_state().unwrap();
+    nes.next_frame().unwrap();
+    let pc = nes.bus.cpu.pc;
+    let cycles = nes.bus.cycles;
+
+    assert!(nes.load_state(&state[..state.len() - 16]).is_err());
+    assert!(nes.load_state(b"not a state").is_err());
+    // A version from the future
+    let mut newer = state.clone();
+    newer[8] = 0xFF;
+    assert!(nes.load_state(&newer).is_err());
+    assert_eq!((nes.bus.cpu.pc, nes.bus.cycles), (pc, cycles));
+
+    // A section whose data is cut short fails while loading, after earlier sections were restored
+    let mut short_cart = state.clone();
+    let cart = short_cart.windows(4).rposition(|tag| tag == b"CART").unwrap();
+    short_cart.truncate(cart + 4);
+    short_cart.extend_from_slice(&2u32.to_le_bytes());
+    short_cart.extend_from_slice(&[0, 0]);
+    assert!(nes.load_state(&short_cart).is_err());
+    assert_eq!((nes.bus.cpu.pc, nes.bus.cycles), (pc, cycles));
+}
+
+#[test]
+fn test_unknown_sections_are_skipped() {
+    let mut nes = booted_nes(&test_rom());
+    nes.next_frame().unwrap();
+    let mut state = nes.save_state().unwrap();
+    let cycles = nes.bus.cycles;
+
+    state.extend_from_slice(b"XTRA");
+    state.extend_from_slice(&3u32.to_le_bytes());
+    state.extend_from_slice(&[1, 2, 3]);
+    nes.next_frame().unwrap();
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.bus.cycles, cycles);
+}
+
+/// The next bits player 1's joypad shifts out
+fn read_buttons(nes: &mut NES, count: usize) -> Vec<u8> {
+    (0..count).map(|_| nes.bus.read::<u8>(0x4016).unwrap() & 0x01).collect()
+}
+
+#[test]
+fn test_state_taken_during_a_controller_read() {
+    let mut nes = booted_nes(&test_rom());
+    nes.set_buttons(0, Buttons::A | Buttons::Start | Buttons::Right).unwrap();
+    nes.bus.write(0x4016, 0x01u8).unwrap();
+    nes.bus.write(0x4016, 0x00u8).unwrap();
+    assert_eq!(read_buttons(&mut nes, 3), [1, 0, 0]);
+
+    let state = nes.save_state().unwrap();
+    let expected = read_buttons(&mut nes, 6);
+    assert_eq!(expected, [1, 0, 0, 0, 1, 1]);
+
+    // The buttons latched before the state was taken are shifted out, not the ones held now
+    nes.set_buttons(0, Buttons::empty()).unwrap();
+    nes.load_state(&state).unwrap();
+    assert_eq!(read_buttons(&mut nes, 6), expected);
+
+    let mut other = booted_nes(&test_rom());
+    other.load_state(&state).unwrap();
+    assert_eq!(read_buttons(&mut other, 6), expected);
+}
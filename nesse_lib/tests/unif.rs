@@ -0,0 +1,108 @@
+use nesse_lib::system::nes::file::Mirroring;
+use nesse_lib::system::nes::loader::NESLoader;
+use nesse_lib::system::nes::unif::{board_to_mapper, UNIFFile};
+use nesse_lib::system::nes::NES;
+
+fn push_chunk(rom: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    rom.extend_from_slice(id);
+    rom.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    rom.extend_from_slice(data);
+}
+
+fn unif_header(board: &[u8]) -> Vec<u8> {
+    let mut rom = b"UNIF".to_vec();
+    rom.extend_from_slice(&7u32.to_le_bytes());
+    rom.resize(32, 0);
+    push_chunk(&mut rom, b"MAPR", board);
+    rom
+}
+
+/// A 64 KiB UNROM board where every 16 KiB bank is filled with its bank number
+fn unrom_unif() -> Vec<u8> {
+    let mut rom = unif_header(b"NES-UNROM\0");
+    push_chunk(&mut rom, b"MIRR", &[1]);
+
+    let prg: Vec<u8> = (0..4u8).flat_map(|bank| vec![bank; 0x4000]).collect();
+    push_chunk(&mut rom, b"PCK0", &crc32fast::hash(&prg).to_le_bytes());
+    push_chunk(&mut rom, b"PRG0", &prg);
+
+    rom
+}
+
+#[test]
+fn test_unif_board_names() {
+    assert_eq!(board_to_mapper("NES-NROM-256"), Some(0));
+    assert_eq!(board_to_mapper("HVC-SKROM"), Some(1));
+    assert_eq!(board_to_mapper("NES-ANROM"), Some(7));
+    assert_eq!(board_to_mapper("UNL-SA-0037"), Some(148));
+    assert_eq!(board_to_mapper("BMC-NovelDiamond9999999in1"), Some(201));
+    // MMC3 isn't implemented
+    assert_eq!(board_to_mapper("NES-TLROM"), None);
+    assert_eq!(board_to_mapper("UNL-SOMETHING-UNKNOWN"), None);
+}
+
+#[test]
+fn test_unif_parses_into_nes_file() {
+    let unif = UNIFFile::from_bytes(&unrom_unif()).unwrap();
+    assert_eq!(unif.board, "NES-UNROM");
+    assert_eq!(unif.mapper(), Some(2));
+
+    let rom = unif.into_nes_file().unwrap();
+    assert!(rom.is_nes2());
+    assert_eq!(rom.mapper(), 2);
+    assert_eq!(rom.mirroring(), Mirroring::Vertical);
+    assert_eq!(rom.data.prg_rom.len(), 0x10000);
+    assert_eq!(rom.chr_ram_size(), 0x2000);
+}
+
+#[test]
+fn test_unif_uses_ines_mapper() {
+    let mut nes = NES::new();
+    NESLoader::load_rom_bytes(&unrom_unif(), &mut nes).unwrap();
+
+    assert_eq!(nes.bus.memory.read::<u8>(0x8000).unwrap(), 0);
+    assert_eq!(nes.bus.memory.read::<u8>(0xC000).unwrap(), 3);
+
+    nes.bus.memory.write(0x8000, 2u8).unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0x8000).unwrap(), 2);
+    assert_eq!(nes.bus.memory.read::<u8>(0xFFFF).unwrap(), 3);
+}
+
+#[test]
+fn test_unif_keeps_single_screen_mirroring() {
+    for (mirr, mirroring) in [(2, Mirroring::SingleScreenLower), (3, Mirroring::SingleScreenUpper)] {
+        let mut rom = unif_header(b"NES-AOROM\0");
+        push_chunk(&mut rom, b"MIRR", &[mirr]);
+        push_chunk(&mut rom, b"PRG0", &[0xEA; 0x8000]);
+
+        let rom = UNIFFile::from_bytes(&rom).unwrap().into_nes_file().unwrap();
+        assert_eq!(rom.mirroring(), mirroring);
+    }
+}
+
+#[test]
+fn test_unif_multicart_boards() {
+    // 32 KiB PRG and 8 KiB CHR banks filled with their bank number
+    let prg: Vec<u8> = (0..4u8).flat_map(|bank| vec![bank; 0x8000]).collect();
+    let chr: Vec<u8> = (0..4u8).flat_map(|bank| vec![bank; 0x2000]).collect();
+
+    let mut rom = unif_header(b"BMC-NovelDiamond9999999in1\0");
+    push_chunk(&mut rom, b"PRG0", &prg);
+    push_chunk(&mut rom, b"CHR0", &chr);
+    let mut nes = NES::new();
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+
+    // The address selects the game, the value is ignored
+    nes.bus.memory.write(0x8002, 0xFFu8).unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0x8000).unwrap(), 2);
+    assert_eq!(nes.bus.memory.cartridge().unwrap().ppu_peek(0x0000), 2);
+
+    let mut rom = unif_header(b"UNL-SA-0037\0");
+    push_chunk(&mut rom, b"PRG0", &prg[..0x10000]);
+    push_chunk(&mut rom, b"CHR0", &chr);
+    NESLoader::load_rom_bytes(&rom, &mut nes).unwrap();
+
+    nes.bus.memory.write(0x8000, 0x0Bu8).unwrap();
+    assert_eq!(nes.bus.memory.read::<u8>(0xFFFF).unwrap(), 1);
+    assert_eq!(nes.bus.memory.cartridge().unwrap().ppu_peek(0x1FFF), 3);
+}